@@ -0,0 +1,234 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use serde::Serialize;
+use crate::errors::CacheError;
+
+/// Extension of the cached transaction files.
+const EXTENSION: &str = "json";
+/// Share of `max_bytes` the cache is reduced to when it grows past it, so evictions are not run on every write.
+const EVICTION_TARGET: f64 = 0.9;
+
+/// Transaction cache configuration.
+/// Contains `dir`, `max_bytes` and `offline` fields.
+#[derive(Debug, Clone, Default)]
+pub struct CacheConfig {
+    /// Directory the transactions are stored in, created if it does not exist.
+    pub dir: PathBuf,
+    /// Maximum total size of the cached transactions, in bytes. The least recently used transactions are
+    /// evicted once it is exceeded.
+    pub max_bytes: Option<u64>,
+    /// Only serve transactions from the cache, never fetch them from the RPC node.
+    pub offline: bool
+}
+
+/// Counters of a `TransactionCache`.
+/// Contains `hits`, `misses`, `writes`, `evictions` and `errors` fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Transactions served from the cache.
+    pub hits: u64,
+    /// Transactions not found in the cache.
+    pub misses: u64,
+    /// Transactions stored in the cache.
+    pub writes: u64,
+    /// Transactions evicted to stay within `max_bytes`.
+    pub evictions: u64,
+    /// Cached transactions that failed to be read, written or evicted. Unreadable transactions are
+    /// treated as misses.
+    pub errors: u64
+}
+
+/// Persistent cache of raw transactions, stored as one JSON file per signature. Only finalized transactions
+/// are stored, as they can no longer change. Pages of transaction signatures are stored alongside, see
+/// `CachedSource`.
+#[derive(Debug)]
+pub struct TransactionCache {
+    config: CacheConfig,
+    /// Total size of the cached transactions, in bytes. Also serializes evictions.
+    bytes: Mutex<u64>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    writes: AtomicU64,
+    evictions: AtomicU64,
+    errors: AtomicU64
+}
+
+impl TransactionCache {
+    /// Open the cache, creating its directory if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Cache configuration.
+    pub fn open(config: CacheConfig) -> Result<Self, CacheError> {
+        if let Err(err) = fs::create_dir_all(&config.dir) {
+            return Err(CacheError::OpenError(format!("{}: {}", config.dir.display(), err)));
+        }
+        let bytes = match entries(&config.dir) {
+            Ok(entries) => entries.iter().map(|x| x.1).sum(),
+            Err(err) => return Err(CacheError::OpenError(format!("{}: {}", config.dir.display(), err)))
+        };
+        Ok(TransactionCache {
+            config,
+            bytes: Mutex::new(bytes),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            errors: AtomicU64::new(0)
+        })
+    }
+
+    /// Directory the transactions are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.config.dir
+    }
+
+    /// Whether transactions are only served from the cache.
+    pub fn offline(&self) -> bool {
+        self.config.offline
+    }
+
+    /// Return the raw transaction stored for `signature`, if any.
+    pub fn get(&self, signature: &str) -> Option<String> {
+        let path = self.path(signature);
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                // Mark the transaction as recently used, failing to do so only affects the eviction order.
+                if let Ok(file) = fs::File::options().append(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+                Some(content)
+            },
+            Err(err) => {
+                if err.kind() != ErrorKind::NotFound {
+                    self.errors.fetch_add(1, Ordering::Relaxed);
+                }
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Store the raw transaction for `signature`. The file is written next to its destination and then
+    /// renamed, so concurrent readers never see a partial transaction. Failures are counted in the stats.
+    pub fn put(&self, signature: &str, transaction: &str) {
+        if self.write(signature, transaction).is_err() {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        let mut bytes = self.bytes.lock().unwrap();
+        *bytes += transaction.len() as u64;
+        if let Some(max_bytes) = self.config.max_bytes {
+            if *bytes > max_bytes {
+                self.evict(&mut bytes, (max_bytes as f64 * EVICTION_TARGET) as u64);
+            }
+        }
+    }
+
+    fn write(&self, signature: &str, transaction: &str) -> std::io::Result<()> {
+        let path = self.path(signature);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temporary, transaction)?;
+        fs::rename(&temporary, &path)
+    }
+
+    /// Remove the least recently used transactions until the cache holds at most `target` bytes.
+    fn evict(&self, bytes: &mut u64, target: u64) {
+        let mut entries = match entries(&self.config.dir) {
+            Ok(entries) => entries,
+            Err(_) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        // Resynchronize with the files on disk, e.g. if another process uses the same directory.
+        *bytes = entries.iter().map(|x| x.1).sum();
+        entries.sort_by_key(|x| x.2);
+        for (path, size, _) in entries {
+            if *bytes <= target {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    *bytes = bytes.saturating_sub(size);
+                    self.evictions.fetch_add(1, Ordering::Relaxed);
+                },
+                Err(_) => {
+                    self.errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Remove all cached transactions.
+    pub fn clear(&self) -> Result<(), CacheError> {
+        let mut bytes = self.bytes.lock().unwrap();
+        let entries = match entries(&self.config.dir) {
+            Ok(entries) => entries,
+            Err(err) => return Err(CacheError::WriteError(format!("{}: {}", self.config.dir.display(), err)))
+        };
+        for (path, size, _) in entries {
+            if let Err(err) = fs::remove_file(&path) {
+                return Err(CacheError::WriteError(format!("{}: {}", path.display(), err)));
+            }
+            *bytes = bytes.saturating_sub(size);
+        }
+        Ok(())
+    }
+
+    /// Return the counters since the cache was opened.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Return the number of cached transactions and their total size, in bytes.
+    pub fn usage(&self) -> Result<(usize, u64), CacheError> {
+        match entries(&self.config.dir) {
+            Ok(entries) => Ok((entries.len(), entries.iter().map(|x| x.1).sum())),
+            Err(err) => Err(CacheError::ReadError(format!("{}: {}", self.config.dir.display(), err)))
+        }
+    }
+
+    /// Path of the file storing the transaction for `signature`. Files are spread over subdirectories named
+    /// after the first two characters of the signature, to keep directories small.
+    fn path(&self, signature: &str) -> PathBuf {
+        let prefix: String = signature.chars().take(2).collect();
+        self.config.dir.join(prefix).join(format!("{}.{}", signature, EXTENSION))
+    }
+}
+
+/// Return the path, size and modification time of every cached transaction in `dir`.
+fn entries(dir: &Path) -> std::io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+    let mut entries = vec![];
+    for subdir in fs::read_dir(dir)? {
+        let subdir = subdir?;
+        if !subdir.file_type()?.is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(subdir.path())? {
+            let file = file?;
+            let path = file.path();
+            if path.extension().and_then(|x| x.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let metadata = file.metadata()?;
+            entries.push((path, metadata.len(), metadata.modified()?));
+        }
+    }
+    Ok(entries)
+}
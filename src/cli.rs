@@ -0,0 +1,442 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::cache::{CacheConfig, TransactionCache};
+use crate::constants::SOL_DECIMALS;
+use crate::client::{ClientConfig, ClientOverrides, get_client, get_offline_client, parse_header};
+use crate::client::cassette::CassetteMode;
+use crate::graph::export::GraphFormat;
+use crate::graph::graph::TransferGraph;
+use crate::graph::taint::{TaintPolicy, TaintSource};
+use crate::logic::{trace_transfers, TraceBudget, TraceConfig, TraceDirection};
+use crate::output::{OutputFormat, Tabular, print_records};
+use crate::parsing::amount::format_amount;
+use crate::signature::signature::{SignatureConfig, Signatures};
+use crate::source::cache::CachedSource;
+use crate::source::fixture::FixtureSource;
+use crate::source::source::ChainSource;
+use crate::transaction::decoder::DecoderRegistry;
+use crate::transaction::transaction::TransactionDetail;
+
+/// Trace and inspect Solana transactions.
+#[derive(Parser)]
+#[clap(name = "solana_rpc_bin", version, about)]
+pub struct Cli {
+    /// RPC endpoint, overrides SOLANA_RPC_URL, SOLANA_RPC_ENDPOINTS and the config file.
+    #[clap(long, global = true, conflicts_with = "endpoints")]
+    pub rpc_url: Option<String>,
+    /// RPC endpoint of a pool, formatted as `url[,weight=<weight>][,archival][,rate_limit=<requests per second>]`.
+    /// Can be repeated, requests are balanced over the endpoints and fail over between them.
+    #[clap(long = "endpoint", global = true)]
+    pub endpoints: Vec<String>,
+    /// Request timeout in seconds.
+    #[clap(long, global = true)]
+    pub timeout: Option<u64>,
+    /// Commitment level: finalized, confirmed or processed.
+    #[clap(long, global = true)]
+    pub commitment: Option<String>,
+    /// HTTP header attached to every request, formatted as `Name: value`. Can be repeated.
+    #[clap(long = "header", global = true)]
+    pub headers: Vec<String>,
+    /// Path to a TOML client config file.
+    #[clap(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Maximum number of retries of a request failing with HTTP 429, HTTP 5xx, a rate limit or node behind JSON-RPC error,
+    /// a timeout or a connection error.
+    #[clap(long, global = true)]
+    pub max_retries: Option<usize>,
+    /// Maximum number of requests per second sent to the RPC endpoint.
+    #[clap(long, global = true)]
+    pub rate_limit: Option<f64>,
+    /// Output format.
+    #[clap(long, value_enum, default_value = "table", global = true)]
+    pub output: OutputFormat,
+    /// Directory of the transaction cache. Finalized transactions and signature pages are read from and stored in it.
+    #[clap(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+    /// Maximum size of the transaction cache in MB, the least recently used transactions are evicted beyond it.
+    #[clap(long, global = true, requires = "cache-dir")]
+    pub cache_max_mb: Option<u64>,
+    /// Never send RPC requests, only read transactions and signature pages from the cache.
+    #[clap(long, global = true, requires = "cache-dir")]
+    pub offline: bool,
+    /// Record every RPC request and response to this cassette file.
+    #[clap(long, global = true, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// Replay the RPC responses from this cassette file instead of sending requests.
+    #[clap(long, global = true)]
+    pub replay: Option<PathBuf>,
+    /// JSON fixture file to read the chain data from instead of the RPC node. Can be repeated.
+    #[clap(long = "fixture", global = true)]
+    pub fixtures: Vec<PathBuf>,
+    /// Anchor IDL file to decode the instructions of its program with, the IDL must contain the program
+    /// address. Can be repeated.
+    #[clap(long = "idl", global = true)]
+    pub idls: Vec<PathBuf>,
+    #[clap(subcommand)]
+    pub command: Command
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Trace SOL transfers starting from an account. SPL Token transfers are only followed with `--tokens`.
+    Trace {
+        /// Account as base-58 encoded string.
+        account: String,
+        /// Number of hops to follow from the account.
+        #[clap(long, default_value = "1")]
+        depth: usize,
+        /// Start of the time window, formatted as `Y-M-D H:M:S`.
+        #[clap(long)]
+        from: Option<String>,
+        /// End of the time window, formatted as `Y-M-D H:M:S`.
+        #[clap(long)]
+        to: Option<String>,
+        /// Transaction signatures to request per page.
+        #[clap(long, default_value = "1000")]
+        limit: usize,
+        /// Maximum transaction signatures to fetch per account, across all pages.
+        #[clap(long)]
+        max: Option<usize>,
+        /// Follow transfers forward to their destination, backward to their source, or both.
+        #[clap(long, default_value = "forward")]
+        direction: TraceDirection,
+        /// Only follow transfers made after the funds arrived at an account. Backward traces always respect time.
+        #[clap(long)]
+        causal: bool,
+        /// Maximum number of seconds between the arrival of funds at an account and the transfer that moved them on.
+        #[clap(long)]
+        max_delay: Option<u64>,
+        /// Number of batches of transactions to fetch concurrently.
+        #[clap(long, default_value = "1")]
+        parallelism: usize,
+        /// Number of transactions to fetch with a single JSON-RPC batch request. Not all RPC providers support batches.
+        #[clap(long, default_value = "1")]
+        batch_size: usize,
+        /// Also follow SPL Token transfers between token accounts. The token transfers are printed after the SOL transfers.
+        #[clap(long)]
+        tokens: bool,
+        /// Print the traced transfers as a graph in this format (dot, graphml or gexf) instead of as records.
+        #[clap(long)]
+        graph: Option<GraphFormat>,
+        #[clap(flatten)]
+        budget: BudgetArgs,
+        #[clap(flatten)]
+        taint: TaintArgs
+    },
+    /// Print a transaction as JSON.
+    Tx {
+        /// Transaction signature as base-58 encoded string.
+        signature: String
+    },
+    /// List transaction signatures for an account.
+    Sigs {
+        /// Account as base-58 encoded string.
+        account: String,
+        /// Start of the time window, formatted as `Y-M-D H:M:S`.
+        #[clap(long)]
+        from: Option<String>,
+        /// End of the time window, formatted as `Y-M-D H:M:S`.
+        #[clap(long)]
+        to: Option<String>,
+        /// Transaction signatures to request per page.
+        #[clap(long, default_value = "1000")]
+        limit: usize,
+        /// Maximum transaction signatures to fetch, across all pages.
+        #[clap(long)]
+        max: Option<usize>
+    },
+    /// Show the SOL balance of an account.
+    Balance {
+        /// Account as base-58 encoded string.
+        account: String
+    },
+    /// Decode the instructions of a transaction.
+    Decode {
+        /// Transaction signature as base-58 encoded string.
+        signature: String
+    },
+    /// Show the size of the transaction cache.
+    Cache {
+        /// Remove all cached transactions.
+        #[clap(long)]
+        clear: bool
+    }
+}
+
+/// Limits on the work done by a trace.
+#[derive(Args)]
+pub struct BudgetArgs {
+    /// Maximum number of accounts to expand.
+    #[clap(long)]
+    max_accounts: Option<usize>,
+    /// Maximum number of transactions to fetch.
+    #[clap(long)]
+    max_transactions: Option<usize>,
+    /// Maximum number of RPC requests to send.
+    #[clap(long)]
+    max_rpc_calls: Option<usize>,
+    /// Maximum number of seconds to spend.
+    #[clap(long)]
+    max_seconds: Option<f64>
+}
+
+impl BudgetArgs {
+    /// Convert to a `TraceBudget`.
+    fn budget(&self) -> Result<TraceBudget, Box<dyn Error>> {
+        Ok(TraceBudget {
+            max_accounts: self.max_accounts,
+            max_transactions: self.max_transactions,
+            max_rpc_calls: self.max_rpc_calls,
+            max_duration: self.max_seconds.map(Duration::try_from_secs_f64).transpose()?
+        })
+    }
+}
+
+/// Taint propagation over the traced transfers.
+#[derive(Args)]
+pub struct TaintArgs {
+    /// Account holding tainted funds before the traced transfers, formatted as `ACCOUNT=LAMPORTS`. Can be
+    /// repeated. Prints the tainted amounts per account instead of the transfers.
+    #[clap(long = "taint-account", conflicts_with = "graph")]
+    accounts: Vec<String>,
+    /// Transaction whose transfers are tainted, formatted as `SIGNATURE` or `SIGNATURE=LAMPORTS`. Can be
+    /// repeated. Prints the tainted amounts per account instead of the transfers.
+    #[clap(long = "taint-tx", conflicts_with = "graph")]
+    transactions: Vec<String>,
+    /// Taint policy: poison, haircut or fifo.
+    #[clap(long, default_value = "haircut")]
+    taint_policy: TaintPolicy
+}
+
+impl TaintArgs {
+    /// Parse the taint sources.
+    fn sources(&self) -> Result<Vec<TaintSource>, Box<dyn Error>> {
+        let mut sources = vec![];
+        for value in &self.accounts {
+            let (account, lamports) = match value.split_once('=') {
+                Some((account, lamports)) => (account, lamports),
+                None => return Err(format!("Invalid taint account {}, expected ACCOUNT=LAMPORTS", value).into())
+            };
+            sources.push(TaintSource::Account { account: account.to_string(), lamports: lamports.parse()? });
+        }
+        for value in &self.transactions {
+            let source = match value.split_once('=') {
+                Some((signature, lamports)) => TaintSource::Transaction { signature: signature.to_string(), lamports: Some(lamports.parse()?) },
+                None => TaintSource::Transaction { signature: value.clone(), lamports: None }
+            };
+            sources.push(source);
+        }
+        Ok(sources)
+    }
+}
+
+/// Balance of an account, in lamports and SOL.
+#[derive(Serialize)]
+struct BalanceRecord {
+    account: String,
+    lamports: u64,
+    balance: String
+}
+
+impl Tabular for BalanceRecord {
+    fn headers() -> Vec<&'static str> {
+        vec!["account", "lamports", "balance"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![self.account.clone(), self.lamports.to_string(), self.balance.clone()]
+    }
+}
+
+/// Size of the transaction cache.
+#[derive(Serialize)]
+struct CacheRecord {
+    dir: String,
+    transactions: usize,
+    bytes: u64
+}
+
+impl Tabular for CacheRecord {
+    fn headers() -> Vec<&'static str> {
+        vec!["dir", "transactions", "bytes"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![self.dir.clone(), self.transactions.to_string(), self.bytes.to_string()]
+    }
+}
+
+impl Cli {
+    /// Run the selected subcommand.
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        let client_config = self.client_config()?;
+        let metrics = client_config.metrics.clone();
+        let base = self.source(client_config)?;
+        let cache = self.cache()?;
+        let cached = cache.as_ref().map(|cache| CachedSource::new(cache, base.as_ref()));
+        let source: &dyn ChainSource = match &cached {
+            Some(cached) => cached,
+            None => base.as_ref()
+        };
+        let commitment = source.commitment();
+        let registry = DecoderRegistry::with_idls(&self.idls)?;
+
+        match self.command {
+            Command::Trace { account, depth, from, to, limit, max, direction, causal, max_delay, parallelism, batch_size, tokens, graph, budget, taint } => {
+                let config = signature_config(from, to, limit, max, commitment);
+                let trace = TraceConfig {
+                    depth,
+                    budget: budget.budget()?,
+                    direction,
+                    causal,
+                    max_delay: max_delay.map(Duration::from_secs),
+                    parallelism,
+                    batch_size,
+                    tokens
+                };
+                let result = trace_transfers(&account, source, &config, &registry, &trace);
+                if let Some(failure) = result.root_failure(&account) {
+                    return Err(failure.error.clone().into());
+                }
+                for failure in &result.failures {
+                    eprintln!("Skipped {} {}: {}", failure.account, failure.signature.as_deref().unwrap_or(""), failure.error);
+                }
+                for node in &result.frontier {
+                    eprintln!("Unexpanded {} at {} depth {} ({}{})", node.account, node.direction, node.depth, node.limit, if node.partial { ", partial" } else { "" });
+                }
+                eprintln!(
+                    "Expanded {} accounts, fetched {} transactions with {} RPC calls in {:.1?}",
+                    result.stats.accounts, result.stats.transactions, result.stats.rpc_calls, result.stats.elapsed
+                );
+                let sources = taint.sources()?;
+                if let Some(format) = graph {
+                    println!("{}", TransferGraph::from_transfers(&result.transfers).export(format));
+                } else if !sources.is_empty() {
+                    let report = TransferGraph::from_transfers(&result.transfers).propagate_taint(&sources, taint.taint_policy);
+                    for unmatched in &report.unmatched {
+                        eprintln!("Unmatched taint source {}", unmatched);
+                    }
+                    print_records(&report.accounts, self.output)?;
+                } else {
+                    print_records(&result.transfers, self.output)?;
+                    if tokens {
+                        print_records(&result.token_transfers, self.output)?;
+                    }
+                }
+            },
+            Command::Tx { signature } => {
+                TransactionDetail::new(&signature, source)?.print();
+            },
+            Command::Sigs { account, from, to, limit, max } => {
+                let config = signature_config(from, to, limit, max, commitment);
+                let signatures = Signatures::fetch(&account, source, &config)?;
+                print_records(&signatures, self.output)?;
+            },
+            Command::Balance { account } => {
+                let pubkey = Pubkey::from_str(&account)?;
+                let lamports = source.get_balance(&pubkey)?;
+                let balance = format_amount(lamports, SOL_DECIMALS);
+                print_records(&[BalanceRecord { account, lamports, balance }], self.output)?;
+            },
+            Command::Decode { signature } => {
+                let transaction = TransactionDetail::new(&signature, source)?;
+                let decoded: Vec<_> = transaction.get_instructions_processed()?.iter()
+                    .filter_map(|ix| registry.decode(ix, &transaction))
+                    .collect();
+                print_records(&decoded, self.output)?;
+            },
+            Command::Cache { clear } => {
+                let cache = match &cache {
+                    Some(cache) => cache,
+                    None => return Err("The cache command requires --cache-dir".into())
+                };
+                if clear {
+                    cache.clear()?;
+                }
+                let (transactions, bytes) = cache.usage()?;
+                let dir = cache.dir().display().to_string();
+                print_records(&[CacheRecord { dir, transactions, bytes }], self.output)?;
+            }
+        }
+        if let Some(stats) = cache.map(|x| x.stats()).filter(|x| x.hits + x.misses > 0) {
+            eprintln!(
+                "Cache: {} hits, {} misses, {} writes, {} evictions, {} errors",
+                stats.hits, stats.misses, stats.writes, stats.evictions, stats.errors
+            );
+        }
+        let stats = metrics.stats();
+        if stats.retries + stats.exhausted + stats.failovers > 0 || !stats.throttled_time.is_zero() {
+            eprintln!(
+                "Retries: {} ({} rate limited, {} server errors, {} transport errors), {} failed, {} failovers, backed off {:.1?}, throttled {:.1?}",
+                stats.retries, stats.rate_limited, stats.server_errors, stats.transport_errors, stats.exhausted, stats.failovers, stats.backoff_time, stats.throttled_time
+            );
+        }
+        Ok(())
+    }
+
+    /// Return the chain data source, i.e. the fixture files if any are set, otherwise the RPC client.
+    fn source(&self, config: ClientConfig) -> Result<Box<dyn ChainSource>, Box<dyn Error>> {
+        if !self.fixtures.is_empty() {
+            return Ok(Box::new(FixtureSource::load(&self.fixtures)?));
+        }
+        if self.offline {
+            return Ok(Box::new(get_offline_client(&config)));
+        }
+        Ok(Box::new(get_client(config)?))
+    }
+
+    /// Return the transaction cache, if a cache directory is set.
+    fn cache(&self) -> Result<Option<TransactionCache>, Box<dyn Error>> {
+        let dir = match &self.cache_dir {
+            Some(dir) => dir.clone(),
+            None => return Ok(None)
+        };
+        let config = CacheConfig {
+            dir,
+            max_bytes: self.cache_max_mb.map(|x| x.saturating_mul(1024 * 1024)),
+            offline: self.offline
+        };
+        Ok(Some(TransactionCache::open(config)?))
+    }
+
+    /// Return the RPC client configuration, resolved from the command-line options with a fallback
+    /// to the environment variables and config file.
+    fn client_config(&self) -> Result<ClientConfig, Box<dyn Error>> {
+        let mut config = ClientConfig::load(self.config.as_deref())?;
+        config.apply_overrides(ClientOverrides {
+            url: self.rpc_url.clone(),
+            endpoints: self.endpoints.clone(),
+            timeout: self.timeout,
+            commitment: self.commitment.clone(),
+            headers: self.headers.iter().map(|x| parse_header(x)).collect::<Result<_, _>>()?,
+            cassette: match (&self.record, &self.replay) {
+                (Some(path), _) => Some(CassetteMode::Record(path.clone())),
+                (_, Some(path)) => Some(CassetteMode::Replay(path.clone())),
+                _ => None
+            },
+            max_retries: self.max_retries,
+            rate_limit: self.rate_limit
+        })?;
+        Ok(config)
+    }
+}
+
+/// Return a signature configuration for the specified time window.
+fn signature_config(from: Option<String>, to: Option<String>, limit: usize, max: Option<usize>, commitment: CommitmentConfig) -> SignatureConfig {
+    SignatureConfig {
+        time_before: from,
+        time_after: to,
+        before: None,
+        until: None,
+        limit: Some(limit),
+        max,
+        commitment: Some(commitment)
+    }
+}
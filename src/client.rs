@@ -1,20 +1,487 @@
+pub mod batch;
+pub mod cassette;
+pub mod pool;
+pub mod retry;
+pub mod sender;
+#[cfg(test)]
+pub mod stub;
+
 use core::time::Duration;
-use solana_client::rpc_client::RpcClient;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_client::rpc_sender::RpcSender;
 use solana_sdk::commitment_config::CommitmentConfig;
+use crate::errors::ClientConfigError;
+use cassette::{CassetteMode, RecordingSender, ReplaySender};
+use pool::{Endpoint, PoolSender};
+use retry::{RateLimiter, RetryMetrics, RetryPolicy};
+use sender::{HeaderHttpSender, OfflineSender};
+
+/// Default RPC endpoint.
+pub const DEFAULT_URL: &str = "https://api.mainnet-beta.solana.com";
+/// Default request timeout, in seconds.
+pub const DEFAULT_TIMEOUT: u64 = 20;
+/// Default weight of an endpoint of a pool.
+pub const DEFAULT_WEIGHT: u32 = 1;
+/// Config file read when `SOLANA_RPC_CONFIG` is not set.
+pub const DEFAULT_CONFIG_FILE: &str = "solana_rpc.toml";
+
+/// Environment variable pointing to the config file.
+pub const ENV_CONFIG: &str = "SOLANA_RPC_CONFIG";
+/// Environment variable overriding the RPC endpoint.
+pub const ENV_URL: &str = "SOLANA_RPC_URL";
+/// Environment variable overriding the endpoints of the pool, formatted as endpoints separated by `;`,
+/// see `parse_endpoint`.
+pub const ENV_ENDPOINTS: &str = "SOLANA_RPC_ENDPOINTS";
+/// Environment variable overriding the request timeout, in seconds.
+pub const ENV_TIMEOUT: &str = "SOLANA_RPC_TIMEOUT";
+/// Environment variable overriding the commitment level.
+pub const ENV_COMMITMENT: &str = "SOLANA_RPC_COMMITMENT";
+/// Environment variable with additional HTTP headers, formatted as `Name: value` pairs separated by `;`.
+pub const ENV_HEADERS: &str = "SOLANA_RPC_HEADERS";
+/// Environment variable with a cassette file to record the RPC requests and responses to.
+pub const ENV_RECORD: &str = "SOLANA_RPC_RECORD";
+/// Environment variable with a cassette file to replay the RPC responses from.
+pub const ENV_REPLAY: &str = "SOLANA_RPC_REPLAY";
+/// Environment variable overriding the maximum number of retries of a failed request.
+pub const ENV_MAX_RETRIES: &str = "SOLANA_RPC_MAX_RETRIES";
+/// Environment variable overriding the maximum number of requests per second sent to the endpoint.
+pub const ENV_RATE_LIMIT: &str = "SOLANA_RPC_RATE_LIMIT";
 
 /// RPC client configuration.
-/// Contains `url`, `timeout` and `commitment` fields.
+/// Contains `url`, `endpoints`, `timeout`, `commitment`, `headers`, `cassette`, `retry`, `rate_limit` and
+/// `metrics` fields.
 pub struct ClientConfig {
     pub url: String,
+    /// Endpoints of a pool the requests are balanced over, see `PoolSender`. If empty, requests are
+    /// sent to `url`.
+    pub endpoints: Vec<EndpointConfig>,
     pub timeout: Duration,
-    pub commitment: CommitmentConfig
+    pub commitment: CommitmentConfig,
+    /// HTTP headers attached to every request, e.g. for authenticated RPC providers.
+    pub headers: HashMap<String, String>,
+    /// Cassette file to record the requests to or replay the responses from, e.g. for offline tests.
+    pub cassette: Option<CassetteMode>,
+    /// Retry policy of requests failing with HTTP 429, HTTP 5xx, a rate limit or node behind JSON-RPC error,
+    /// a timeout or a connection error.
+    pub retry: RetryPolicy,
+    /// Maximum number of requests per second sent to the endpoint. Each request of a batch counts.
+    pub rate_limit: Option<f64>,
+    /// Retry counters, updated by the clients built from this configuration.
+    pub metrics: Arc<RetryMetrics>
+}
+
+/// RPC endpoint of a pool.
+/// Contains `url`, `weight`, `archival`, `rate_limit` and `headers` fields.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct EndpointConfig {
+    pub url: String,
+    /// Relative share of the requests sent to the endpoint, zero to only use it when the others fail.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// Whether the endpoint keeps the full ledger history. Signature history is read from archival
+    /// endpoints, fresh data from the others.
+    #[serde(default)]
+    pub archival: bool,
+    /// Maximum number of requests per second sent to the endpoint, overrides the `rate_limit` of the
+    /// client configuration.
+    pub rate_limit: Option<f64>,
+    /// HTTP headers attached to the requests to the endpoint, in addition to the client configuration ones.
+    #[serde(default)]
+    pub headers: HashMap<String, String>
+}
+
+fn default_weight() -> u32 {
+    DEFAULT_WEIGHT
+}
+
+impl EndpointConfig {
+    /// Returns a new `EndpointConfig` with the default weight.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - RPC endpoint.
+    pub fn new(url: String) -> Self {
+        EndpointConfig { url, weight: DEFAULT_WEIGHT, archival: false, rate_limit: None, headers: HashMap::new() }
+    }
+}
+
+/// Client configuration as stored in a TOML config file, all fields are optional.
+///
+/// ```toml
+/// url = "https://api.mainnet-beta.solana.com"
+/// timeout = 20
+/// commitment = "finalized"
+/// max_retries = 5
+/// rate_limit = 10.0
+///
+/// [headers]
+/// Authorization = "Bearer <token>"
+///
+/// [[endpoints]]
+/// url = "https://fast.example.com"
+/// weight = 2
+///
+/// [[endpoints]]
+/// url = "https://archive.example.com"
+/// archival = true
+/// rate_limit = 5.0
+/// ```
+#[derive(Deserialize, Default)]
+struct ClientConfigFile {
+    url: Option<String>,
+    /// Request timeout in seconds.
+    timeout: Option<u64>,
+    commitment: Option<String>,
+    max_retries: Option<usize>,
+    /// Requests per second.
+    rate_limit: Option<f64>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    endpoints: Vec<EndpointConfig>
+}
+
+/// Client configuration set by the caller, e.g. from command-line options, overriding the loaded
+/// configuration, see `ClientConfig::apply_overrides`. All fields are optional.
+/// Contains `url`, `endpoints`, `timeout`, `commitment`, `headers`, `cassette`, `max_retries` and
+/// `rate_limit` fields.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOverrides {
+    /// RPC endpoint, replaces the configured endpoints.
+    pub url: Option<String>,
+    /// Endpoints of a pool, formatted as in `parse_endpoint`. Replace the configured endpoints if not empty.
+    pub endpoints: Vec<String>,
+    /// Request timeout in seconds.
+    pub timeout: Option<u64>,
+    /// Commitment level, i.e. `finalized`, `confirmed` or `processed`.
+    pub commitment: Option<String>,
+    /// HTTP headers, added to the configured ones.
+    pub headers: HashMap<String, String>,
+    pub cassette: Option<CassetteMode>,
+    pub max_retries: Option<usize>,
+    /// Requests per second.
+    pub rate_limit: Option<f64>
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            url: DEFAULT_URL.to_string(),
+            endpoints: vec![],
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT),
+            commitment: CommitmentConfig::finalized(),
+            headers: HashMap::new(),
+            cassette: None,
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            metrics: Arc::new(RetryMetrics::default())
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Load client configuration. Values are resolved, in order of precedence, from the environment
+    /// variables, the config file and the defaults. The config file is `path` if specified, otherwise
+    /// the file in `SOLANA_RPC_CONFIG`, otherwise `solana_rpc.toml` if it exists. A `url` replaces the
+    /// endpoints resolved with a lower precedence.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Optional path to a TOML config file.
+    pub fn load(path: Option<&Path>) -> Result<Self, ClientConfigError> {
+        let mut config = Self::default();
+
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => match env::var(ENV_CONFIG) {
+                Ok(path) => Some(PathBuf::from(path)),
+                Err(_) => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|x| x.exists())
+            }
+        };
+        if let Some(path) = path {
+            config.apply_file(&path)?;
+        }
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Override the configuration with the values of a TOML config file.
+    fn apply_file(&mut self, path: &Path) -> Result<(), ClientConfigError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => return Err(ClientConfigError::ReadError(format!("{}: {}", path.display(), err)))
+        };
+        let file: ClientConfigFile = match toml::from_str(&content) {
+            Ok(file) => file,
+            Err(err) => return Err(ClientConfigError::ParseError(err.to_string()))
+        };
+        if let Some(url) = file.url {
+            self.url = url;
+        }
+        if !file.endpoints.is_empty() {
+            self.endpoints = file.endpoints;
+        }
+        if let Some(timeout) = file.timeout {
+            self.timeout = Duration::from_secs(timeout);
+        }
+        if let Some(commitment) = file.commitment {
+            self.commitment = parse_commitment(&commitment)?;
+        }
+        if let Some(max_retries) = file.max_retries {
+            self.retry.max_retries = max_retries;
+        }
+        if let Some(rate_limit) = file.rate_limit {
+            self.rate_limit = Some(rate_limit);
+        }
+        self.headers.extend(file.headers);
+        Ok(())
+    }
+
+    /// Override the configuration with the values of the `SOLANA_RPC_*` environment variables.
+    fn apply_env(&mut self) -> Result<(), ClientConfigError> {
+        if let Ok(url) = env::var(ENV_URL) {
+            self.url = url;
+            self.endpoints.clear();
+        }
+        if let Ok(endpoints) = env::var(ENV_ENDPOINTS) {
+            self.endpoints = endpoints.split(';')
+                .filter(|x| !x.trim().is_empty())
+                .map(parse_endpoint)
+                .collect::<Result<_, _>>()?;
+        }
+        if let Ok(timeout) = env::var(ENV_TIMEOUT) {
+            match timeout.parse::<u64>() {
+                Ok(timeout) => self.timeout = Duration::from_secs(timeout),
+                Err(err) => return Err(ClientConfigError::ParseError(format!("{}: {}", ENV_TIMEOUT, err)))
+            }
+        }
+        if let Ok(commitment) = env::var(ENV_COMMITMENT) {
+            self.commitment = parse_commitment(&commitment)?;
+        }
+        if let Ok(max_retries) = env::var(ENV_MAX_RETRIES) {
+            match max_retries.parse::<usize>() {
+                Ok(max_retries) => self.retry.max_retries = max_retries,
+                Err(err) => return Err(ClientConfigError::ParseError(format!("{}: {}", ENV_MAX_RETRIES, err)))
+            }
+        }
+        if let Ok(rate_limit) = env::var(ENV_RATE_LIMIT) {
+            match rate_limit.parse::<f64>() {
+                Ok(rate_limit) => self.rate_limit = Some(rate_limit),
+                Err(err) => return Err(ClientConfigError::ParseError(format!("{}: {}", ENV_RATE_LIMIT, err)))
+            }
+        }
+        if let Ok(headers) = env::var(ENV_HEADERS) {
+            for header in headers.split(';').filter(|x| !x.trim().is_empty()) {
+                let (name, value) = parse_header(header)?;
+                self.headers.insert(name, value);
+            }
+        }
+        match (env::var(ENV_RECORD), env::var(ENV_REPLAY)) {
+            (Ok(_), Ok(_)) => return Err(ClientConfigError::ParseError(format!("{} and {} are both set", ENV_RECORD, ENV_REPLAY))),
+            (Ok(path), _) => self.cassette = Some(CassetteMode::Record(PathBuf::from(path))),
+            (_, Ok(path)) => self.cassette = Some(CassetteMode::Replay(PathBuf::from(path))),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Override the configuration with the values set by the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `overrides` - Values set by the caller, e.g. from command-line options.
+    pub fn apply_overrides(&mut self, overrides: ClientOverrides) -> Result<(), ClientConfigError> {
+        if let Some(url) = overrides.url {
+            self.url = url;
+            self.endpoints.clear();
+        }
+        if !overrides.endpoints.is_empty() {
+            self.endpoints = overrides.endpoints.iter().map(|x| parse_endpoint(x)).collect::<Result<_, _>>()?;
+        }
+        if let Some(timeout) = overrides.timeout {
+            self.timeout = Duration::from_secs(timeout);
+        }
+        if let Some(commitment) = overrides.commitment {
+            self.commitment = parse_commitment(&commitment)?;
+        }
+        self.headers.extend(overrides.headers);
+        if let Some(cassette) = overrides.cassette {
+            self.cassette = Some(cassette);
+        }
+        if let Some(max_retries) = overrides.max_retries {
+            self.retry.max_retries = max_retries;
+        }
+        if let Some(rate_limit) = overrides.rate_limit {
+            self.rate_limit = Some(rate_limit);
+        }
+        Ok(())
+    }
+
+    /// Return the configured endpoints, or `url` if there are none.
+    fn pool(&self) -> Vec<EndpointConfig> {
+        if self.endpoints.is_empty() {
+            vec![EndpointConfig::new(self.url.clone())]
+        } else {
+            self.endpoints.clone()
+        }
+    }
+
+    /// Return the configured headers and the headers of `endpoint` as a `HeaderMap`.
+    fn header_map(&self, endpoint: &EndpointConfig) -> Result<HeaderMap, ClientConfigError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers.iter().chain(&endpoint.headers) {
+            let name = match HeaderName::from_str(name) {
+                Ok(name) => name,
+                Err(err) => return Err(ClientConfigError::InvalidHeader(format!("{}: {}", name, err)))
+            };
+            let value = match HeaderValue::from_str(value) {
+                Ok(value) => value,
+                Err(err) => return Err(ClientConfigError::InvalidHeader(format!("{}: {}", name, err)))
+            };
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    /// Return the rate limiter of `endpoint`, if it or the configuration sets a rate limit.
+    fn rate_limiter(&self, endpoint: &EndpointConfig) -> Result<Option<RateLimiter>, ClientConfigError> {
+        match endpoint.rate_limit.or(self.rate_limit) {
+            Some(rate) if !(rate.is_finite() && rate > 0.0) => Err(ClientConfigError::InvalidRateLimit(format!("{}: must be a positive number of requests per second", rate))),
+            rate => Ok(rate.map(RateLimiter::new))
+        }
+    }
+
+    /// Return the HTTP sender of `endpoint`.
+    fn sender(&self, endpoint: &EndpointConfig) -> Result<HeaderHttpSender, ClientConfigError> {
+        let headers = self.header_map(endpoint)?;
+        let rate_limiter = self.rate_limiter(endpoint)?;
+        match HeaderHttpSender::new(endpoint.url.clone(), self.timeout, headers, self.retry, rate_limiter, self.metrics.clone()) {
+            Ok(sender) => Ok(sender),
+            Err(err) => Err(ClientConfigError::BuildError(err.to_string()))
+        }
+    }
+}
+
+/// Parse a commitment level, i.e. `finalized`, `confirmed` or `processed`.
+pub fn parse_commitment(commitment: &str) -> Result<CommitmentConfig, ClientConfigError> {
+    match CommitmentConfig::from_str(commitment) {
+        Ok(commitment) => Ok(commitment),
+        Err(err) => Err(ClientConfigError::InvalidCommitment(format!("{}: {}", commitment, err)))
+    }
+}
+
+/// Parse a pool endpoint formatted as `url[,weight=<weight>][,archival][,rate_limit=<requests per second>]`.
+pub fn parse_endpoint(endpoint: &str) -> Result<EndpointConfig, ClientConfigError> {
+    let mut options = endpoint.split(',').map(str::trim);
+    let mut config = match options.next().filter(|x| !x.is_empty()) {
+        Some(url) => EndpointConfig::new(url.to_string()),
+        None => return Err(ClientConfigError::InvalidEndpoint(endpoint.to_string()))
+    };
+    for option in options {
+        let invalid = || ClientConfigError::InvalidEndpoint(format!("{}: {}", endpoint, option));
+        match option.split_once('=').map(|(name, value)| (name.trim(), value.trim())) {
+            None if option == "archival" => config.archival = true,
+            Some(("weight", weight)) => config.weight = weight.parse().map_err(|_| invalid())?,
+            Some(("rate_limit", rate_limit)) => config.rate_limit = Some(rate_limit.parse().map_err(|_| invalid())?),
+            _ => return Err(invalid())
+        }
+    }
+    Ok(config)
+}
+
+/// Parse an HTTP header formatted as `Name: value`.
+pub fn parse_header(header: &str) -> Result<(String, String), ClientConfigError> {
+    match header.split_once(':') {
+        Some((name, value)) => Ok((name.trim().to_string(), value.trim().to_string())),
+        None => Err(ClientConfigError::InvalidHeader(header.to_string()))
+    }
 }
 
-/// Returns RPC client.
+/// Returns RPC client. Requests failing with a transient error are retried, and counted in the
+/// configured `metrics`. If several endpoints are configured the requests are balanced over them, see
+/// `PoolSender`. If a cassette is configured the requests are recorded to it, or the responses are
+/// replayed from it without connecting to the RPC endpoints.
 /// 
 /// # Arguments
 /// 
 /// * `config` - Client configuration.
-pub fn get_client(config: ClientConfig) -> RpcClient {
-    RpcClient::new_with_timeout_and_commitment(config.url, config.timeout, config.commitment)
-}
\ No newline at end of file
+pub fn get_client(config: ClientConfig) -> Result<RpcClient, ClientConfigError> {
+    let client_config = RpcClientConfig::with_commitment(config.commitment);
+    if let Some(CassetteMode::Replay(path)) = &config.cassette {
+        return match ReplaySender::load(path) {
+            Ok(sender) => Ok(RpcClient::new_sender(sender, client_config)),
+            Err(err) => Err(ClientConfigError::CassetteError(err))
+        };
+    }
+    let pool = config.pool();
+    if let [endpoint] = &pool[..] {
+        return new_client(config.sender(endpoint)?, &config);
+    }
+    let mut endpoints = Vec::with_capacity(pool.len());
+    for endpoint in &pool {
+        endpoints.push(Endpoint::new(config.sender(endpoint)?, endpoint.weight, endpoint.archival));
+    }
+    new_client(PoolSender::new(endpoints, config.retry, config.metrics.clone()), &config)
+}
+
+/// Returns RPC client sending the requests with `sender`, recording them if a cassette is configured.
+fn new_client<S: RpcSender + Send + Sync + 'static>(sender: S, config: &ClientConfig) -> Result<RpcClient, ClientConfigError> {
+    let client_config = RpcClientConfig::with_commitment(config.commitment);
+    if let Some(CassetteMode::Record(path)) = &config.cassette {
+        return match RecordingSender::create(sender, path) {
+            Ok(sender) => Ok(RpcClient::new_sender(sender, client_config)),
+            Err(err) => Err(ClientConfigError::CassetteError(format!("{}: {}", path.display(), err)))
+        };
+    }
+    Ok(RpcClient::new_sender(sender, client_config))
+}
+
+/// Returns RPC client that never sends a request, every call fails. Used in offline mode, where
+/// transactions are only read from the cache.
+///
+/// # Arguments
+///
+/// * `config` - Client configuration, only the commitment is used.
+pub fn get_offline_client(config: &ClientConfig) -> RpcClient {
+    RpcClient::new_sender(OfflineSender, RpcClientConfig::with_commitment(config.commitment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_overrides() {
+        let mut config = ClientConfig {
+            endpoints: vec![EndpointConfig::new("https://a.example.com".to_string())],
+            headers: HashMap::from([("A".to_string(), "1".to_string())]),
+            ..Default::default()
+        };
+        config.apply_overrides(ClientOverrides {
+            url: Some("https://b.example.com".to_string()),
+            timeout: Some(5),
+            commitment: Some("confirmed".to_string()),
+            headers: HashMap::from([("B".to_string(), "2".to_string())]),
+            max_retries: Some(0),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(config.url, "https://b.example.com");
+        assert!(config.endpoints.is_empty());
+        assert_eq!(config.timeout, Duration::from_secs(5));
+        assert_eq!(config.commitment, CommitmentConfig::confirmed());
+        assert_eq!(config.headers.len(), 2);
+        assert_eq!((config.retry.max_retries, config.rate_limit), (0, None));
+
+        config.apply_overrides(ClientOverrides { endpoints: vec!["https://c.example.com,weight=2,archival".to_string()], ..Default::default() }).unwrap();
+        assert_eq!(config.pool(), [EndpointConfig { weight: 2, archival: true, ..EndpointConfig::new("https://c.example.com".to_string()) }]);
+        assert!(config.apply_overrides(ClientOverrides { commitment: Some("final".to_string()), ..Default::default() }).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use serde_json::{json, Value};
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::rpc_request::{RpcError, RpcRequest, RpcResponseErrorData};
+
+/// Method of the pseudo request that sends a JSON-RPC batch through an `RpcSender`. Its parameters are an
+/// array of `{"method": ..., "params": ...}` objects, its result an array with a `{"result": ...}` or
+/// `{"error": {"code": ..., "message": ...}}` object per request, in the same order.
+pub const BATCH_METHOD: &str = "batch";
+/// JSON-RPC error code of an unknown method, returned for batch requests by senders that do not support
+/// them, e.g. the `HttpSender` of `solana_client`, which sends the pseudo method as is.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC error code of an invalid request, returned for batch requests by endpoints that reject them,
+/// and the code of the errors of `rejected_batch`.
+pub const INVALID_REQUEST: i64 = -32600;
+
+/// Whether `request` is a batch request, see `BATCH_METHOD`.
+pub fn is_batch(request: &RpcRequest) -> bool {
+    matches!(request, RpcRequest::Custom { method } if *method == BATCH_METHOD)
+}
+
+/// Whether `err` is the error of a batch request sent by a sender or to an endpoint that does not support
+/// batches, i.e. an unknown method or invalid request error.
+pub fn is_unsupported(err: &ClientError) -> bool {
+    matches!(err.kind(), ClientErrorKind::RpcError(RpcError::RpcResponseError { code: METHOD_NOT_FOUND | INVALID_REQUEST, .. }))
+}
+
+/// Return the error of a batch request the endpoint rejected, e.g. with an HTTP client error or a single
+/// response instead of an array, as an invalid request error. `reason` describes the rejection.
+pub fn rejected_batch(reason: &str) -> ClientError {
+    RpcError::RpcResponseError {
+        code: INVALID_REQUEST,
+        message: format!("Batch request rejected: {}", reason),
+        data: RpcResponseErrorData::Empty
+    }.into()
+}
+
+/// Return the batch request and its parameters for `requests`, to be sent with `RpcClient::send`.
+///
+/// # Arguments
+///
+/// * `requests` - Requests and their parameters.
+pub fn batch_request(requests: &[(RpcRequest, Value)]) -> (RpcRequest, Value) {
+    let params = requests.iter()
+        .map(|(request, params)| json!({ "method": request.to_string(), "params": params }))
+        .collect();
+    (RpcRequest::Custom { method: BATCH_METHOD }, Value::Array(params))
+}
+
+/// Build the JSON-RPC 2.0 batch body for the parameters of a batch request, numbering the requests
+/// from `first_id`.
+pub fn build_batch_json(params: &Value, first_id: u64) -> Value {
+    let requests = params.as_array().map_or(&[][..], |x| &x[..]);
+    Value::Array(requests.iter().zip(first_id..).map(|(request, id)| json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": request["method"],
+        "params": request["params"]
+    })).collect())
+}
+
+/// Convert the JSON-RPC batch response to `count` requests numbered from `first_id` into the result of a
+/// batch request. Responses may arrive in any order and are matched by id, requests without a
+/// response get an error.
+pub fn batch_result(response: Value, first_id: u64, count: usize) -> Value {
+    let mut results = vec![Value::Null; count];
+    if let Value::Array(responses) = response {
+        for mut response in responses {
+            let index = match response["id"].as_u64().and_then(|id| id.checked_sub(first_id)) {
+                Some(index) if (index as usize) < count => index as usize,
+                _ => continue
+            };
+            results[index] = if response["error"].is_object() {
+                json!({ "error": response["error"].take() })
+            } else {
+                json!({ "result": response["result"].take() })
+            };
+        }
+    }
+    Value::Array(results.into_iter().map(|result| match result {
+        Value::Null => json!({ "error": { "code": -32603, "message": "No response in batch" } }),
+        result => result
+    }).collect())
+}
+
+/// Split the result of a batch request into the results of its `count` requests.
+#[allow(clippy::result_large_err)]
+pub fn batch_results(result: Value, count: usize) -> Vec<ClientResult<Value>> {
+    let mut items = match result {
+        Value::Array(items) => items,
+        _ => vec![]
+    };
+    items.resize(count, Value::Null);
+    items.into_iter().map(|mut item| {
+        if let Some(error) = item.get_mut("error") {
+            let code = error["code"].as_i64().unwrap_or_default();
+            let message = error["message"].as_str().unwrap_or_default().to_string();
+            return Err(RpcError::RpcResponseError { code, message, data: RpcResponseErrorData::Empty }.into());
+        }
+        match item.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(RpcError::RpcRequestError("No response in batch".to_string()).into())
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_responses_by_id() {
+        let response = json!([
+            {"jsonrpc": "2.0", "id": 12, "result": "c"},
+            {"jsonrpc": "2.0", "id": 10, "result": "a"},
+            {"jsonrpc": "2.0", "id": 11, "error": {"code": -32009, "message": "Slot skipped"}},
+            {"jsonrpc": "2.0", "id": 14, "result": "out of range"},
+            {"jsonrpc": "2.0", "id": null, "error": {"code": -32600, "message": "Invalid request"}}
+        ]);
+        let results = batch_results(batch_result(response, 10, 4), 4);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap(), "a");
+        assert_eq!(results[2].as_ref().unwrap(), "c");
+        for (index, code) in [(1, -32009), (3, -32603)] {
+            match results[index].as_ref().unwrap_err().kind() {
+                ClientErrorKind::RpcError(RpcError::RpcResponseError { code: x, .. }) => assert_eq!(*x, code),
+                kind => panic!("{:?}", kind)
+            }
+        }
+    }
+
+    #[test]
+    fn detect_unsupported_batches() {
+        let error = |code| RpcError::RpcResponseError { code, message: String::new(), data: RpcResponseErrorData::Empty }.into();
+        assert!(is_unsupported(&error(METHOD_NOT_FOUND)));
+        assert!(is_unsupported(&error(INVALID_REQUEST)));
+        assert!(!is_unsupported(&error(-32005)));
+        assert!(is_unsupported(&rejected_batch("HTTP status client error (413 Payload Too Large)")));
+        assert!(!is_unsupported(&RpcError::RpcRequestError("connection refused".to_string()).into()));
+    }
+
+    #[test]
+    fn build_numbered_requests() {
+        let (request, params) = batch_request(&[
+            (RpcRequest::GetSlot, Value::Null),
+            (RpcRequest::GetBalance, json!(["account"]))
+        ]);
+        assert!(is_batch(&request));
+        assert_eq!(build_batch_json(&params, 7), json!([
+            {"jsonrpc": "2.0", "id": 7, "method": "getSlot", "params": null},
+            {"jsonrpc": "2.0", "id": 8, "method": "getBalance", "params": ["account"]}
+        ]));
+    }
+}
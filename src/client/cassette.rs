@@ -0,0 +1,274 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::Instant;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::rpc_request::{RpcError, RpcRequest, RpcResponseErrorData};
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+
+/// Whether RPC requests are recorded to or replayed from a cassette file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Send requests to the RPC node and write every request and response to the file.
+    Record(PathBuf),
+    /// Serve responses from the file, never sending a request.
+    Replay(PathBuf)
+}
+
+/// Request and response pair, stored as one line of JSON in a cassette file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Interaction {
+    method: String,
+    params: serde_json::Value,
+    #[serde(flatten)]
+    response: Response
+}
+
+/// Response of an interaction: the JSON-RPC result, or the error the request failed with.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+enum Response {
+    Result(serde_json::Value),
+    Error(ResponseError)
+}
+
+/// Error of an interaction. JSON-RPC errors keep their `code`, other errors are replayed as request errors.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ResponseError {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<i64>,
+    message: String
+}
+
+impl Response {
+    fn new(result: &ClientResult<serde_json::Value>) -> Self {
+        match result {
+            Ok(value) => Response::Result(value.clone()),
+            Err(ClientError { kind: ClientErrorKind::RpcError(RpcError::RpcResponseError { code, message, .. }), .. }) => {
+                Response::Error(ResponseError { code: Some(*code), message: message.clone() })
+            },
+            Err(err) => Response::Error(ResponseError { code: None, message: err.to_string() })
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn replay(&self) -> ClientResult<serde_json::Value> {
+        match self {
+            Response::Result(value) => Ok(value.clone()),
+            Response::Error(ResponseError { code: Some(code), message }) => Err(RpcError::RpcResponseError {
+                code: *code,
+                message: message.clone(),
+                data: RpcResponseErrorData::Empty
+            }.into()),
+            Response::Error(ResponseError { code: None, message }) => Err(RpcError::RpcRequestError(message.clone()).into())
+        }
+    }
+}
+
+/// Key an interaction is replayed by, its method and parameters.
+fn interaction_key(method: &str, params: &serde_json::Value) -> String {
+    format!("{} {}", method, params)
+}
+
+/// `RpcSender` that forwards requests to another sender and appends every request and response to a
+/// cassette file, one JSON object per line, see `ReplaySender`.
+pub struct RecordingSender<S> {
+    sender: S,
+    path: PathBuf,
+    file: Mutex<BufWriter<File>>
+}
+
+impl<S: RpcSender> RecordingSender<S> {
+    /// Returns a new `RecordingSender`, truncating the cassette file.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender` - Sender the requests are forwarded to.
+    /// * `path` - Path to the cassette file.
+    pub fn create(sender: S, path: &Path) -> std::io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(RecordingSender { sender, path: path.to_path_buf(), file: Mutex::new(BufWriter::new(file)) })
+    }
+}
+
+#[async_trait]
+impl<S: RpcSender + Send + Sync + 'static> RpcSender for RecordingSender<S> {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+        let result = self.sender.send(request, params.clone()).await;
+        let interaction = Interaction { method: request.to_string(), params, response: Response::new(&result) };
+        // Every interaction is flushed, so the cassette is complete up to the last request if the process exits.
+        let line = serde_json::to_string(&interaction)?;
+        let mut file = self.file.lock().unwrap();
+        if let Err(err) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+            return Err(RpcError::RpcRequestError(format!("Failed to write cassette {}: {}", self.path.display(), err)).into());
+        }
+        result
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.sender.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.sender.url()
+    }
+}
+
+/// `RpcSender` that serves the responses of a cassette file written by `RecordingSender`, without
+/// sending any request. Requests are matched by method and parameters. Identical requests are served
+/// the recorded responses in order, and the last one once those run out. Requests that were not
+/// recorded fail with a request error.
+pub struct ReplaySender {
+    path: PathBuf,
+    /// Recorded responses per interaction key, in order.
+    responses: Mutex<HashMap<String, VecDeque<Response>>>,
+    /// Transport statistics, counting every replayed request.
+    stats: RwLock<RpcTransportStats>
+}
+
+impl ReplaySender {
+    /// Returns a new `ReplaySender` serving the interactions of a cassette file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the cassette file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => return Err(format!("{}: {}", path.display(), err))
+        };
+        let mut responses: HashMap<String, VecDeque<Response>> = HashMap::new();
+        for (number, line) in content.lines().enumerate().filter(|x| !x.1.trim().is_empty()) {
+            let interaction: Interaction = match serde_json::from_str(line) {
+                Ok(interaction) => interaction,
+                Err(err) => return Err(format!("{} line {}: {}", path.display(), number + 1, err))
+            };
+            let key = interaction_key(&interaction.method, &interaction.params);
+            responses.entry(key).or_default().push_back(interaction.response);
+        }
+        Ok(ReplaySender {
+            path: path.to_path_buf(),
+            responses: Mutex::new(responses),
+            stats: RwLock::new(RpcTransportStats::default())
+        })
+    }
+}
+
+#[async_trait]
+impl RpcSender for ReplaySender {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+        let started = Instant::now();
+        let method = request.to_string();
+        let response = {
+            let mut responses = self.responses.lock().unwrap();
+            match responses.get_mut(&interaction_key(&method, &params)) {
+                Some(queue) if queue.len() > 1 => queue.pop_front(),
+                Some(queue) => queue.front().cloned(),
+                None => None
+            }
+        };
+        let mut stats = self.stats.write().unwrap();
+        stats.request_count += 1;
+        stats.elapsed_time += started.elapsed();
+        match response {
+            Some(response) => response.replay(),
+            None => Err(RpcError::RpcRequestError(format!("No response recorded in {} for {} {}", self.path.display(), method, params)).into())
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.stats.read().unwrap().clone()
+    }
+
+    fn url(&self) -> String {
+        format!("replay:{}", self.path.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+    use solana_sdk::commitment_config::CommitmentConfig;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+    use crate::client::{get_client, ClientConfig};
+    use crate::errors::SourceError;
+    use crate::logic::{trace_transfers, TraceConfig};
+    use crate::signature::signature::SignatureConfig;
+    use crate::source::source::ChainSource;
+    use crate::transaction::decoder::DecoderRegistry;
+
+    /// Cassette recorded with `--record` from a trace of `ACCOUNT` at depth `0`, reading its two transactions
+    /// with a batch request. The RPC node was a local stub serving synthetic transactions, not a cluster.
+    const CASSETTE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cassettes/stub_trace.jsonl");
+    const ACCOUNT: &str = "AS5CnoZ7ezMhUXphn32H5Y2YAXnChdZAjjwywYYXuhgP";
+
+    fn replay() -> RpcClient {
+        get_client(ClientConfig { cassette: Some(CassetteMode::Replay(PathBuf::from(CASSETTE))), ..Default::default() }).unwrap()
+    }
+
+    #[test]
+    fn replay_cassette() {
+        let client = replay();
+        assert_eq!(client.get_version().unwrap().solana_core, "1.10.25");
+
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before: None,
+            until: None,
+            limit: Some(1000),
+            commitment: Some(CommitmentConfig::finalized())
+        };
+        let signatures = client.get_signatures(&Pubkey::from_str(ACCOUNT).unwrap(), config).unwrap();
+        assert_eq!(signatures.iter().map(|x| x.slot).collect::<Vec<_>>(), [100, 80]);
+
+        let signatures: Vec<Signature> = signatures.iter().map(|x| Signature::from_str(&x.signature).unwrap()).collect();
+        let transactions = client.get_transactions(&signatures);
+        assert_eq!(transactions.len(), 2);
+        for (signature, transaction) in signatures.iter().zip(transactions) {
+            let transaction: serde_json::Value = serde_json::from_str(&transaction.unwrap()).unwrap();
+            assert_eq!(transaction["transaction"]["signatures"][0], signature.to_string());
+        }
+    }
+
+    #[test]
+    fn replay_trace() {
+        let client = replay();
+        let config = SignatureConfig {
+            time_before: None,
+            time_after: None,
+            before: None,
+            until: None,
+            limit: Some(1000),
+            max: None,
+            commitment: Some(CommitmentConfig::finalized())
+        };
+        let trace = TraceConfig { depth: 0, batch_size: 2, ..Default::default() };
+        let result = trace_transfers(ACCOUNT, &client, &config, &DecoderRegistry::default(), &trace);
+        assert!(result.failures.is_empty() && result.frontier.is_empty(), "{:?}", result.failures);
+
+        let transfers: Vec<_> = result.transfers.iter().map(|x| (x.slot, x.source.as_str(), x.destination.as_str(), x.lamports)).collect();
+        assert_eq!(transfers, [
+            (80, "63QRh6CpNaGpV13A7eV99sdKhBE2z1Yh8knawNfMqqbm", ACCOUNT, 9),
+            (100, ACCOUNT, "65N9XRM5po8GpFU76FSCxBr4g98bHw9aEKQX4Sr5EPFi", 5)
+        ]);
+        assert_eq!((result.stats.accounts, result.stats.transactions, result.stats.rpc_calls), (1, 2, 3));
+    }
+
+    #[test]
+    fn replay_unmatched_request() {
+        let client = replay();
+        // The transactions were only recorded as a batch.
+        let signature = Signature::from_str("5PBPbWnJj6np1NX4LWeJoKytoayeL8Ys4qc5B2M8T5kh7i9XYB4vi3UxQbPLBMqxepUcMvDbr2hce52ZHGx38x3d").unwrap();
+        match ChainSource::get_transaction(&client, &signature) {
+            Err(SourceError::ClientError(err)) => assert!(err.contains("No response recorded"), "{}", err),
+            result => panic!("{:?}", result)
+        }
+        assert!(client.get_slot().is_err());
+    }
+}
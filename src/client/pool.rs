@@ -0,0 +1,478 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use futures::future::join_all;
+use rand::Rng;
+use tokio::time::sleep;
+use serde_json::Value;
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::rpc_custom_error::{
+    JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP,
+    JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+    JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+};
+use solana_client::rpc_request::{RpcError, RpcRequest};
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use super::batch::is_batch;
+use super::retry::{RetryMetrics, RetryPolicy, RetryReason};
+use super::sender::HeaderHttpSender;
+
+/// Interval between health checks of the endpoints of a pool.
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Maximum number of slots an endpoint may lag behind the most advanced endpoint of its pool and still
+/// be healthy.
+pub const MAX_SLOT_LAG: u64 = 150;
+/// JSON-RPC errors of endpoints that no longer hold the requested slot.
+const PRUNED_ERRORS: [i64; 2] = [JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP, JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE];
+
+/// RPC endpoint of a `PoolSender`.
+pub struct Endpoint {
+    sender: HeaderHttpSender,
+    /// Relative share of the requests sent to the endpoint.
+    weight: u32,
+    /// Whether the endpoint keeps the full ledger history.
+    archival: bool,
+    /// Result of the last health check, cleared when a request to the endpoint fails over.
+    healthy: AtomicBool
+}
+
+impl Endpoint {
+    /// Returns a new `Endpoint`, healthy until checked.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender` - Sender of the requests to the endpoint.
+    /// * `weight` - Relative share of the requests sent to the endpoint, zero to only use it when the others fail.
+    /// * `archival` - Whether the endpoint keeps the full ledger history.
+    pub fn new(sender: HeaderHttpSender, weight: u32, archival: bool) -> Self {
+        Endpoint { sender, weight, archival, healthy: AtomicBool::new(true) }
+    }
+}
+
+/// Route of a request, deciding which endpoints of a pool it is sent to first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Route {
+    /// Current state, e.g. balances, sent to fast endpoints first.
+    Fresh,
+    /// Transactions and blocks, sent to fast endpoints first and to archival endpoints if the fast
+    /// endpoint no longer holds them.
+    Ledger,
+    /// Signatures of an address and ranges of blocks, sent to archival endpoints first, as fast endpoints
+    /// only list the recent ones.
+    History
+}
+
+impl Route {
+    /// Return the route of a request. Batches take the route of their most demanding request.
+    fn of(request: &RpcRequest, params: &Value) -> Self {
+        if !is_batch(request) {
+            return Route::of_method(&request.to_string());
+        }
+        params.as_array().map_or(&[][..], |x| &x[..]).iter()
+            .map(|x| Route::of_method(x["method"].as_str().unwrap_or_default()))
+            .max()
+            .unwrap_or(Route::Fresh)
+    }
+
+    fn of_method(method: &str) -> Self {
+        match method {
+            "getSignaturesForAddress" | "getConfirmedSignaturesForAddress2"
+            | "getBlocks" | "getConfirmedBlocks"
+            | "getBlocksWithLimit" | "getConfirmedBlocksWithLimit"
+            | "getFirstAvailableBlock" => Route::History,
+            "getTransaction" | "getConfirmedTransaction"
+            | "getBlock" | "getConfirmedBlock"
+            | "getBlockTime" => Route::Ledger,
+            _ => Route::Fresh
+        }
+    }
+}
+
+/// `RpcSender` balancing the requests over a pool of endpoints, in proportion to their weight.
+/// Requests failing because their endpoint is unreachable, answers with an HTTP error or reports itself
+/// unhealthy are sent to the next endpoint. Endpoints are health checked with `getHealth` and `getSlot`
+/// every `HEALTH_CHECK_INTERVAL`, unhealthy endpoints and endpoints lagging more than `MAX_SLOT_LAG`
+/// slots behind are only used once the healthy ones fail. Signature history and block ranges are read
+/// from archival endpoints, transactions and blocks from fast endpoints and, once those no longer hold
+/// them, from archival endpoints. Each endpoint is sent a request once, without retries, requests failing
+/// with a transient error on every endpoint are retried on the pool according to the retry policy.
+pub struct PoolSender {
+    endpoints: Vec<Endpoint>,
+    /// Retry policy of requests that failed on every endpoint.
+    retry: RetryPolicy,
+    /// Failover counters, shared with the owner of the client.
+    metrics: Arc<RetryMetrics>,
+    /// Time of the last health check, `None` before the first.
+    checked: Mutex<Option<Instant>>
+}
+
+impl PoolSender {
+    /// Returns a new `PoolSender`.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoints` - Endpoints of the pool.
+    /// * `retry` - Retry policy of requests that failed on every endpoint.
+    /// * `metrics` - Retry counters to update.
+    pub fn new(endpoints: Vec<Endpoint>, retry: RetryPolicy, metrics: Arc<RetryMetrics>) -> Self {
+        PoolSender { endpoints, retry, metrics, checked: Mutex::new(None) }
+    }
+
+    /// Check the health of the endpoints if the last check is older than `HEALTH_CHECK_INTERVAL`. Only
+    /// one caller runs the check, the others keep using the previous results. The endpoints are checked
+    /// concurrently, so the check takes at most one request timeout. Checks are not retried.
+    async fn check_health(&self) {
+        {
+            let mut checked = self.checked.lock().unwrap();
+            if checked.is_some_and(|x| x.elapsed() < HEALTH_CHECK_INTERVAL) {
+                return;
+            }
+            *checked = Some(Instant::now());
+        }
+        let retry = RetryPolicy { max_retries: 0, ..self.retry };
+        let checks = join_all(self.endpoints.iter().map(|endpoint| async {
+            let (health, slot) = futures::join!(
+                endpoint.sender.send_with(RpcRequest::GetHealth, Value::Null, &retry),
+                endpoint.sender.send_with(RpcRequest::GetSlot, Value::Null, &retry)
+            );
+            // Endpoints that do not support `getHealth` are only unhealthy if they fail like unhealthy ones.
+            let healthy = match health {
+                Err(err) => !is_failover_error(&err),
+                Ok(_) => true
+            };
+            (healthy, slot.ok().and_then(|x| x.as_u64()).filter(|_| healthy))
+        })).await;
+        let max_slot = checks.iter().filter_map(|x| x.1).max().unwrap_or_default();
+        for (endpoint, (healthy, slot)) in self.endpoints.iter().zip(checks) {
+            let lagging = slot.is_some_and(|x| max_slot.saturating_sub(x) > MAX_SLOT_LAG);
+            endpoint.healthy.store(healthy && !lagging, Ordering::Relaxed);
+        }
+    }
+
+    /// Return the order to try the endpoints in for a request of `route`: healthy endpoints first, then
+    /// the endpoints suited to the route, in a random order weighted by the endpoint weights.
+    fn order(&self, route: Route) -> Vec<usize> {
+        let mut rng = rand::thread_rng();
+        let mut keys: Vec<(bool, bool, f64, usize)> = self.endpoints.iter().enumerate().map(|(index, endpoint)| {
+            // Sorting by `-ln(u) / weight`, with `u` uniform in (0, 1], samples without replacement
+            // in proportion to the weights.
+            let key = match endpoint.weight {
+                0 => f64::INFINITY,
+                weight => -(1.0 - rng.gen::<f64>()).ln() / weight as f64
+            };
+            let unhealthy = !endpoint.healthy.load(Ordering::Relaxed);
+            let unsuited = endpoint.archival != (route == Route::History);
+            (unhealthy, unsuited, key, index)
+        }).collect();
+        keys.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)).then(a.2.total_cmp(&b.2)));
+        keys.into_iter().map(|x| x.3).collect()
+    }
+
+    /// Send a request to the endpoints in `order`, until one does not fail with a failover or transient
+    /// error. Each endpoint is sent the request once, if it failed with a transient error on every endpoint
+    /// it is sent to them again after a backoff, up to the `max_retries` of the pool's retry policy.
+    /// Returns the index of the endpoint that answered, if any, and its result.
+    async fn send_to(&self, order: &[usize], request: RpcRequest, params: &Value) -> (Option<usize>, ClientResult<Value>) {
+        let once = RetryPolicy { max_retries: 0, ..self.retry };
+        let mut result = Err(RpcError::RpcRequestError("No RPC endpoint".to_string()).into());
+        let mut retries = 0;
+        loop {
+            let mut reason = None;
+            for (attempt, index) in order.iter().enumerate() {
+                if attempt > 0 {
+                    self.metrics.record_failover();
+                }
+                let endpoint = &self.endpoints[*index];
+                result = endpoint.sender.send_with(request, params.clone(), &once).await;
+                let err = match &result {
+                    Ok(_) => return (Some(*index), result),
+                    Err(err) => err
+                };
+                reason = RetryReason::from_client_error(err);
+                if is_failover_error(err) {
+                    endpoint.healthy.store(false, Ordering::Relaxed);
+                } else if reason.is_none() {
+                    return (Some(*index), result);
+                }
+            }
+            let reason = match reason {
+                Some(reason) if retries < self.retry.max_retries => reason,
+                Some(_) => {
+                    self.metrics.record_exhausted();
+                    return (None, result);
+                },
+                None => return (None, result)
+            };
+            let delay = self.retry.backoff(retries);
+            self.metrics.record_retry(reason, delay);
+            retries += 1;
+            sleep(delay).await;
+        }
+    }
+
+    /// Send the requests of a batch whose result is pruned from the fast endpoint that answered it to the
+    /// `archival` endpoints, and replace their results.
+    async fn fill_batch(&self, archival: &[usize], request: RpcRequest, params: &Value, results: &mut Value) {
+        let pruned: Vec<usize> = match results.as_array() {
+            Some(items) => items.iter().enumerate().filter(|x| is_pruned_item(x.1)).map(|x| x.0).collect(),
+            None => return
+        };
+        if pruned.is_empty() {
+            return;
+        }
+        let params = Value::Array(pruned.iter().map(|x| params[*x].clone()).collect());
+        if let (_, Ok(Value::Array(items))) = self.send_to(archival, request, &params).await {
+            for (index, item) in pruned.into_iter().zip(items) {
+                if !is_pruned_item(&item) {
+                    results[index] = item;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RpcSender for PoolSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        self.check_health().await;
+        let route = Route::of(&request, &params);
+        let order = self.order(route);
+        let (index, result) = self.send_to(&order, request, &params).await;
+
+        // Fast endpoints only hold recent transactions and blocks, older ones are read from archival endpoints.
+        let fast = index.is_some_and(|x| !self.endpoints[x].archival);
+        let archival: Vec<usize> = order.into_iter().filter(|x| self.endpoints[*x].archival).collect();
+        if route != Route::Ledger || !fast || archival.is_empty() {
+            return result;
+        }
+        if is_batch(&request) {
+            let mut results = result?;
+            self.fill_batch(&archival, request, &params, &mut results).await;
+            return Ok(results);
+        }
+        if !is_pruned(&result) {
+            return result;
+        }
+        match self.send_to(&archival, request, &params).await {
+            (Some(_), archived) => archived,
+            (None, _) => result
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        let mut total = RpcTransportStats::default();
+        for stats in self.endpoints.iter().map(|x| x.sender.get_transport_stats()) {
+            total.request_count += stats.request_count;
+            total.elapsed_time += stats.elapsed_time;
+            total.rate_limited_time += stats.rate_limited_time;
+        }
+        total
+    }
+
+    fn url(&self) -> String {
+        self.endpoints.iter().map(|x| x.sender.url()).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Whether a request failing with `err` is sent to another endpoint: the endpoint could not be reached,
+/// answered with an HTTP error or reported itself unhealthy. Batches the endpoint rejected, see
+/// `rejected_batch`, are not sent to another endpoint.
+fn is_failover_error(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => true,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => *code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+        _ => false
+    }
+}
+
+/// Whether the result of a transaction or block request shows the endpoint no longer holds it.
+fn is_pruned(result: &ClientResult<Value>) -> bool {
+    match result {
+        Ok(value) => value.is_null(),
+        Err(err) => matches!(err.kind(), ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) if PRUNED_ERRORS.contains(code))
+    }
+}
+
+/// Whether the result of a request of a batch shows the endpoint no longer holds it, see `is_pruned`.
+fn is_pruned_item(item: &Value) -> bool {
+    item.get("result").is_some_and(Value::is_null)
+        || item["error"]["code"].as_i64().is_some_and(|x| PRUNED_ERRORS.contains(&x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use serde_json::json;
+    use reqwest::header::HeaderMap;
+    use crate::client::batch::{batch_request, is_unsupported};
+    use crate::client::stub::{self, StubServer};
+
+    /// Run `future` to completion on a new runtime.
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(future)
+    }
+
+    /// Stub endpoint at `slot`, answering requests other than health checks with `respond`.
+    fn node<F: Fn(&Value) -> (u16, String) + Send + Sync + 'static>(slot: u64, respond: F) -> StubServer {
+        StubServer::start(move |request| match request["method"].as_str() {
+            Some("getHealth") => (200, stub::result(request, json!("ok"))),
+            Some("getSlot") => (200, stub::result(request, json!(slot))),
+            _ => respond(request)
+        })
+    }
+
+    /// Stub endpoint at `slot` holding a balance of `lamports`.
+    fn balance(slot: u64, lamports: u64) -> StubServer {
+        node(slot, move |request| (200, stub::result(request, json!({"context": {"slot": slot}, "value": lamports}))))
+    }
+
+    /// Pool of `nodes` with `weights`, timing out requests after half a second and retrying requests that
+    /// failed on every endpoint `max_retries` times.
+    fn pool(nodes: &[StubServer], weights: &[u32], max_retries: usize) -> PoolSender {
+        let retry = RetryPolicy { max_retries, initial_backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(1) };
+        let metrics = Arc::new(RetryMetrics::default());
+        let endpoints = nodes.iter().zip(weights).map(|(node, weight)| {
+            let sender = HeaderHttpSender::new(node.url.clone(), Duration::from_millis(500), HeaderMap::new(), retry, None, metrics.clone()).unwrap();
+            Endpoint::new(sender, *weight, false)
+        }).collect();
+        PoolSender::new(endpoints, retry, metrics)
+    }
+
+    fn healthy(pool: &PoolSender) -> Vec<bool> {
+        pool.endpoints.iter().map(|x| x.healthy.load(Ordering::Relaxed)).collect()
+    }
+
+    fn count(node: &StubServer, method: &str) -> usize {
+        node.methods().iter().filter(|x| *x == method).count()
+    }
+
+    #[test]
+    fn route_requests() {        for (method, route) in [
+            ("getBalance", Route::Fresh),
+            ("getSlot", Route::Fresh),
+            ("getTransaction", Route::Ledger),
+            ("getBlock", Route::Ledger),
+            ("getBlockTime", Route::Ledger),
+            ("getSignaturesForAddress", Route::History),
+            ("getBlocks", Route::History),
+            ("getBlocksWithLimit", Route::History),
+            ("getFirstAvailableBlock", Route::History)
+        ] {
+            assert_eq!(Route::of_method(method), route, "{}", method);
+        }
+        assert_eq!(Route::of(&RpcRequest::GetBlockTime, &json!([100])), Route::Ledger);
+
+        let (request, params) = batch_request(&[
+            (RpcRequest::GetBalance, json!(["account"])),
+            (RpcRequest::GetBlocksWithLimit, json!([100, 10])),
+            (RpcRequest::GetTransaction, json!(["signature", "json"]))
+        ]);
+        assert_eq!(Route::of(&request, &params), Route::History);
+    }
+
+    #[test]
+    fn fail_over_after_server_error() {
+        // The endpoint with weight zero is only used once the other fails.
+        let nodes = [node(100, |_| (503, String::new())), balance(100, 7)];
+        let pool = pool(&nodes, &[1, 0], 0);
+        for _ in 0..2 {
+            let result = block_on(pool.send(RpcRequest::GetBalance, json!(["account"]))).unwrap();
+            assert_eq!(result["value"], 7);
+        }
+        assert_eq!(healthy(&pool), [false, true]);
+        // The unhealthy endpoint is tried last once it failed.
+        assert_eq!((count(&nodes[0], "getBalance"), count(&nodes[1], "getBalance")), (1, 2));
+        assert_eq!(pool.metrics.stats().failovers, 1);
+    }
+
+    #[test]
+    fn fail_over_after_timeout() {
+        let slow = node(100, |request| {
+            thread::sleep(Duration::from_secs(2));
+            (200, stub::result(request, json!({"context": {"slot": 100}, "value": 1})))
+        });
+        let nodes = [slow, balance(100, 7)];
+        let pool = pool(&nodes, &[1, 0], 0);
+        let started = Instant::now();
+        let result = block_on(pool.send(RpcRequest::GetBalance, json!(["account"]))).unwrap();
+        assert_eq!(result["value"], 7);
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+        assert_eq!(healthy(&pool), [false, true]);
+    }
+
+    #[test]
+    fn retry_on_the_pool() {
+        let nodes = [node(100, |_| (503, String::new())), node(100, |_| (503, String::new()))];
+        let pool = pool(&nodes, &[1, 1], 2);
+        assert!(block_on(pool.send(RpcRequest::GetBalance, json!(["account"]))).is_err());
+        // Each endpoint is sent the request once per attempt, without retrying it on its own.
+        assert_eq!((count(&nodes[0], "getBalance"), count(&nodes[1], "getBalance")), (3, 3));
+        let stats = pool.metrics.stats();
+        assert_eq!((stats.retries, stats.server_errors, stats.exhausted, stats.failovers), (2, 2, 1, 3));
+    }
+
+    #[test]
+    fn mark_unhealthy_endpoints() {
+        let unhealthy = StubServer::start(|request| match request["method"].as_str() {
+            Some("getHealth") => (200, stub::error(request, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY, "Node is behind by 500 slots")),
+            _ => (200, stub::result(request, json!(500)))
+        });
+        // `getHealth` is not supported by every provider.
+        let unsupported = StubServer::start(|request| match request["method"].as_str() {
+            Some("getHealth") => (200, stub::error(request, -32601, "Method not found")),
+            _ => (200, stub::result(request, json!(1000)))
+        });
+        let nodes = [balance(1000, 7), balance(1000 - MAX_SLOT_LAG - 1, 7), balance(1000 - MAX_SLOT_LAG, 7), unhealthy, unsupported];
+        let pool = pool(&nodes, &[1; 5], 0);
+        block_on(pool.check_health());
+        assert_eq!(healthy(&pool), [true, false, true, false, true]);
+        for _ in 0..20 {
+            let order = pool.order(Route::Fresh);
+            assert!(order[3..].iter().all(|x| [1, 3].contains(x)), "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn check_health_concurrently() {
+        let delayed = || StubServer::start(|request| {
+            thread::sleep(Duration::from_millis(300));
+            (200, stub::result(request, json!(100)))
+        });
+        let nodes = [delayed(), delayed(), delayed()];
+        let pool = pool(&nodes, &[1; 3], 0);
+        let started = Instant::now();
+        block_on(pool.check_health());
+        // Sent one after the other, the probes would take 1.8 seconds.
+        assert!(started.elapsed() < Duration::from_millis(900), "{:?}", started.elapsed());
+        assert_eq!(healthy(&pool), [true; 3]);
+    }
+
+    #[test]
+    fn order_by_weight() {
+        let nodes = [balance(100, 7), balance(100, 7), balance(100, 7)];
+        let pool = pool(&nodes, &[3, 1, 0], 0);
+        let mut first = [0; 3];
+        for _ in 0..10_000 {
+            let order = pool.order(Route::Fresh);
+            assert_eq!(order[2], 2);
+            first[order[0]] += 1;
+        }
+        assert!((7_000..8_000).contains(&first[0]), "{:?}", first);
+        assert_eq!(first[2], 0);
+    }
+
+    #[test]
+    fn keep_endpoints_rejecting_batches() {
+        let nodes = [node(100, |_| (413, String::new())), node(100, |_| (413, String::new()))];
+        let pool = pool(&nodes, &[1, 1], 0);
+        let (request, params) = batch_request(&[(RpcRequest::GetSlot, Value::Null), (RpcRequest::GetSlot, Value::Null)]);
+        let err = block_on(pool.send(request, params)).unwrap_err();
+        assert!(is_unsupported(&err), "{:?}", err);
+
+        // The rejection is not a failure of the endpoint, so the batch is not sent to the other one.
+        assert_eq!(count(&nodes[0], "batch") + count(&nodes[1], "batch"), 1);
+        assert_eq!(healthy(&pool), [true, true]);
+    }
+}
@@ -0,0 +1,289 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::StatusCode;
+use serde::Serialize;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_request::RpcError;
+use solana_client::rpc_custom_error::{
+    JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+    JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+    JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+};
+
+/// Default maximum number of retries of a failed request.
+pub const DEFAULT_MAX_RETRIES: usize = 5;
+/// Default delay before the first retry, doubled on every further retry.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Default maximum delay between two retries.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Longest `Retry-After` honored. Requests asked to wait longer are retried after this delay, so a
+/// misbehaving endpoint cannot stall the caller indefinitely.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Retry policy of RPC requests failing with a transient error, see `RetryReason`.
+/// Contains `max_retries`, `initial_backoff` and `max_backoff` fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries of a request, zero to never retry.
+    pub max_retries: usize,
+    /// Delay before the first retry, doubled on every further retry.
+    pub initial_backoff: Duration,
+    /// Maximum delay between two retries.
+    pub max_backoff: Duration
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Return the delay before retry number `retry`, counted from zero. The delay grows exponentially
+    /// up to `max_backoff` and is randomly reduced by up to half, so clients throttled together do not
+    /// retry together.
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let factor = u32::try_from(retry).ok().and_then(|x| 1u32.checked_shl(x)).unwrap_or(u32::MAX);
+        let backoff = self.initial_backoff.saturating_mul(factor).min(self.max_backoff);
+        backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// JSON-RPC error codes RPC providers report rate limiting with, in responses with HTTP 200.
+pub const RATE_LIMIT_ERRORS: [i64; 2] = [429, -32429];
+/// JSON-RPC error codes of a node that is behind the cluster, or not yet at the requested slot.
+pub const NODE_BEHIND_ERRORS: [i64; 3] = [
+    JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+    JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+];
+
+/// Transient error a request is retried for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryReason {
+    /// The endpoint responded with HTTP 429 or a rate limit error, see `RATE_LIMIT_ERRORS`.
+    RateLimited,
+    /// The endpoint responded with HTTP 500, 502, 503 or 504, or reported it is behind, see `NODE_BEHIND_ERRORS`.
+    ServerError,
+    /// The request timed out or failed to connect.
+    TransportError
+}
+
+impl RetryReason {
+    /// Return the reason a response with `status` is retried, if its error is transient.
+    pub fn from_status(status: StatusCode) -> Option<Self> {
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => Some(RetryReason::RateLimited),
+            StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => Some(RetryReason::ServerError),
+            _ => None
+        }
+    }
+
+    /// Return the reason a request failing with `err` is retried, if the error is transient.
+    pub fn from_error(err: &reqwest::Error) -> Option<Self> {
+        (err.is_timeout() || err.is_connect()).then_some(RetryReason::TransportError)
+    }
+
+    /// Return the reason a request answered with a JSON-RPC error of `code` is retried, if the error is
+    /// transient. Providers and lagging nodes answer with these errors while responding with HTTP 200.
+    pub fn from_rpc_error(code: i64) -> Option<Self> {
+        if RATE_LIMIT_ERRORS.contains(&code) {
+            Some(RetryReason::RateLimited)
+        } else if NODE_BEHIND_ERRORS.contains(&code) {
+            Some(RetryReason::ServerError)
+        } else {
+            None
+        }
+    }
+
+    /// Return the reason a request failing with the client error `err` is retried, if the error is
+    /// transient, i.e. a transient HTTP status, transport error or JSON-RPC error.
+    pub fn from_client_error(err: &ClientError) -> Option<Self> {
+        match err.kind() {
+            ClientErrorKind::Reqwest(err) => RetryReason::from_error(err).or_else(|| err.status().and_then(RetryReason::from_status)),
+            ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => RetryReason::from_rpc_error(*code),
+            _ => None
+        }
+    }
+}
+
+/// Parse a `Retry-After` header, formatted as a number of seconds or as an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+}
+
+/// Client-side token bucket limiting the rate of requests sent to an endpoint. The bucket holds one
+/// second of requests, so short bursts are sent without delay.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Requests per second.
+    rate: f64,
+    /// Maximum number of tokens in the bucket.
+    capacity: f64,
+    /// Tokens in the bucket and when they were counted. Tokens are negative while reserved by waiting
+    /// requests.
+    bucket: Mutex<(f64, Instant)>
+}
+
+impl RateLimiter {
+    /// Returns a new `RateLimiter` with a full bucket.
+    ///
+    /// # Arguments
+    ///
+    /// * `rate` - Requests per second, must be positive.
+    pub fn new(rate: f64) -> Self {
+        let capacity = rate.max(1.0);
+        RateLimiter { rate, capacity, bucket: Mutex::new((capacity, Instant::now())) }
+    }
+
+    /// Reserve `count` requests and return how long to wait before sending them.
+    pub fn reserve(&self, count: usize) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let refilled = bucket.0 + now.duration_since(bucket.1).as_secs_f64() * self.rate;
+        let tokens = refilled.min(self.capacity) - count as f64;
+        *bucket = (tokens, now);
+        if tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-tokens / self.rate)
+        }
+    }
+}
+
+/// Counters of the retries, failovers and rate limiting of the requests sent by a client.
+/// Contains `retries`, `rate_limited`, `server_errors`, `transport_errors`, `exhausted`, `failovers`,
+/// `backoff_time` and `throttled_time` fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RetryStats {
+    /// Requests retried.
+    pub retries: u64,
+    /// Retries after an HTTP 429 response or a rate limit error.
+    pub rate_limited: u64,
+    /// Retries after an HTTP 5xx response or a node behind error.
+    pub server_errors: u64,
+    /// Retries after a timeout or connection failure.
+    pub transport_errors: u64,
+    /// Requests that failed with a transient error after all retries.
+    pub exhausted: u64,
+    /// Requests sent to another endpoint of a pool after their endpoint failed, see `PoolSender`.
+    pub failovers: u64,
+    /// Time waited before retries.
+    pub backoff_time: Duration,
+    /// Time waited for the client-side rate limiter.
+    pub throttled_time: Duration
+}
+
+/// Counters of the retries, failovers and rate limiting of the requests sent by a client, shared
+/// between the client and its owner, see `RetryStats`.
+#[derive(Debug, Default)]
+pub struct RetryMetrics {
+    retries: AtomicU64,
+    rate_limited: AtomicU64,
+    server_errors: AtomicU64,
+    transport_errors: AtomicU64,
+    exhausted: AtomicU64,
+    failovers: AtomicU64,
+    /// Time waited before retries, in microseconds.
+    backoff_time: AtomicU64,
+    /// Time waited for the rate limiter, in microseconds.
+    throttled_time: AtomicU64
+}
+
+impl RetryMetrics {
+    /// Count a retry after `delay`.
+    pub fn record_retry(&self, reason: RetryReason, delay: Duration) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+        let counter = match reason {
+            RetryReason::RateLimited => &self.rate_limited,
+            RetryReason::ServerError => &self.server_errors,
+            RetryReason::TransportError => &self.transport_errors
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.backoff_time.fetch_add(delay.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Count a request that failed with a transient error without being retried.
+    pub fn record_exhausted(&self) {
+        self.exhausted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a request sent to another endpoint after its endpoint failed.
+    pub fn record_failover(&self) {
+        self.failovers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a wait of `delay` for the rate limiter.
+    pub fn record_throttle(&self, delay: Duration) {
+        self.throttled_time.fetch_add(delay.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Return the counters.
+    pub fn stats(&self) -> RetryStats {
+        RetryStats {
+            retries: self.retries.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            server_errors: self.server_errors.load(Ordering::Relaxed),
+            transport_errors: self.transport_errors.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+            failovers: self.failovers.load(Ordering::Relaxed),
+            backoff_time: Duration::from_micros(self.backoff_time.load(Ordering::Relaxed)),
+            throttled_time: Duration::from_micros(self.throttled_time.load(Ordering::Relaxed))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_retry_after_header() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        let date = (Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let retry_after = parse_retry_after(&date).unwrap();
+        assert!(retry_after > Duration::from_secs(55) && retry_after <= Duration::from_secs(60), "{:?}", retry_after);
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn classify_rpc_errors() {
+        assert_eq!(RetryReason::from_rpc_error(JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY), Some(RetryReason::ServerError));
+        assert_eq!(RetryReason::from_rpc_error(JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED), Some(RetryReason::ServerError));
+        assert_eq!(RetryReason::from_rpc_error(-32429), Some(RetryReason::RateLimited));
+        assert_eq!(RetryReason::from_rpc_error(429), Some(RetryReason::RateLimited));
+        // Pruned blocks and invalid requests fail the same way when retried.
+        assert_eq!(RetryReason::from_rpc_error(-32001), None);
+        assert_eq!(RetryReason::from_rpc_error(-32600), None);
+    }
+
+    #[test]
+    fn back_off_exponentially() {
+        let policy = RetryPolicy { max_retries: 10, initial_backoff: Duration::from_secs(1), max_backoff: Duration::from_secs(10) };
+        for (retry, expected) in [(0, 1), (1, 2), (2, 4), (3, 8), (4, 10), (40, 10), (usize::MAX, 10)] {
+            let expected = Duration::from_secs(expected);
+            for _ in 0..20 {
+                let backoff = policy.backoff(retry);
+                assert!(backoff >= expected / 2 && backoff <= expected, "retry {}: {:?}", retry, backoff);
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio::time::sleep;
+use solana_client::client_error::Result as ClientResult;
+use solana_client::rpc_request::{RpcError, RpcRequest, RpcResponseErrorData};
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use super::batch::{batch_result, build_batch_json, is_batch, rejected_batch};
+use super::retry::{parse_retry_after, RateLimiter, RetryMetrics, RetryPolicy, RetryReason, MAX_RETRY_AFTER};
+
+/// JSON-RPC error object returned by the endpoint.
+#[derive(Deserialize, Debug)]
+struct RpcErrorObject {
+    code: i64,
+    message: String
+}
+
+/// `RpcSender` over HTTP that attaches custom headers to every request, e.g. for
+/// authenticated RPC providers. Batch requests, see `BATCH_METHOD`, are sent as JSON-RPC batches, and
+/// fail with a `rejected_batch` error if the endpoint answers them with an HTTP client error other than
+/// 429 or with anything but an array of responses.
+/// Requests failing with a transient error are retried according to the retry policy, and throttled
+/// by the rate limiter if one is set.
+pub struct HeaderHttpSender {
+    /// HTTP client, configured with the timeout and default headers.
+    client: reqwest::Client,
+    /// RPC endpoint.
+    url: String,
+    /// Incrementing JSON-RPC request id.
+    request_id: AtomicU64,
+    /// Transport statistics.
+    stats: RwLock<RpcTransportStats>,
+    retry: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    /// Retry counters, shared with the owner of the client.
+    metrics: Arc<RetryMetrics>
+}
+
+impl HeaderHttpSender {
+    /// Returns a new `HeaderHttpSender`.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - RPC endpoint.
+    /// * `timeout` - Request timeout.
+    /// * `headers` - Headers attached to every request.
+    /// * `retry` - Retry policy of requests failing with a transient error.
+    /// * `rate_limiter` - Optional limit of the requests sent per second.
+    /// * `metrics` - Retry counters to update.
+    pub fn new(url: String, timeout: Duration, headers: HeaderMap, retry: RetryPolicy, rate_limiter: Option<RateLimiter>, metrics: Arc<RetryMetrics>) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .default_headers(headers)
+            .build()?;
+        Ok(HeaderHttpSender {
+            client,
+            url,
+            request_id: AtomicU64::new(0),
+            stats: RwLock::new(RpcTransportStats::default()),
+            retry,
+            rate_limiter,
+            metrics
+        })
+    }
+
+    /// Update transport statistics after a request.
+    fn record(&self, started: Instant, rate_limited_time: Duration) {
+        let mut stats = self.stats.write().unwrap();
+        stats.request_count += 1;
+        stats.elapsed_time += started.elapsed();
+        stats.rate_limited_time += rate_limited_time;
+    }
+
+    /// Send a request with a specific retry policy instead of the sender's, e.g. to send health checks
+    /// without retrying. Requests still failing with a transient error are not counted as exhausted,
+    /// callers count them once they give up on the request.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - RPC request.
+    /// * `params` - Request parameters.
+    /// * `retry` - Retry policy of the request.
+    pub async fn send_with(&self, request: RpcRequest, params: serde_json::Value, retry: &RetryPolicy) -> ClientResult<serde_json::Value> {
+
+        let started = Instant::now();
+        let mut rate_limited_time = Duration::default();
+        // Batches are numbered with consecutive ids, see `batch_result`.
+        let batch = is_batch(&request).then(|| params.as_array().map_or(0, Vec::len));
+        let request_id = self.request_id.fetch_add(batch.unwrap_or(1).max(1) as u64, Ordering::Relaxed);
+        let request_json = match batch {
+            Some(_) => build_batch_json(&params, request_id).to_string(),
+            None => build_request_json(request, request_id, params).to_string()
+        };
+
+        let mut retries = 0;
+        let result = loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                let wait = rate_limiter.reserve(batch.unwrap_or(1));
+                if !wait.is_zero() {
+                    self.metrics.record_throttle(wait);
+                    sleep(wait).await;
+                    rate_limited_time += wait;
+                }
+            }
+            let sent = self.client
+                .post(&self.url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(request_json.clone())
+                .send()
+                .await;
+
+            // JSON-RPC errors are only known once the body is read, so the response is read before deciding to retry.
+            let (result, retry_after) = match sent {
+                Ok(response) => {
+                    let retry_after = response.headers().get(RETRY_AFTER)
+                        .and_then(|x| x.to_str().ok())
+                        .and_then(parse_retry_after);
+                    (read_response(response, batch, request_id).await, retry_after)
+                },
+                Err(err) => (Err(err.into()), None)
+            };
+            let reason = match result.as_ref().err().and_then(RetryReason::from_client_error) {
+                Some(reason) if retries < retry.max_retries => reason,
+                _ => break result
+            };
+            // `Retry-After` is honored up to `MAX_RETRY_AFTER`, otherwise the delay backs off exponentially.
+            let delay = match retry_after {
+                Some(retry_after) => retry_after.min(MAX_RETRY_AFTER),
+                None => retry.backoff(retries)
+            };
+            self.metrics.record_retry(reason, delay);
+            retries += 1;
+            sleep(delay).await;
+            if reason == RetryReason::RateLimited {
+                rate_limited_time += delay;
+            }
+        };
+        self.record(started, rate_limited_time);
+        result
+    }
+}
+
+/// Read the result of a request from `response`. `batch` is the number of requests of a batch request,
+/// numbered from `request_id`, `None` for other requests.
+async fn read_response(response: reqwest::Response, batch: Option<usize>, request_id: u64) -> ClientResult<serde_json::Value> {
+    if let Err(err) = response.error_for_status_ref() {
+        let status = response.status();
+        if batch.is_some() && status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+            return Err(rejected_batch(&err.to_string()));
+        }
+        return Err(err.into());
+    }
+
+    let mut json = response.json::<serde_json::Value>().await?;
+    if let Some(count) = batch {
+        if !json.is_array() {
+            let reason = json["error"]["message"].as_str().map_or_else(|| json.to_string(), str::to_string);
+            return Err(rejected_batch(&reason));
+        }
+        return Ok(batch_result(json, request_id, count));
+    }
+    if json["error"].is_object() {
+        return match serde_json::from_value::<RpcErrorObject>(json["error"].clone()) {
+            Ok(error) => Err(RpcError::RpcResponseError {
+                code: error.code,
+                message: error.message,
+                data: RpcResponseErrorData::Empty
+            }.into()),
+            Err(err) => Err(RpcError::RpcRequestError(format!("Failed to deserialize RPC error response: {} [{}]", json["error"], err)).into())
+        };
+    }
+    Ok(json["result"].take())
+}
+
+#[async_trait]
+impl RpcSender for HeaderHttpSender {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+        let result = self.send_with(request, params, &self.retry).await;
+        if result.as_ref().err().and_then(RetryReason::from_client_error).is_some() {
+            self.metrics.record_exhausted();
+        }
+        result
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.stats.read().unwrap().clone()
+    }
+
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// `RpcSender` that fails every request without sending it, for offline use.
+pub struct OfflineSender;
+
+#[async_trait]
+impl RpcSender for OfflineSender {
+    async fn send(&self, request: RpcRequest, _params: serde_json::Value) -> ClientResult<serde_json::Value> {
+        Err(RpcError::RpcRequestError(format!("Offline, {} was not sent", request)).into())
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "offline".to_string()
+    }
+}
+
+/// Build a JSON-RPC 2.0 request body.
+pub fn build_request_json(request: RpcRequest, id: u64, params: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": request.to_string(),
+        "params": params
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+    use crate::client::batch::{batch_request, batch_results, is_unsupported};
+    use crate::client::stub::{self, StubServer};
+
+    fn client(url: &str) -> RpcClient {
+        let retry = RetryPolicy { max_retries: 0, ..Default::default() };
+        let sender = HeaderHttpSender::new(url.to_string(), Duration::from_secs(5), HeaderMap::new(), retry, None, Arc::new(RetryMetrics::default())).unwrap();
+        RpcClient::new_sender(sender, RpcClientConfig::default())
+    }
+
+    #[test]
+    fn reject_batches() {
+        let (request, params) = batch_request(&[(RpcRequest::GetSlot, Value::Null), (RpcRequest::GetBlockHeight, Value::Null)]);
+        for (status, body) in [
+            (413, String::new()),
+            (400, json!({"jsonrpc": "2.0", "id": null, "error": {"code": -32600, "message": "Invalid request"}}).to_string()),
+            (200, json!({"jsonrpc": "2.0", "id": null, "error": {"code": -32000, "message": "Batch requests are not allowed"}}).to_string()),
+            (200, json!({"jsonrpc": "2.0", "id": null, "result": null}).to_string())
+        ] {
+            let server = StubServer::start(move |_| (status, body.clone()));
+            let err = client(&server.url).send::<Value>(request, params.clone()).unwrap_err();
+            assert!(is_unsupported(&err), "{}: {:?}", status, err);
+        }
+
+        // Rate limits are not rejections.
+        let server = StubServer::start(|_| (429, String::new()));
+        assert!(!is_unsupported(&client(&server.url).send::<Value>(request, params.clone()).unwrap_err()));
+
+        let server = StubServer::start(|request| {
+            let responses = request.as_array().unwrap().iter().map(|x| json!({"jsonrpc": "2.0", "id": x["id"], "result": x["method"]})).collect();
+            (200, Value::Array(responses).to_string())
+        });
+        let results = batch_results(client(&server.url).send(request, params).unwrap(), 2);
+        assert_eq!(results.into_iter().map(Result::unwrap).collect::<Vec<_>>(), ["getSlot", "getBlockHeight"]);
+        assert_eq!(server.methods(), ["batch"]);
+
+        let server = StubServer::start(|request| (200, stub::result(request, json!(42))));
+        assert_eq!(client(&server.url).send::<u64>(RpcRequest::GetSlot, Value::Null).unwrap(), 42);
+    }
+
+    #[test]
+    fn retry_rpc_errors() {
+        let retry = RetryPolicy { max_retries: 2, initial_backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(1) };
+        let metrics = Arc::new(RetryMetrics::default());
+        let send = |url: &str| {
+            let sender = HeaderHttpSender::new(url.to_string(), Duration::from_secs(5), HeaderMap::new(), retry, None, metrics.clone()).unwrap();
+            RpcClient::new_sender(sender, RpcClientConfig::default()).send::<u64>(RpcRequest::GetSlot, Value::Null).ok()
+        };
+
+        // The node is behind for the first request, then catches up.
+        let requests = AtomicU64::new(0);
+        let server = StubServer::start(move |request| match requests.fetch_add(1, Ordering::Relaxed) {
+            0 => (200, stub::error(request, -32005, "Node is behind by 42 slots")),
+            _ => (200, stub::result(request, json!(42)))
+        });
+        assert_eq!(send(&server.url), Some(42));
+        assert_eq!(server.methods().len(), 2);
+
+        let server = StubServer::start(|request| (200, stub::error(request, -32429, "Rate limit exceeded")));
+        assert_eq!(send(&server.url), None);
+        assert_eq!(server.methods().len(), 3);
+
+        // Other errors are final.
+        let server = StubServer::start(|request| (200, stub::error(request, -32602, "Invalid params")));
+        assert_eq!(send(&server.url), None);
+        assert_eq!(server.methods().len(), 1);
+
+        let stats = metrics.stats();
+        assert_eq!((stats.retries, stats.server_errors, stats.rate_limited, stats.exhausted), (3, 1, 2, 1));
+    }
+}
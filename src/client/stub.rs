@@ -0,0 +1,73 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use serde_json::Value;
+
+/// Local HTTP server answering JSON-RPC requests with canned responses, for tests of the senders.
+/// Every connection is answered once and closed.
+pub struct StubServer {
+    /// URL of the server.
+    pub url: String,
+    /// Bodies of the requests received, in order.
+    requests: Arc<Mutex<Vec<Value>>>
+}
+
+impl StubServer {
+    /// Start a server answering each request with `respond(request)`, as `(status, body)`.
+    pub fn start<F: Fn(&Value) -> (u16, String) + Send + Sync + 'static>(respond: F) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let received = requests.clone();
+        let respond = Arc::new(respond);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (received, respond) = (received.clone(), respond.clone());
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(&stream);
+                    let mut length = 0;
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).is_ok_and(|x| x > 2) {
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                length = value.trim().parse().unwrap_or_default();
+                            }
+                        }
+                        line.clear();
+                    }
+                    let mut body = vec![0; length];
+                    if reader.read_exact(&mut body).is_err() {
+                        return;
+                    }
+                    let request: Value = serde_json::from_slice(&body).unwrap_or_default();
+                    received.lock().unwrap().push(request.clone());
+                    let (status, body) = respond(&request);
+                    let response = format!(
+                        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status, body.len(), body
+                    );
+                    let _ = (&stream).write_all(response.as_bytes());
+                });
+            }
+        });
+        StubServer { url, requests }
+    }
+
+    /// Return the methods of the requests received, in order. Batches are listed as `batch`.
+    pub fn methods(&self) -> Vec<String> {
+        self.requests.lock().unwrap().iter()
+            .map(|x| x["method"].as_str().unwrap_or("batch").to_string())
+            .collect()
+    }
+}
+
+/// JSON-RPC response body with `result` to `request`.
+pub fn result(request: &Value, result: Value) -> String {
+    serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": result}).to_string()
+}
+
+/// JSON-RPC response body with an error of `code` to `request`.
+pub fn error(request: &Value, code: i64, message: &str) -> String {
+    serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "error": {"code": code, "message": message}}).to_string()
+}
@@ -1 +1,3 @@
-pub const SYSTEM_PROGRAM: &str =  "11111111111111111111111111111111";
\ No newline at end of file
+pub const SYSTEM_PROGRAM: &str =  "11111111111111111111111111111111";
+pub const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const SOL_DECIMALS: u8 = 9;
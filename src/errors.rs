@@ -1,29 +1,210 @@
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::PyErr;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+create_exception!(solana_rpc, SolanaRpcError, PyException, "Base class of all errors raised by solana_rpc.");
+create_exception!(solana_rpc, RpcConnectionError, SolanaRpcError, "Failed to get a response from the RPC node.");
+create_exception!(solana_rpc, SignatureParseError, SolanaRpcError, "Failed to parse an account address or transaction signature.");
+create_exception!(solana_rpc, TransactionDecodeError, SolanaRpcError, "Failed to parse or decode a transaction.");
+create_exception!(solana_rpc, InvalidTimeFormat, SolanaRpcError, "Failed to parse a time string, expected `Y-M-D H:M:S`.");
+create_exception!(solana_rpc, NotFoundError, SolanaRpcError, "The requested transaction or signature does not exist.");
+
+#[derive(Error, Debug, Clone)]
 pub enum SolanaSignatureError {
-    #[error("Failed to parse pubkey address for signature")]
+    #[error("Failed to parse pubkey address for signature: {0}")]
     SignatureParsePubkeyError(String),
+    #[error("Failed to parse signature: {0}")]
+    ParseSignatureError(String),
+    #[error("Failed to get signatures: {0}")]
+    ClientError(String),
+    #[error("Failed to parse time window for signatures: {0}")]
+    TimeError(String),
+    #[error("Signature not found: {0}")]
+    NotFound(String)
 }
 
 #[derive(Error, Debug)]
+pub enum TimeError {
+    #[error("Failed to parse time string: {0}")]
+    ParseError(String),
+    #[error("Time out of range: {0}")]
+    RangeError(String)
+}
+
+#[derive(Error, Debug, Clone)]
 pub enum TransactionDetailError {
-    #[error("Failed to parse signature for transaction")]
+    #[error("Failed to parse signature for transaction: {0}")]
     ParseSignatureError(String),
-    #[error("Failed to get transaction")]
+    #[error("Failed to get transaction: {0}")]
     ClientError(String),
-    #[error("Failed to parse transaction")]
+    #[error("Failed to parse transaction: {0}")]
     TransactionParseError(String),
-    #[error("Failed to serialize transaction")]
+    #[error("Failed to serialize transaction: {0}")]
     SerializeError(String),
-    #[error("Failed to deserialize transaction")]
+    #[error("Failed to deserialize transaction: {0}")]
     DeserializeError(String),
-    #[error("Failed to get transaction transaction data")]
+    #[error("Failed to get transaction transaction data: {0}")]
     TransactionDataError(String),
-    #[error("Failed to get transaction meta data")]
+    #[error("Failed to get transaction meta data: {0}")]
     TransactionMetaError(String),
-    #[error("Failed to get inner instruction")]
+    #[error("Failed to get inner instruction: {0}")]
     TransactionInnerInstruction(String),
-    #[error("Failed to get instructions")]
-    TransactionInstruction(String)
+    #[error("Failed to get instructions: {0}")]
+    TransactionInstruction(String),
+    #[error("Transaction not found in cache while offline: {0}")]
+    CacheMiss(String),
+    #[error("Transaction not found: {0}")]
+    NotFound(String)
+}
+
+#[derive(Error, Debug)]
+pub enum IdlError {
+    #[error("Failed to read IDL file: {0}")]
+    ReadError(String),
+    #[error("Failed to parse IDL: {0}")]
+    ParseError(String),
+    #[error("Failed to get program id for IDL: {0}")]
+    MissingProgramId(String)
+}
+
+#[derive(Error, Debug)]
+pub enum ClientConfigError {
+    #[error("Failed to read client config file: {0}")]
+    ReadError(String),
+    #[error("Failed to parse client config: {0}")]
+    ParseError(String),
+    #[error("Invalid commitment level: {0}")]
+    InvalidCommitment(String),
+    #[error("Invalid HTTP header: {0}")]
+    InvalidHeader(String),
+    #[error("Failed to build RPC client: {0}")]
+    BuildError(String),
+    #[error("Failed to open RPC cassette: {0}")]
+    CassetteError(String),
+    #[error("Invalid rate limit: {0}")]
+    InvalidRateLimit(String),
+    #[error("Invalid RPC endpoint: {0}")]
+    InvalidEndpoint(String)
+}
+
+#[derive(Error, Debug)]
+pub enum CacheError {
+    #[error("Failed to open transaction cache: {0}")]
+    OpenError(String),
+    #[error("Failed to read transaction cache: {0}")]
+    ReadError(String),
+    #[error("Failed to write transaction cache: {0}")]
+    WriteError(String)
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum SourceError {
+    #[error("Failed to get data from the RPC node: {0}")]
+    ClientError(String),
+    #[error("Data not found: {0}")]
+    NotFound(String),
+    #[error("Not found in cache while offline: {0}")]
+    CacheMiss(String),
+    #[error("Failed to load fixture: {0}")]
+    FixtureError(String)
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum TraceError {
+    #[error("Failed to get signatures for account: {0}")]
+    SignatureError(#[from] SolanaSignatureError),
+    #[error("Failed to process transaction: {0}")]
+    TransactionError(#[from] TransactionDetailError)
+}
+
+impl From<SourceError> for SolanaSignatureError {
+    fn from(err: SourceError) -> Self {
+        match err {
+            SourceError::ClientError(message) => SolanaSignatureError::ClientError(message),
+            SourceError::NotFound(message) => SolanaSignatureError::NotFound(message),
+            _ => SolanaSignatureError::ClientError(err.to_string())
+        }
+    }
+}
+
+impl From<SourceError> for TransactionDetailError {
+    fn from(err: SourceError) -> Self {
+        match err {
+            SourceError::ClientError(message) => TransactionDetailError::ClientError(message),
+            SourceError::CacheMiss(signature) => TransactionDetailError::CacheMiss(signature),
+            SourceError::NotFound(message) => TransactionDetailError::NotFound(message),
+            _ => TransactionDetailError::ClientError(err.to_string())
+        }
+    }
+}
+
+impl From<SolanaSignatureError> for PyErr {
+    fn from(err: SolanaSignatureError) -> Self {
+        let message = err.to_string();
+        match err {
+            SolanaSignatureError::SignatureParsePubkeyError(_) => SignatureParseError::new_err(message),
+            SolanaSignatureError::ParseSignatureError(_) => SignatureParseError::new_err(message),
+            SolanaSignatureError::ClientError(_) => RpcConnectionError::new_err(message),
+            SolanaSignatureError::TimeError(_) => InvalidTimeFormat::new_err(message),
+            SolanaSignatureError::NotFound(_) => NotFoundError::new_err(message)
+        }
+    }
+}
+
+impl From<TransactionDetailError> for PyErr {
+    fn from(err: TransactionDetailError) -> Self {
+        let message = err.to_string();
+        match err {
+            TransactionDetailError::ParseSignatureError(_) => SignatureParseError::new_err(message),
+            TransactionDetailError::ClientError(_) => RpcConnectionError::new_err(message),
+            TransactionDetailError::CacheMiss(_) => RpcConnectionError::new_err(message),
+            TransactionDetailError::NotFound(_) => NotFoundError::new_err(message),
+            _ => TransactionDecodeError::new_err(message)
+        }
+    }
+}
+
+impl From<TimeError> for PyErr {
+    fn from(err: TimeError) -> Self {
+        InvalidTimeFormat::new_err(err.to_string())
+    }
+}
+
+impl From<IdlError> for PyErr {
+    fn from(err: IdlError) -> Self {
+        SolanaRpcError::new_err(err.to_string())
+    }
+}
+
+impl From<ClientConfigError> for PyErr {
+    fn from(err: ClientConfigError) -> Self {
+        SolanaRpcError::new_err(err.to_string())
+    }
+}
+
+impl From<SourceError> for PyErr {
+    fn from(err: SourceError) -> Self {
+        let message = err.to_string();
+        match err {
+            SourceError::FixtureError(_) => SolanaRpcError::new_err(message),
+            SourceError::NotFound(_) => NotFoundError::new_err(message),
+            _ => RpcConnectionError::new_err(message)
+        }
+    }
+}
+
+impl From<CacheError> for PyErr {
+    fn from(err: CacheError) -> Self {
+        SolanaRpcError::new_err(err.to_string())
+    }
+}
+
+impl From<TraceError> for PyErr {
+    fn from(err: TraceError) -> Self {
+        match err {
+            TraceError::SignatureError(err) => err.into(),
+            TraceError::TransactionError(err) => err.into()
+        }
+    }
 }
\ No newline at end of file
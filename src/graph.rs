@@ -0,0 +1,5 @@
+pub mod export;
+#[allow(clippy::module_inception)]
+pub mod graph;
+pub mod paths;
+pub mod taint;
\ No newline at end of file
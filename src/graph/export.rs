@@ -0,0 +1,221 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::parsing::time::convert_unix_to_time;
+use super::graph::{AccountNode, TransferEdge, TransferGraph};
+
+/// File format a `TransferGraph` can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT.
+    Dot,
+    /// GraphML, e.g. for yEd.
+    Graphml,
+    /// GEXF 1.3, e.g. for Gephi.
+    Gexf
+}
+
+impl fmt::Display for GraphFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Dot => "dot",
+            Self::Graphml => "graphml",
+            Self::Gexf => "gexf"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "dot" => Ok(Self::Dot),
+            "graphml" => Ok(Self::Graphml),
+            "gexf" => Ok(Self::Gexf),
+            _ => Err(format!("Invalid graph format {}, expected dot, graphml or gexf", format))
+        }
+    }
+}
+
+/// Node attributes exported to GraphML and GEXF, as `(name, type)`.
+const NODE_ATTRIBUTES: [(&str, &str); 5] = [
+    ("label", "string"),
+    ("received", "long"),
+    ("sent", "long"),
+    ("transfers_in", "long"),
+    ("transfers_out", "long")
+];
+
+/// Edge attributes exported to GraphML and GEXF, as `(name, type)`.
+const EDGE_ATTRIBUTES: [(&str, &str); 7] = [
+    ("lamports", "long"),
+    ("action", "string"),
+    ("block_time", "long"),
+    ("human_time", "string"),
+    ("slot", "long"),
+    ("signature", "string"),
+    ("instruction", "string")
+];
+
+/// Values of `NODE_ATTRIBUTES` for `node`.
+fn node_values(node: &AccountNode) -> [String; 5] {
+    [
+        node.account.clone(),
+        node.received.to_string(),
+        node.sent.to_string(),
+        node.transfers_in.to_string(),
+        node.transfers_out.to_string()
+    ]
+}
+
+/// Values of `EDGE_ATTRIBUTES` for `edge`.
+fn edge_values(edge: &TransferEdge) -> [String; 7] {
+    [
+        edge.lamports.to_string(),
+        edge.action.clone(),
+        edge.block_time.to_string(),
+        convert_unix_to_time(edge.block_time),
+        edge.slot.to_string(),
+        edge.signature.clone(),
+        match edge.inner_index {
+            Some(inner_index) => format!("{}.{}", edge.instruction_index, inner_index),
+            None => edge.instruction_index.to_string()
+        }
+    ]
+}
+
+/// Escape a value for use within a double quoted DOT string.
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escape a value for use within XML text or a double quoted attribute.
+fn escape_xml(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl TransferGraph {
+    /// Export the graph in the specified format.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - File format to export to.
+    pub fn export(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Graphml => self.to_graphml(),
+            GraphFormat::Gexf => self.to_gexf()
+        }
+    }
+
+    /// Export the graph as Graphviz DOT. Nodes are labelled with their aggregate volume and edges
+    /// with the amount transferred and time.
+    pub fn to_dot(&self) -> String {
+        let mut lines = vec!["digraph transfers {".to_string()];
+        for node in self.nodes() {
+            lines.push(format!(
+                "    \"{}\" [label=\"{}\\nin: {} SOL\\nout: {} SOL\"];",
+                escape_dot(&node.account), escape_dot(&node.account), node.received_sol(), node.sent_sol()
+            ));
+        }
+        for edge in self.edges() {
+            lines.push(format!(
+                "    \"{}\" -> \"{}\" [label=\"{} SOL\\n{}\", lamports={}, signature=\"{}\"];",
+                escape_dot(&self.nodes()[edge.source].account),
+                escape_dot(&self.nodes()[edge.destination].account),
+                edge.sol_amount(),
+                convert_unix_to_time(edge.block_time),
+                edge.lamports,
+                escape_dot(&edge.signature)
+            ));
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    /// Export the graph as GraphML, with the node and edge attributes declared as keys.
+    pub fn to_graphml(&self) -> String {
+        let mut lines = vec![
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_string(),
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">".to_string()
+        ];
+        for (name, ty) in NODE_ATTRIBUTES {
+            lines.push(format!("  <key id=\"n_{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>", name, name, ty));
+        }
+        for (name, ty) in EDGE_ATTRIBUTES {
+            lines.push(format!("  <key id=\"e_{}\" for=\"edge\" attr.name=\"{}\" attr.type=\"{}\"/>", name, name, ty));
+        }
+        lines.push("  <graph id=\"transfers\" edgedefault=\"directed\">".to_string());
+        for (index, node) in self.nodes().iter().enumerate() {
+            lines.push(format!("    <node id=\"n{}\">", index));
+            for ((name, _), value) in NODE_ATTRIBUTES.iter().zip(node_values(node)) {
+                lines.push(format!("      <data key=\"n_{}\">{}</data>", name, escape_xml(&value)));
+            }
+            lines.push("    </node>".to_string());
+        }
+        for (index, edge) in self.edges().iter().enumerate() {
+            lines.push(format!("    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\">", index, edge.source, edge.destination));
+            for ((name, _), value) in EDGE_ATTRIBUTES.iter().zip(edge_values(edge)) {
+                lines.push(format!("      <data key=\"e_{}\">{}</data>", name, escape_xml(&value)));
+            }
+            lines.push("    </edge>".to_string());
+        }
+        lines.push("  </graph>".to_string());
+        lines.push("</graphml>".to_string());
+        lines.join("\n")
+    }
+
+    /// Export the graph as GEXF 1.3. Edges are weighted by the lamports transferred.
+    pub fn to_gexf(&self) -> String {
+        let mut lines = vec![
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_string(),
+            "<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">".to_string(),
+            "  <graph defaultedgetype=\"directed\" mode=\"static\">".to_string()
+        ];
+        // The node label is written as the `label` attribute of the node element instead.
+        lines.push("    <attributes class=\"node\">".to_string());
+        for (index, (name, ty)) in NODE_ATTRIBUTES.iter().enumerate().skip(1) {
+            lines.push(format!("      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>", index, name, ty));
+        }
+        lines.push("    </attributes>".to_string());
+        lines.push("    <attributes class=\"edge\">".to_string());
+        for (index, (name, ty)) in EDGE_ATTRIBUTES.iter().enumerate() {
+            lines.push(format!("      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>", index, name, ty));
+        }
+        lines.push("    </attributes>".to_string());
+        lines.push("    <nodes>".to_string());
+        for (index, node) in self.nodes().iter().enumerate() {
+            lines.push(format!("      <node id=\"{}\" label=\"{}\">", index, escape_xml(&node.account)));
+            lines.push("        <attvalues>".to_string());
+            for (attribute, value) in node_values(node).iter().enumerate().skip(1) {
+                lines.push(format!("          <attvalue for=\"{}\" value=\"{}\"/>", attribute, escape_xml(value)));
+            }
+            lines.push("        </attvalues>".to_string());
+            lines.push("      </node>".to_string());
+        }
+        lines.push("    </nodes>".to_string());
+        lines.push("    <edges>".to_string());
+        for (index, edge) in self.edges().iter().enumerate() {
+            lines.push(format!(
+                "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\">",
+                index, edge.source, edge.destination, edge.lamports
+            ));
+            lines.push("        <attvalues>".to_string());
+            for (attribute, value) in edge_values(edge).iter().enumerate() {
+                lines.push(format!("          <attvalue for=\"{}\" value=\"{}\"/>", attribute, escape_xml(value)));
+            }
+            lines.push("        </attvalues>".to_string());
+            lines.push("      </edge>".to_string());
+        }
+        lines.push("    </edges>".to_string());
+        lines.push("  </graph>".to_string());
+        lines.push("</gexf>".to_string());
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+
+use crate::constants::SOL_DECIMALS;
+use crate::hash::SData;
+use crate::parsing::amount::format_amount;
+use crate::parsing::time::convert_unix_to_time;
+use crate::transaction::instructions::InstructionTransferWrapper;
+
+/// Account in a `TransferGraph`, with the aggregate volume of its transfers.
+/// Contains `account`, `received`, `sent`, `transfers_in` and `transfers_out` fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountNode {
+    /// Account as base-58 encoded string.
+    pub account: String,
+    /// Lamports received by the account across all incoming transfers.
+    pub received: u64,
+    /// Lamports sent by the account across all outgoing transfers.
+    pub sent: u64,
+    /// Number of incoming transfers.
+    pub transfers_in: usize,
+    /// Number of outgoing transfers.
+    pub transfers_out: usize
+}
+
+impl AccountNode {
+    /// Lamports received in SOL, as an exact decimal string.
+    pub fn received_sol(&self) -> String {
+        format_amount(self.received, SOL_DECIMALS)
+    }
+
+    /// Lamports sent in SOL, as an exact decimal string.
+    pub fn sent_sol(&self) -> String {
+        format_amount(self.sent, SOL_DECIMALS)
+    }
+}
+
+/// Transfer in a `TransferGraph`, between the nodes at index `source` and `destination`.
+/// Contains `source`, `destination`, `lamports`, `program`, `action`, `block_time`, `slot`, `signature`,
+/// `instruction_index` and `inner_index` fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransferEdge {
+    /// Index of the source account node.
+    pub source: usize,
+    /// Index of the destination account node.
+    pub destination: usize,
+    /// Number of lamports transferred.
+    pub lamports: u64,
+    /// Program account that executed the instruction.
+    pub program: String,
+    /// The instruction performed.
+    pub action: String,
+    /// Estimated production time, as UNIX timestamp.
+    pub block_time: u64,
+    /// The slot the transaction was processed in.
+    pub slot: u64,
+    /// Transaction signature.
+    pub signature: String,
+    /// Index of the outer transaction instruction.
+    pub instruction_index: usize,
+    /// Stack position within the inner instructions of `instruction_index`, `None` for outer instructions.
+    pub inner_index: Option<usize>
+}
+
+impl TransferEdge {
+    /// Lamports transferred in SOL, as an exact decimal string.
+    pub fn sol_amount(&self) -> String {
+        format_amount(self.lamports, SOL_DECIMALS)
+    }
+
+    /// Identity of the transfer instruction within its transaction.
+    pub fn id(&self) -> SData {
+        SData {
+            signature: self.signature.clone(),
+            instruction_index: self.instruction_index,
+            inner_index: self.inner_index
+        }
+    }
+}
+
+/// Directed multigraph of SOL transfers, with accounts as nodes and transfers as edges.
+/// Nodes and edges are kept in insertion order and addressed by index.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TransferGraph {
+    nodes: Vec<AccountNode>,
+    edges: Vec<TransferEdge>,
+    #[serde(skip)]
+    index: HashMap<String, usize>,
+    #[serde(skip)]
+    outgoing: Vec<Vec<usize>>,
+    #[serde(skip)]
+    incoming: Vec<Vec<usize>>
+}
+
+impl TransferGraph {
+    /// Create an empty `TransferGraph`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a `TransferGraph` from decoded transfers, e.g. the transfers of a `logic::TraceResult`.
+    /// Transfers with the same identity are only added once.
+    ///
+    /// # Arguments
+    ///
+    /// * `transfers` - Decoded transfers.
+    pub fn from_transfers(transfers: &[InstructionTransferWrapper]) -> Self {
+        let mut graph = Self::new();
+        let mut seen = HashSet::new();
+        for transfer in transfers {
+            if seen.insert(transfer.id()) {
+                graph.add_transfer(transfer);
+            }
+        }
+        graph
+    }
+
+    /// Add a transfer, and its accounts if not yet present, to the graph. Returns the edge index.
+    pub fn add_transfer(&mut self, transfer: &InstructionTransferWrapper) -> usize {
+        let source = self.add_account(&transfer.source);
+        let destination = self.add_account(&transfer.destination);
+        let edge = self.edges.len();
+
+        self.nodes[source].sent = self.nodes[source].sent.saturating_add(transfer.lamports);
+        self.nodes[source].transfers_out += 1;
+        self.nodes[destination].received = self.nodes[destination].received.saturating_add(transfer.lamports);
+        self.nodes[destination].transfers_in += 1;
+        self.outgoing[source].push(edge);
+        self.incoming[destination].push(edge);
+        self.edges.push(TransferEdge {
+            source,
+            destination,
+            lamports: transfer.lamports,
+            program: transfer.program.clone(),
+            action: transfer.action.clone(),
+            block_time: transfer.block_time,
+            slot: transfer.slot,
+            signature: transfer.signature.clone(),
+            instruction_index: transfer.instruction_index,
+            inner_index: transfer.inner_index
+        });
+        edge
+    }
+
+    /// Add an account to the graph if not yet present. Returns the node index.
+    pub fn add_account(&mut self, account: &str) -> usize {
+        if let Some(index) = self.index.get(account) {
+            return *index;
+        }
+        let index = self.nodes.len();
+        self.nodes.push(AccountNode {
+            account: account.to_string(),
+            received: 0,
+            sent: 0,
+            transfers_in: 0,
+            transfers_out: 0
+        });
+        self.index.insert(account.to_string(), index);
+        self.outgoing.push(vec![]);
+        self.incoming.push(vec![]);
+        index
+    }
+
+    /// Account nodes, in insertion order.
+    pub fn nodes(&self) -> &[AccountNode] {
+        &self.nodes
+    }
+
+    /// Transfer edges, in insertion order.
+    pub fn edges(&self) -> &[TransferEdge] {
+        &self.edges
+    }
+
+    /// Index of the node for `account`, if present.
+    pub fn node_index(&self, account: &str) -> Option<usize> {
+        self.index.get(account).copied()
+    }
+
+    /// Node for `account`, if present.
+    pub fn node(&self, account: &str) -> Option<&AccountNode> {
+        self.node_index(account).map(|index| &self.nodes[index])
+    }
+
+    /// Transfer at edge index `edge` as a decoded transfer record, if present.
+    pub fn transfer(&self, edge: usize) -> Option<InstructionTransferWrapper> {
+        let edge = self.edges.get(edge)?;
+        Some(InstructionTransferWrapper {
+            program: edge.program.clone(),
+            lamports: edge.lamports,
+            source: self.nodes[edge.source].account.clone(),
+            destination: self.nodes[edge.destination].account.clone(),
+            action: edge.action.clone(),
+            block_time: edge.block_time,
+            human_time: convert_unix_to_time(edge.block_time),
+            slot: edge.slot,
+            signature: edge.signature.clone(),
+            instruction_index: edge.instruction_index,
+            inner_index: edge.inner_index
+        })
+    }
+
+    /// Indices of the edges leaving the node at `node`.
+    pub fn outgoing(&self, node: usize) -> &[usize] {
+        self.outgoing.get(node).map_or(&[], |x| &x[..])
+    }
+
+    /// Indices of the edges entering the node at `node`.
+    pub fn incoming(&self, node: usize) -> &[usize] {
+        self.incoming.get(node).map_or(&[], |x| &x[..])
+    }
+}
@@ -0,0 +1,372 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use pyo3::prelude::*;
+
+use crate::hash::SData;
+use crate::transaction::instructions::InstructionTransferWrapper;
+use super::graph::{TransferEdge, TransferGraph};
+
+/// Default maximum number of paths returned by `find_paths`, bounding the exponential search.
+pub const DEFAULT_MAX_PATHS: usize = 1000;
+
+/// Processing order of a transfer: slot, transaction signature, outer instruction index and inner stack
+/// position. The order of the transactions within a slot is not known, they are ordered by signature.
+type TransferOrder<'a> = (u64, &'a str, usize, Option<usize>);
+
+/// Time-respecting path of transfers between two accounts, with python bindings.
+/// Contains `transfers`, `accounts` and `capacity` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[pyclass]
+pub struct TransferPath {
+    /// Transfers along the path, in order.
+    #[pyo3(get)]
+    pub transfers: Vec<InstructionTransferWrapper>,
+    /// Accounts along the path, starting with the source and ending with the destination.
+    #[pyo3(get)]
+    pub accounts: Vec<String>,
+    /// Smallest number of lamports transferred along the path.
+    #[pyo3(get)]
+    pub capacity: u64
+}
+
+impl TransferPath {
+    /// Number of transfers along the path.
+    pub fn hops(&self) -> usize {
+        self.transfers.len()
+    }
+
+    /// Slot of the first transfer.
+    fn departure(&self) -> u64 {
+        self.transfers.first().map_or(0, |x| x.slot)
+    }
+
+    /// Slot of the last transfer.
+    fn arrival(&self) -> u64 {
+        self.transfers.last().map_or(0, |x| x.slot)
+    }
+}
+
+/// Paths and flow of funds between two accounts, with python bindings.
+/// Contains `paths`, `max_flow`, `earliest` and `latest` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[pyclass]
+pub struct PathAnalysis {
+    /// Time-respecting paths from the source to the destination account.
+    #[pyo3(get)]
+    pub paths: Vec<TransferPath>,
+    /// Maximum number of lamports that could have moved from the source to the destination account
+    /// through the transfers of `paths`, respecting the order in which they were processed.
+    #[pyo3(get)]
+    pub max_flow: u64,
+    /// Path that reaches the destination account first, preferring fewer hops on ties.
+    #[pyo3(get)]
+    pub earliest: Option<TransferPath>,
+    /// Path that leaves the source account last, preferring fewer hops on ties.
+    #[pyo3(get)]
+    pub latest: Option<TransferPath>
+}
+
+/// Whether funds moved by `previous` could have been moved on by `next`, i.e. `next` is processed after
+/// `previous`, see `TransferOrder`.
+fn follows(previous: &TransferEdge, next: &TransferEdge) -> bool {
+    edge_order(next) > edge_order(previous)
+}
+
+/// Processing order of the transfer of `edge`, see `TransferOrder`.
+fn edge_order(edge: &TransferEdge) -> TransferOrder<'_> {
+    (edge.slot, &edge.signature, edge.instruction_index, edge.inner_index)
+}
+
+/// Processing order of `transfer`, see `TransferOrder`.
+fn transfer_order(transfer: &InstructionTransferWrapper) -> TransferOrder<'_> {
+    (transfer.slot, &transfer.signature, transfer.instruction_index, transfer.inner_index)
+}
+
+impl TransferGraph {
+    /// Enumerate the simple, time-respecting paths from `source` to `destination`, in depth-first order.
+    /// Every transfer along a path is processed after the transfer before it and no account is
+    /// visited twice. The number of paths can grow exponentially with `max_hops`, use `max_paths` to
+    /// bound the search.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Account the funds are sent from, as base-58 encoded string.
+    /// * `destination` - Account the funds arrive at, as base-58 encoded string.
+    /// * `max_hops` - Maximum number of transfers along a path.
+    /// * `max_paths` - Maximum number of paths to return.
+    pub fn paths(&self, source: &str, destination: &str, max_hops: usize, max_paths: Option<usize>) -> Vec<TransferPath> {
+        let (source, destination) = match (self.node_index(source), self.node_index(destination)) {
+            (Some(source), Some(destination)) if source != destination => (source, destination),
+            _ => return vec![]
+        };
+        let mut found: Vec<Vec<usize>> = vec![];
+        let mut visited = vec![false; self.nodes().len()];
+        let mut path: Vec<usize> = vec![];
+        visited[source] = true;
+        self.search_paths(source, destination, max_hops, max_paths, &mut visited, &mut path, &mut found);
+        found.iter().map(|edges| self.to_path(edges)).collect()
+    }
+
+    /// Depth-first search step of `TransferGraph::paths`, extending `path` from the node at `node`.
+    #[allow(clippy::too_many_arguments)]
+    fn search_paths(&self, node: usize, destination: usize, max_hops: usize, max_paths: Option<usize>, visited: &mut [bool], path: &mut Vec<usize>, found: &mut Vec<Vec<usize>>) {
+        for &edge in self.outgoing(node) {
+            if max_paths.is_some_and(|max| found.len() >= max) {
+                return;
+            }
+            let next = &self.edges()[edge];
+            if visited[next.destination] {
+                continue;
+            }
+            if let Some(&previous) = path.last() {
+                if !follows(&self.edges()[previous], next) {
+                    continue;
+                }
+            }
+            path.push(edge);
+            if next.destination == destination {
+                found.push(path.clone());
+            } else if path.len() < max_hops {
+                visited[next.destination] = true;
+                self.search_paths(next.destination, destination, max_hops, max_paths, visited, path, found);
+                visited[next.destination] = false;
+            }
+            path.pop();
+        }
+    }
+
+    /// Convert edge indices along a path to a `TransferPath`.
+    fn to_path(&self, edges: &[usize]) -> TransferPath {
+        let transfers: Vec<InstructionTransferWrapper> = edges.iter().filter_map(|edge| self.transfer(*edge)).collect();
+        let mut accounts: Vec<String> = transfers.iter().map(|x| x.source.clone()).collect();
+        accounts.extend(transfers.last().map(|x| x.destination.clone()));
+        TransferPath {
+            capacity: transfers.iter().map(|x| x.lamports).min().unwrap_or(0),
+            transfers,
+            accounts
+        }
+    }
+
+    /// Find the time-respecting paths from `source` to `destination`, the earliest and latest of them, and
+    /// the maximum flow through their transfers. See `TransferGraph::paths` for the arguments.
+    ///
+    /// The maximum flow is computed on a time-expanded network, where funds received by an account can
+    /// only be moved on by transfers processed after they arrived, in the order paths respect. If
+    /// `max_paths` truncates the search the flow only accounts for the paths found.
+    pub fn analyze_paths(&self, source: &str, destination: &str, max_hops: usize, max_paths: Option<usize>) -> PathAnalysis {
+        let paths = self.paths(source, destination, max_hops, max_paths);
+        let earliest = paths.iter().min_by_key(|x| (x.arrival(), x.hops())).cloned();
+        let latest = paths.iter().max_by_key(|x| (x.departure(), Reverse(x.hops()))).cloned();
+        let max_flow = temporal_max_flow(source, destination, &paths);
+        PathAnalysis { paths, max_flow, earliest, latest }
+    }
+}
+
+/// Maximum flow from `source` to `destination` through the transfers of `paths`, on a network with a node
+/// per account and transfer of the account. Transfers connect the nodes of their source and destination,
+/// and each account holds funds over to its next transfer, in `TransferOrder`.
+fn temporal_max_flow(source: &str, destination: &str, paths: &[TransferPath]) -> u64 {
+    // Paths share transfers, each is only added to the network once.
+    let transfers: HashMap<SData, &InstructionTransferWrapper> = paths.iter()
+        .flat_map(|path| &path.transfers)
+        .map(|x| (x.id(), x))
+        .collect();
+    if transfers.is_empty() {
+        return 0;
+    }
+
+    let mut network = FlowNetwork::default();
+    let (network_source, network_sink) = (network.add_node(), network.add_node());
+    // Nodes of each account, in the order of its transfers.
+    let mut nodes: HashMap<&String, BTreeMap<TransferOrder, usize>> = HashMap::new();
+    for transfer in transfers.values() {
+        for account in [&transfer.source, &transfer.destination] {
+            nodes.entry(account).or_default().entry(transfer_order(transfer)).or_insert_with(|| network.add_node());
+        }
+    }
+    for transfer in transfers.values() {
+        let from = nodes[&transfer.source][&transfer_order(transfer)];
+        let to = nodes[&transfer.destination][&transfer_order(transfer)];
+        network.add_edge(from, to, u128::from(transfer.lamports));
+    }
+    for (account, nodes) in &nodes {
+        let nodes: Vec<usize> = nodes.values().copied().collect();
+        for pair in nodes.windows(2) {
+            network.add_edge(pair[0], pair[1], u128::MAX);
+        }
+        if account.as_str() == source {
+            network.add_edge(network_source, nodes[0], u128::MAX);
+        }
+        if account.as_str() == destination {
+            network.add_edge(nodes[nodes.len() - 1], network_sink, u128::MAX);
+        }
+    }
+    u64::try_from(network.max_flow(network_source, network_sink)).unwrap_or(u64::MAX)
+}
+
+/// Flow network with residual edges, solved with the Edmonds-Karp algorithm.
+#[derive(Default)]
+struct FlowNetwork {
+    /// Edge indices leaving each node, including residual edges.
+    adjacency: Vec<Vec<usize>>,
+    /// Node each edge points to. Edge `i ^ 1` is the residual of edge `i`.
+    to: Vec<usize>,
+    /// Remaining capacity of each edge.
+    capacity: Vec<u128>
+}
+
+impl FlowNetwork {
+    fn add_node(&mut self) -> usize {
+        self.adjacency.push(vec![]);
+        self.adjacency.len() - 1
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: u128) {
+        self.adjacency[from].push(self.to.len());
+        self.to.push(to);
+        self.capacity.push(capacity);
+        self.adjacency[to].push(self.to.len());
+        self.to.push(from);
+        self.capacity.push(0);
+    }
+
+    /// Maximum flow from `source` to `sink`, augmenting along shortest paths until none remain.
+    fn max_flow(&mut self, source: usize, sink: usize) -> u128 {
+        let mut flow: u128 = 0;
+        loop {
+            // Edge used to reach each node on the shortest augmenting path.
+            let mut parent: Vec<Option<usize>> = vec![None; self.adjacency.len()];
+            let mut queue = VecDeque::from([source]);
+            while let Some(node) = queue.pop_front() {
+                for &edge in &self.adjacency[node] {
+                    let next = self.to[edge];
+                    if self.capacity[edge] > 0 && next != source && parent[next].is_none() {
+                        parent[next] = Some(edge);
+                        queue.push_back(next);
+                    }
+                }
+            }
+            if parent[sink].is_none() {
+                return flow;
+            }
+
+            let mut path = vec![];
+            let mut node = sink;
+            while let Some(edge) = parent[node] {
+                path.push(edge);
+                node = self.to[edge ^ 1];
+            }
+            let bottleneck = path.iter().map(|edge| self.capacity[*edge]).min().unwrap_or(0);
+            for edge in path {
+                self.capacity[edge] -= bottleneck;
+                self.capacity[edge ^ 1] = self.capacity[edge ^ 1].saturating_add(bottleneck);
+            }
+            flow = flow.saturating_add(bottleneck);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(source: &str, destination: &str, lamports: u64, slot: u64, signature: &str, instruction_index: usize) -> InstructionTransferWrapper {
+        InstructionTransferWrapper {
+            program: "11111111111111111111111111111111".to_string(),
+            lamports,
+            source: source.to_string(),
+            destination: destination.to_string(),
+            action: "Transfer".to_string(),
+            block_time: 1_600_000_000 + slot,
+            human_time: String::new(),
+            slot,
+            signature: signature.to_string(),
+            instruction_index,
+            inner_index: None
+        }
+    }
+
+    fn accounts(paths: &[TransferPath]) -> Vec<String> {
+        paths.iter().map(|x| x.accounts.join(">")).collect()
+    }
+
+    #[test]
+    fn search_time_respecting_paths() {
+        let graph = TransferGraph::from_transfers(&[
+            transfer("A", "B", 100, 1, "s1", 0),
+            transfer("B", "C", 60, 2, "s2", 0),
+            transfer("A", "C", 10, 3, "s3", 0),
+            // Leaves B before the funds arrived.
+            transfer("B", "D", 70, 0, "s0", 0),
+            transfer("D", "C", 70, 4, "s4", 0),
+            // Returns to A, which is already on the path.
+            transfer("B", "A", 5, 2, "s5", 0)
+        ]);
+        let paths = graph.paths("A", "C", 6, None);
+        assert_eq!(accounts(&paths), vec!["A>B>C", "A>C"]);
+        assert_eq!(paths[0].capacity, 60);
+        assert_eq!(paths[0].hops(), 2);
+
+        assert_eq!(accounts(&graph.paths("A", "C", 1, None)), vec!["A>C"]);
+        assert_eq!(graph.paths("A", "C", 6, Some(1)).len(), 1);
+        assert!(graph.paths("C", "A", 6, None).is_empty());
+        assert!(graph.paths("A", "unknown", 6, None).is_empty());
+    }
+
+    #[test]
+    fn order_transfers_within_transaction() {
+        // B pays C before A pays B in the same transaction.
+        let graph = TransferGraph::from_transfers(&[
+            transfer("A", "B", 80, 5, "tx", 1),
+            transfer("B", "C", 80, 5, "tx", 0)
+        ]);
+        assert!(graph.paths("A", "C", 6, None).is_empty());
+    }
+
+    #[test]
+    fn earliest_and_latest_paths() {
+        let graph = TransferGraph::from_transfers(&[
+            transfer("A", "B", 100, 1, "s1", 0),
+            transfer("B", "C", 60, 9, "s2", 0),
+            transfer("A", "C", 10, 3, "s3", 0),
+            transfer("A", "D", 10, 5, "s4", 0),
+            transfer("D", "C", 10, 6, "s5", 0)
+        ]);
+        let analysis = graph.analyze_paths("A", "C", 6, None);
+        assert_eq!(analysis.paths.len(), 3);
+        assert_eq!(analysis.earliest.unwrap().accounts, vec!["A", "C"]);
+        assert_eq!(analysis.latest.unwrap().accounts, vec!["A", "D", "C"]);
+        assert_eq!(analysis.max_flow, 80);
+    }
+
+    #[test]
+    fn flow_respects_transfer_order() {
+        let transfers = [
+            transfer("A", "B", 30, 4, "s4", 0),
+            // B pays C before A pays B within the same transaction, only the 30 lamports received earlier
+            // can be moved on.
+            transfer("B", "C", 80, 5, "tx", 0),
+            transfer("A", "B", 80, 5, "tx", 1),
+            transfer("B", "C", 5, 6, "s6", 0)
+        ];
+        let graph = TransferGraph::from_transfers(&transfers);
+        let analysis = graph.analyze_paths("A", "C", 6, None);
+        assert_eq!(accounts(&analysis.paths), vec!["A>B>C", "A>B>C", "A>B>C"]);
+        assert_eq!(analysis.max_flow, 35);
+    }
+
+    #[test]
+    fn flow_counts_shared_transfers_once() {
+        let graph = TransferGraph::from_transfers(&[
+            transfer("A", "B", 50, 1, "s1", 0),
+            transfer("B", "C", 40, 2, "s2", 0),
+            transfer("B", "D", 40, 2, "s3", 0),
+            transfer("C", "E", 40, 3, "s4", 0),
+            transfer("D", "E", 40, 3, "s5", 0)
+        ]);
+        let analysis = graph.analyze_paths("A", "E", 6, None);
+        assert_eq!(analysis.paths.len(), 2);
+        assert_eq!(analysis.max_flow, 50);
+        assert_eq!(graph.analyze_paths("A", "E", 6, Some(1)).max_flow, 40);
+    }
+}
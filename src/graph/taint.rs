@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use crate::transaction::instructions::InstructionTransferWrapper;
+use super::graph::TransferGraph;
+
+/// Policy deciding which part of the funds leaving an account is tainted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TaintPolicy {
+    /// Once an account receives tainted funds, everything it sends is tainted.
+    Poison,
+    /// Funds sent are tainted in proportion to the tainted share of the account's balance.
+    #[default]
+    Haircut,
+    /// Funds are sent in the order they were received, first in first out.
+    Fifo
+}
+
+impl fmt::Display for TaintPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Poison => "poison",
+            Self::Haircut => "haircut",
+            Self::Fifo => "fifo"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for TaintPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "poison" => Ok(Self::Poison),
+            "haircut" => Ok(Self::Haircut),
+            "fifo" => Ok(Self::Fifo),
+            _ => Err(format!("Invalid taint policy {}, expected poison, haircut or fifo", policy))
+        }
+    }
+}
+
+/// Origin of tainted funds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaintSource {
+    /// `lamports` held by `account` before its first traced transfer are tainted.
+    Account { account: String, lamports: u64 },
+    /// Funds moved by the transfers of transaction `signature` are tainted, up to `lamports` in total if given.
+    Transaction { signature: String, lamports: Option<u64> }
+}
+
+/// Tainted funds of an account, with python bindings.
+/// Contains `account`, `received`, `sent` and `held` fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[pyclass]
+pub struct AccountTaint {
+    /// Account as base-58 encoded string.
+    #[pyo3(get)]
+    pub account: String,
+    /// Tainted lamports received by the account, excluding its own tainted balance.
+    #[pyo3(get)]
+    pub received: u64,
+    /// Tainted lamports sent by the account.
+    #[pyo3(get)]
+    pub sent: u64,
+    /// Tainted lamports still held by the account after its last traced transfer.
+    #[pyo3(get)]
+    pub held: u64
+}
+
+/// Tainted part of a transfer, with python bindings.
+/// Contains `transfer` and `tainted` fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[pyclass]
+pub struct TransferTaint {
+    /// The transfer.
+    #[pyo3(get)]
+    pub transfer: InstructionTransferWrapper,
+    /// Tainted lamports moved by the transfer.
+    #[pyo3(get)]
+    pub tainted: u64
+}
+
+/// Result of a taint propagation, with python bindings.
+/// Contains `policy`, `accounts`, `transfers` and `unmatched` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[pyclass]
+pub struct TaintReport {
+    /// Policy the taint was propagated with.
+    pub policy: TaintPolicy,
+    /// Accounts that received, sent or held tainted funds, in graph order.
+    #[pyo3(get)]
+    pub accounts: Vec<AccountTaint>,
+    /// Transfers that moved tainted funds, in the order they were processed.
+    #[pyo3(get)]
+    pub transfers: Vec<TransferTaint>,
+    /// Accounts and transaction signatures of the taint sources that match no transfer, whose taint
+    /// could not be propagated.
+    #[pyo3(get)]
+    pub unmatched: Vec<String>
+}
+
+/// Funds held by an account while taint is propagated. The traced transfers rarely cover the full history
+/// of an account, funds sent beyond what it is known to hold are assumed to be untainted.
+#[derive(Debug, Default)]
+struct Holdings {
+    /// Lamports the account is known to hold.
+    balance: u64,
+    /// Tainted part of `balance`.
+    tainted: u64,
+    /// Lamports received, in order, and whether they are tainted. Only kept for `TaintPolicy::Fifo`.
+    lots: VecDeque<(u64, bool)>,
+    /// Whether the account ever held tainted funds.
+    poisoned: bool
+}
+
+impl Holdings {
+    fn receive(&mut self, lamports: u64, tainted: u64, policy: TaintPolicy) {
+        self.balance = self.balance.saturating_add(lamports);
+        self.tainted = self.tainted.saturating_add(tainted);
+        self.poisoned |= tainted > 0;
+        if policy == TaintPolicy::Poison && self.poisoned {
+            self.tainted = self.balance;
+        }
+        if policy == TaintPolicy::Fifo {
+            if tainted > 0 {
+                self.lots.push_back((tainted, true));
+            }
+            if lamports > tainted {
+                self.lots.push_back((lamports - tainted, false));
+            }
+        }
+    }
+
+    /// Send `lamports`, returning the tainted part.
+    fn send(&mut self, lamports: u64, policy: TaintPolicy) -> u64 {
+        let tainted = match policy {
+            TaintPolicy::Poison if self.poisoned => lamports,
+            TaintPolicy::Poison => 0,
+            TaintPolicy::Haircut => {
+                let base = self.balance.max(lamports);
+                if base == 0 { 0 } else { (u128::from(lamports) * u128::from(self.tainted) / u128::from(base)) as u64 }
+            },
+            TaintPolicy::Fifo => {
+                let (mut remaining, mut tainted) = (lamports, 0);
+                while remaining > 0 {
+                    let Some((amount, is_tainted)) = self.lots.front_mut() else { break };
+                    let taken = remaining.min(*amount);
+                    if *is_tainted {
+                        tainted += taken;
+                    }
+                    *amount -= taken;
+                    remaining -= taken;
+                    if *amount == 0 {
+                        self.lots.pop_front();
+                    }
+                }
+                tainted
+            }
+        };
+        self.balance = self.balance.saturating_sub(lamports);
+        self.tainted = self.tainted.saturating_sub(tainted).min(self.balance);
+        tainted
+    }
+}
+
+impl TransferGraph {
+    /// Propagate taint forward through the graph. Transfers are processed in the order of their slot, block
+    /// time and position, transfers of different transactions in the same slot are ordered by signature.
+    /// Sources that match no transfer are reported in the result's `unmatched`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - Origins of the tainted funds.
+    /// * `policy` - Policy deciding which part of the funds leaving an account is tainted.
+    pub fn propagate_taint(&self, sources: &[TaintSource], policy: TaintPolicy) -> TaintReport {
+        let mut holdings: Vec<Holdings> = self.nodes().iter().map(|_| Holdings::default()).collect();
+        let mut received = vec![0u64; self.nodes().len()];
+        let mut sent = vec![0u64; self.nodes().len()];
+        let mut seeded: HashMap<&str, Option<u64>> = HashMap::new();
+        let mut unmatched = vec![];
+        let signatures: HashSet<&str> = self.edges().iter().map(|x| x.signature.as_str()).collect();
+        for source in sources {
+            match source {
+                TaintSource::Account { account, lamports } => match self.node_index(account) {
+                    Some(node) => holdings[node].receive(*lamports, *lamports, policy),
+                    None => unmatched.push(account.clone())
+                },
+                TaintSource::Transaction { signature, .. } if !signatures.contains(signature.as_str()) => {
+                    unmatched.push(signature.clone());
+                },
+                TaintSource::Transaction { signature, lamports } => {
+                    seeded.insert(signature, *lamports);
+                }
+            }
+        }
+
+        let mut order: Vec<usize> = (0..self.edges().len()).collect();
+        order.sort_by_key(|edge| {
+            let edge = &self.edges()[*edge];
+            (edge.slot, edge.block_time, &edge.signature, edge.instruction_index, edge.inner_index)
+        });
+
+        let mut transfers = vec![];
+        for index in order {
+            let edge = &self.edges()[index];
+            let mut tainted = holdings[edge.source].send(edge.lamports, policy);
+            if let Some(remaining) = seeded.get_mut(edge.signature.as_str()) {
+                let extra = remaining.map_or(edge.lamports, |x| x.min(edge.lamports)).min(edge.lamports - tainted);
+                *remaining = remaining.map(|x| x - extra);
+                tainted += extra;
+            }
+            holdings[edge.destination].receive(edge.lamports, tainted, policy);
+            if tainted > 0 {
+                sent[edge.source] = sent[edge.source].saturating_add(tainted);
+                received[edge.destination] = received[edge.destination].saturating_add(tainted);
+                if let Some(transfer) = self.transfer(index) {
+                    transfers.push(TransferTaint { transfer, tainted });
+                }
+            }
+        }
+
+        let accounts = self.nodes().iter().enumerate()
+            .map(|(index, node)| AccountTaint {
+                account: node.account.clone(),
+                received: received[index],
+                sent: sent[index],
+                held: holdings[index].tainted
+            })
+            .filter(|x| x.received > 0 || x.sent > 0 || x.held > 0)
+            .collect();
+        TaintReport { policy, accounts, transfers, unmatched }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(source: &str, destination: &str, lamports: u64, slot: u64, signature: &str) -> InstructionTransferWrapper {
+        InstructionTransferWrapper {
+            program: "11111111111111111111111111111111".to_string(),
+            lamports,
+            source: source.to_string(),
+            destination: destination.to_string(),
+            action: "Transfer".to_string(),
+            block_time: 1_600_000_000 + slot,
+            human_time: String::new(),
+            slot,
+            signature: signature.to_string(),
+            instruction_index: 0,
+            inner_index: None
+        }
+    }
+
+    /// `A` holds 100 tainted lamports, receives 100 clean lamports, then sends 50 to `B` and 100 to `C`.
+    /// Returns the tainted lamports sent to `B` and `C`, and held by `A`.
+    fn propagate(policy: TaintPolicy) -> (u64, u64, u64) {
+        let graph = TransferGraph::from_transfers(&[
+            transfer("X", "A", 100, 1, "s1"),
+            transfer("A", "B", 50, 2, "s2"),
+            transfer("A", "C", 100, 3, "s3")
+        ]);
+        let report = graph.propagate_taint(&[TaintSource::Account { account: "A".to_string(), lamports: 100 }], policy);
+        assert_eq!(report.policy, policy);
+        assert!(report.unmatched.is_empty());
+        let account = |name: &str| report.accounts.iter().find(|x| x.account == name).cloned();
+        assert!(account("X").is_none());
+        let a = account("A").unwrap();
+        let (b, c) = (account("B").map_or(0, |x| x.received), account("C").map_or(0, |x| x.received));
+        assert_eq!(a.sent, b + c);
+        assert_eq!(report.transfers.iter().map(|x| x.tainted).sum::<u64>(), b + c);
+        (b, c, a.held)
+    }
+
+    #[test]
+    fn poison_taints_everything_sent() {
+        assert_eq!(propagate(TaintPolicy::Poison), (50, 100, 50));
+    }
+
+    #[test]
+    fn haircut_taints_in_proportion() {
+        assert_eq!(propagate(TaintPolicy::Haircut), (25, 50, 25));
+    }
+
+    #[test]
+    fn fifo_sends_funds_in_order_received() {
+        assert_eq!(propagate(TaintPolicy::Fifo), (50, 50, 0));
+    }
+
+    #[test]
+    fn taint_transactions_and_report_unmatched_sources() {
+        let graph = TransferGraph::from_transfers(&[
+            transfer("Y", "Z", 30, 1, "s1"),
+            transfer("Z", "W", 30, 2, "s2")
+        ]);
+        let sources = [
+            TaintSource::Transaction { signature: "s1".to_string(), lamports: Some(10) },
+            TaintSource::Transaction { signature: "missing".to_string(), lamports: None },
+            TaintSource::Account { account: "nobody".to_string(), lamports: 5 }
+        ];
+        let report = graph.propagate_taint(&sources, TaintPolicy::Haircut);
+        assert_eq!(report.unmatched, vec!["missing".to_string(), "nobody".to_string()]);
+        let tainted: Vec<(&str, u64)> = report.transfers.iter().map(|x| (x.transfer.signature.as_str(), x.tainted)).collect();
+        assert_eq!(tainted, vec![("s1", 10), ("s2", 10)]);
+    }
+
+    #[test]
+    fn parse_policy() {
+        assert_eq!(TaintPolicy::from_str("fifo"), Ok(TaintPolicy::Fifo));
+        assert_eq!(TaintPolicy::from_str(&TaintPolicy::Poison.to_string()), Ok(TaintPolicy::Poison));
+        assert!(TaintPolicy::from_str("lifo").is_err());
+    }
+}
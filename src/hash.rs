@@ -1,18 +1,22 @@
 use std::{hash::{Hash, Hasher}, collections::hash_map::DefaultHasher};
 
-/// Data structure used to keep track of isolated SOL transfer instructions.
-/// Contains `source`, `dest` and `signature` fields.
+/// Data structure used to identify a single instruction invoked within a transaction.
+/// Contains `signature`, `instruction_index` and `inner_index` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SData {
-    pub source: String,
-    pub dest: String,
-    pub signature: String
+    /// Transaction signature.
+    pub signature: String,
+    /// Index of the outer transaction instruction.
+    pub instruction_index: usize,
+    /// Stack position within the inner instructions of `instruction_index`, `None` for the outer instruction itself.
+    pub inner_index: Option<usize>
 }
 
 impl Hash for SData {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.source.hash(state);
-        self.dest.hash(state);
         self.signature.hash(state);
+        self.instruction_index.hash(state);
+        self.inner_index.hash(state);
     }
 }
 /// Calculate hash. 
@@ -1,5 +1,9 @@
 use pyo3::prelude::*;
-use solana_sdk::commitment_config::CommitmentConfig;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::types::PyDict;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
 use std::time::Duration;
 
 pub mod parsing;
@@ -8,26 +12,102 @@ pub mod hash;
 pub mod errors;
 pub mod constants;
 pub mod client;
+pub mod cache;
+pub mod source;
 pub mod signature;
 pub mod logic;
+pub mod graph;
 
-use client::{ClientConfig, get_client};
+use cache::{CacheConfig, TransactionCache};
+use client::{ClientConfig, ClientOverrides, get_client, get_offline_client};
+use client::cassette::CassetteMode;
+use errors::{SolanaRpcError, RpcConnectionError, SignatureParseError, TransactionDecodeError, InvalidTimeFormat, NotFoundError};
 use signature::signature::SignatureConfig;
-use logic::get_transfers_recursive;
+use source::cache::CachedSource;
+use source::fixture::FixtureSource;
+use source::source::ChainSource;
+use logic::{trace_transfers, TraceBudget, TraceConfig, TraceDirection, TraceFailure, TraceFrontier, TraceResult, TraceStats};
 use transaction::instructions::InstructionTransferWrapper;
+use transaction::decoder::DecoderRegistry;
+use graph::export::GraphFormat;
+use graph::graph::TransferGraph;
+use graph::paths::{PathAnalysis, TransferPath, DEFAULT_MAX_PATHS};
+use graph::taint::{AccountTaint, TaintPolicy, TaintReport, TaintSource, TransferTaint};
 
-/// Get SOL transactions.
-#[pyfunction]
-fn get_transactions(account: String, start: String, end: String, depth: i32) -> PyResult<Vec<InstructionTransferWrapper>> {
+/// Get SOL transactions. All arguments after `depth` are keyword-only and optional, unknown keywords
+/// raise `TypeError`. Returns a `TraceResult` with the transfers found in chain order, the failures that
+/// were skipped, the accounts left unexpanded and the work done.
+///
+/// Raises `SignatureParseError` or `InvalidTimeFormat` for invalid arguments, and the matching
+/// `SolanaRpcError` subclass if the signatures of `account` could not be fetched. Failures
+/// further down the trace are skipped and reported in the result's `failures`.
+///
+/// `direction` is one of `forward` (where the funds went), `backward` (where the funds came from)
+/// or `both`. If `causal` is set only outgoing transfers made after the funds arrived at an account
+/// are followed forward, backward traces always respect time. `max_delay` limits the seconds between
+/// the arrival of funds at an account and the transfer that moved them on.
+///
+/// SPL Token transfers are only followed if `tokens` is set, between token accounts or their owners, and
+/// are returned in the result's `token_transfers`.
+///
+/// Transactions are fetched with JSON-RPC batch requests of `batch_size` transactions, up to `parallelism`
+/// batches concurrently. The result does not depend on either.
+///
+/// The trace stops once `max_accounts`, `max_transactions`, `max_rpc_calls` or `max_seconds` is
+/// reached, and the accounts left unexpanded are reported in the result's `frontier`.
+///
+/// If `cache_dir` is set finalized transactions are read from and stored in that directory, which
+/// is kept below `cache_max_bytes` by evicting the least recently used transactions. If `offline` is
+/// set no RPC request is sent and only cached transactions and signature pages are used. If `fixtures` is set the chain
+/// data is read from those JSON fixture files instead of the RPC node, see `FixtureSource`. If `record`
+/// is set every RPC request and response is written to that cassette file, if `replay` is set the
+/// responses are served from it instead of sending requests.
+///
+/// `idl` lists Anchor IDL files, the instructions of their programs are decoded with an `AnchorDecoder`.
+///
+/// Requests failing with HTTP 429, HTTP 5xx, a rate limit or node behind JSON-RPC error, a timeout or a
+/// connection error are retried up to `max_retries` times with exponential backoff, honoring `Retry-After`, and `rate_limit` caps the
+/// requests sent per second. If `endpoints` is set the requests are balanced over that pool of endpoints,
+/// each formatted as `url[,weight=<weight>][,archival][,rate_limit=<requests per second>]`.
+///
+/// The RPC endpoint, timeout (in seconds), commitment, HTTP headers and retry settings default to the values
+/// resolved by `ClientConfig::load`, i.e. the `SOLANA_RPC_*` environment variables and the
+/// `config` TOML file.
+#[pyfunction(kwargs = "**")]
+fn get_transactions(account: String, start: String, end: String, depth: usize, kwargs: Option<&PyDict>) -> PyResult<TraceResult> {
+    let mut kwargs = Kwargs::new("get_transactions", kwargs);
+    let config: Option<String> = kwargs.take("config")?;
+    let overrides = client_overrides(&mut kwargs)?;
+    let trace = trace_config(depth, &mut kwargs)?;
+    let cache_dir: Option<String> = kwargs.take("cache_dir")?;
+    let cache_max_bytes: Option<u64> = kwargs.take("cache_max_bytes")?;
+    let offline = kwargs.take("offline")?.unwrap_or(false);
+    let fixtures: Option<Vec<String>> = kwargs.take("fixtures")?;
+    let idl: Option<Vec<String>> = kwargs.take("idl")?;
+    kwargs.finish()?;
 
     /* Get the RPC client */
-    let client_config = ClientConfig {
-        url: "https://ssc-dao.genesysgo.net/".to_string(),
-        timeout: Duration::from_secs(20),
-        commitment: CommitmentConfig::finalized()
+    let mut client_config = ClientConfig::load(config.as_deref().map(Path::new))?;
+    client_config.apply_overrides(overrides)?;
+    let commitment = client_config.commitment;
+
+    let base: Box<dyn ChainSource> = match fixtures {
+        Some(fixtures) => Box::new(FixtureSource::load(&fixtures)?),
+        None if offline => Box::new(get_offline_client(&client_config)),
+        None => Box::new(get_client(client_config)?)
     };
 
-    let client = get_client(client_config);
+    /* Open the transaction cache */
+    let cache = match cache_dir {
+        Some(dir) => Some(TransactionCache::open(CacheConfig { dir: dir.into(), max_bytes: cache_max_bytes, offline })?),
+        None if offline => return Err(PyValueError::new_err("offline requires cache_dir")),
+        None => None
+    };
+    let cached = cache.as_ref().map(|cache| CachedSource::new(cache, base.as_ref()));
+    let source: &dyn ChainSource = match &cached {
+        Some(cached) => cached,
+        None => base.as_ref()
+    };
 
     /* Specify signature configuration */
     let signature_config = SignatureConfig {
@@ -36,20 +116,157 @@ fn get_transactions(account: String, start: String, end: String, depth: i32) ->
         before: None,
         until: None,
         limit: Some(1000),
-        commitment: Some(CommitmentConfig::finalized())
+        max: None,
+        commitment: Some(commitment)
     };
 
     /* Get SOL transfers */
-    let mut data: Vec<InstructionTransferWrapper> = vec![];
-    get_transfers_recursive(&account, &client, &signature_config, depth, &mut data);
+    let registry = DecoderRegistry::with_idls(&idl.unwrap_or_default())?;
+    let result = trace_transfers(&account, source, &signature_config, &registry, &trace);
+    if let Some(failure) = result.root_failure(&account) {
+        return Err(failure.error.clone().into());
+    }
+
+    Ok(result)
+
+}
 
-    Ok(data.to_vec())
+/// Keyword arguments of a Python function, taken one at a time by name.
+struct Kwargs<'a> {
+    function: &'static str,
+    kwargs: Option<&'a PyDict>,
+    /// Names of the keyword arguments taken so far.
+    taken: Vec<&'static str>
+}
+
+impl<'a> Kwargs<'a> {
+    fn new(function: &'static str, kwargs: Option<&'a PyDict>) -> Self {
+        Kwargs { function, kwargs, taken: vec![] }
+    }
+
+    /// Return the value of the keyword argument `name`, `None` if it is missing or `None`.
+    fn take<T: FromPyObject<'a>>(&mut self, name: &'static str) -> PyResult<Option<T>> {
+        self.taken.push(name);
+        match self.kwargs.and_then(|x| x.get_item(name)) {
+            Some(value) => value.extract::<Option<T>>()
+                .map_err(|err| PyTypeError::new_err(format!("{}() argument '{}': {}", self.function, name, err))),
+            None => Ok(None)
+        }
+    }
+
+    /// Raise `TypeError` for the keyword arguments that were not taken, like Python does for unexpected ones.
+    fn finish(self) -> PyResult<()> {
+        for name in self.kwargs.into_iter().flat_map(|x| x.keys()) {
+            let name: &str = name.extract()?;
+            if !self.taken.contains(&name) {
+                return Err(PyTypeError::new_err(format!("{}() got an unexpected keyword argument '{}'", self.function, name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Take the client configuration keyword arguments, i.e. `rpc_url`, `endpoints`, `timeout`, `commitment`,
+/// `headers`, `record`, `replay`, `max_retries` and `rate_limit`.
+fn client_overrides(kwargs: &mut Kwargs) -> PyResult<ClientOverrides> {
+    let cassette = match (kwargs.take::<String>("record")?, kwargs.take::<String>("replay")?) {
+        (Some(_), Some(_)) => return Err(PyValueError::new_err("record and replay are mutually exclusive")),
+        (Some(path), None) => Some(CassetteMode::Record(path.into())),
+        (None, Some(path)) => Some(CassetteMode::Replay(path.into())),
+        (None, None) => None
+    };
+    Ok(ClientOverrides {
+        url: kwargs.take("rpc_url")?,
+        endpoints: kwargs.take("endpoints")?.unwrap_or_default(),
+        timeout: kwargs.take("timeout")?,
+        commitment: kwargs.take("commitment")?,
+        headers: kwargs.take("headers")?.unwrap_or_default(),
+        cassette,
+        max_retries: kwargs.take("max_retries")?,
+        rate_limit: kwargs.take("rate_limit")?
+    })
+}
+
+/// Take the trace keyword arguments, i.e. `max_accounts`, `max_transactions`, `max_rpc_calls`,
+/// `max_seconds`, `direction`, `causal`, `max_delay`, `parallelism`, `batch_size` and `tokens`.
+fn trace_config(depth: usize, kwargs: &mut Kwargs) -> PyResult<TraceConfig> {
+    let max_duration = match kwargs.take("max_seconds")?.map(Duration::try_from_secs_f64).transpose() {
+        Ok(max_duration) => max_duration,
+        Err(err) => return Err(PyValueError::new_err(format!("Invalid max_seconds: {}", err)))
+    };
+    let direction: Option<String> = kwargs.take("direction")?;
+    Ok(TraceConfig {
+        depth,
+        budget: TraceBudget {
+            max_accounts: kwargs.take("max_accounts")?,
+            max_transactions: kwargs.take("max_transactions")?,
+            max_rpc_calls: kwargs.take("max_rpc_calls")?,
+            max_duration
+        },
+        direction: match direction {
+            Some(direction) => TraceDirection::from_str(&direction).map_err(PyValueError::new_err)?,
+            None => TraceDirection::Forward
+        },
+        causal: kwargs.take("causal")?.unwrap_or(false),
+        max_delay: kwargs.take("max_delay")?.map(Duration::from_secs),
+        parallelism: kwargs.take("parallelism")?.unwrap_or(1),
+        batch_size: kwargs.take("batch_size")?.unwrap_or(1),
+        tokens: kwargs.take("tokens")?.unwrap_or(false)
+    })
+}
+
+/// Export transfers, e.g. the `transfers` of a `TraceResult`, as a graph with accounts as nodes and
+/// transfers as edges. `format` is one of `dot`, `graphml` or `gexf`.
+#[pyfunction(format = "\"dot\"")]
+fn export_graph(transfers: Vec<InstructionTransferWrapper>, format: &str) -> PyResult<String> {
+    let format = GraphFormat::from_str(format).map_err(PyValueError::new_err)?;
+    Ok(TransferGraph::from_transfers(&transfers).export(format))
+}
+
+/// Find the time-respecting paths of funds from `source` to `destination` through `transfers`, e.g. the
+/// `transfers` of a `TraceResult`, up to `max_hops` transfers long. Returns a `PathAnalysis` with the
+/// paths, the earliest and latest of them, and the maximum flow of lamports through them. At most
+/// `max_paths` paths are searched, `None` searches them all, which can take exponential time.
+#[pyfunction(max_hops = "6", max_paths = "DEFAULT_MAX_PATHS")]
+fn find_paths(transfers: Vec<InstructionTransferWrapper>, source: &str, destination: &str, max_hops: usize, max_paths: Option<usize>) -> PathAnalysis {
+    TransferGraph::from_transfers(&transfers).analyze_paths(source, destination, max_hops, max_paths)
+}
 
+/// Propagate taint forward through `transfers`, e.g. the `transfers` of a `TraceResult`. `accounts` maps
+/// accounts to the tainted lamports they hold before the transfers, `transactions` maps signatures of
+/// transactions whose transfers are tainted to the tainted lamports they move, or `None` for all of them.
+/// `policy` is one of `poison`, `haircut` or `fifo`. Returns a `TaintReport` with the tainted amounts per
+/// account and transfer, and the accounts and signatures that match no transfer.
+#[pyfunction(accounts = "None", transactions = "None", policy = "\"haircut\"")]
+fn propagate_taint(transfers: Vec<InstructionTransferWrapper>, accounts: Option<HashMap<String, u64>>, transactions: Option<HashMap<String, Option<u64>>>, policy: &str) -> PyResult<TaintReport> {
+    let policy = TaintPolicy::from_str(policy).map_err(PyValueError::new_err)?;
+    let mut sources: Vec<TaintSource> = vec![];
+    sources.extend(accounts.unwrap_or_default().into_iter().map(|(account, lamports)| TaintSource::Account { account, lamports }));
+    sources.extend(transactions.unwrap_or_default().into_iter().map(|(signature, lamports)| TaintSource::Transaction { signature, lamports }));
+    Ok(TransferGraph::from_transfers(&transfers).propagate_taint(&sources, policy))
 }
 
 /// A Python module implemented in Rust.
 #[pymodule]
-fn solana_rpc(_py: Python, m: &PyModule) -> PyResult<()> {
+fn solana_rpc(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(get_transactions, m)?)?;
+    m.add_class::<TraceResult>()?;
+    m.add_class::<TraceFailure>()?;
+    m.add_class::<TraceFrontier>()?;
+    m.add_class::<TraceStats>()?;
+    m.add_function(wrap_pyfunction!(export_graph, m)?)?;
+    m.add_function(wrap_pyfunction!(find_paths, m)?)?;
+    m.add_class::<PathAnalysis>()?;
+    m.add_class::<TransferPath>()?;
+    m.add_function(wrap_pyfunction!(propagate_taint, m)?)?;
+    m.add_class::<TaintReport>()?;
+    m.add_class::<AccountTaint>()?;
+    m.add_class::<TransferTaint>()?;
+    m.add("SolanaRpcError", py.get_type::<SolanaRpcError>())?;
+    m.add("RpcConnectionError", py.get_type::<RpcConnectionError>())?;
+    m.add("SignatureParseError", py.get_type::<SignatureParseError>())?;
+    m.add("TransactionDecodeError", py.get_type::<TransactionDecodeError>())?;
+    m.add("InvalidTimeFormat", py.get_type::<InvalidTimeFormat>())?;
+    m.add("NotFoundError", py.get_type::<NotFoundError>())?;
     Ok(())
 }
\ No newline at end of file
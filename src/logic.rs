@@ -1,40 +1,590 @@
-use solana_client::rpc_client::RpcClient;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::panic;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use pyo3::prelude::*;
 
-use crate::signature::signature::{SignatureConfig, Signatures};
-use crate::transaction::decode::decode_instruction;
-use crate::transaction::instructions::InstructionTransferWrapper;
-use crate::transaction::transaction::TransactionDetail;
+use crate::errors::{TraceError, TransactionDetailError};
+use crate::hash::SData;
+use crate::signature::signature::{SignatureBoundaries, SignatureConfig, SignatureDetail, Signatures};
+use crate::source::source::ChainSource;
+use crate::transaction::decoder::{DecodedInstruction, DecoderRegistry};
+use crate::transaction::instructions::{InstructionTokenTransferWrapper, InstructionTransferWrapper};
+use crate::transaction::transaction::{InstructionProcessed, TransactionDetail};
 
-/// Get SOL transfers starting from `account`.
+/// Item that could not be processed during a trace and was skipped.
+/// Contains `account`, `signature` and `error` fields.
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct TraceFailure {
+    /// Account being traced when the failure occurred.
+    #[pyo3(get)]
+    pub account: String,
+    /// Transaction signature, if the failure is specific to a transaction.
+    #[pyo3(get)]
+    pub signature: Option<String>,
+    /// The error that occurred.
+    pub error: TraceError
+}
+
+#[pymethods]
+impl TraceFailure {
+    /// The error that occurred, as a message.
+    #[getter(error)]
+    fn py_error(&self) -> String {
+        self.error.to_string()
+    }
+}
+
+/// Limits on the work done by a single trace. Limits that are `None` are not enforced.
+/// Contains `max_accounts`, `max_transactions`, `max_rpc_calls` and `max_duration` fields.
+#[derive(Debug, Clone, Default)]
+pub struct TraceBudget {
+    /// Maximum number of accounts to expand.
+    pub max_accounts: Option<usize>,
+    /// Maximum number of distinct transactions to fetch.
+    pub max_transactions: Option<usize>,
+    /// Maximum number of RPC requests to send, as counted by the source's client transport. Budgets are checked
+    /// before each account and transaction, so requests for a page of signatures may exceed it slightly.
+    pub max_rpc_calls: Option<usize>,
+    /// Maximum wall-clock time to spend.
+    pub max_duration: Option<Duration>
+}
+
+/// Budget limit that stopped a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceLimit {
+    MaxAccounts,
+    MaxTransactions,
+    MaxRpcCalls,
+    MaxDuration
+}
+
+impl fmt::Display for TraceLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::MaxAccounts => "max accounts",
+            Self::MaxTransactions => "max transactions",
+            Self::MaxRpcCalls => "max RPC calls",
+            Self::MaxDuration => "max duration"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Direction in which transfers are followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TraceDirection {
+    /// Follow transfers to their destination, i.e. where the funds went.
+    #[default]
+    Forward,
+    /// Follow transfers to their source, i.e. where the funds came from.
+    Backward,
+    /// Follow transfers in both directions from the starting account.
+    Both
+}
+
+impl fmt::Display for TraceDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Forward => "forward",
+            Self::Backward => "backward",
+            Self::Both => "both"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for TraceDirection {
+    type Err = String;
+
+    fn from_str(direction: &str) -> Result<Self, Self::Err> {
+        match direction {
+            "forward" => Ok(Self::Forward),
+            "backward" => Ok(Self::Backward),
+            "both" => Ok(Self::Both),
+            _ => Err(format!("Invalid trace direction {}, expected forward, backward or both", direction))
+        }
+    }
+}
+
+/// Trace configuration.
+/// Contains `depth`, `budget`, `direction`, `causal`, `max_delay`, `parallelism`, `batch_size` and `tokens` fields.
+#[derive(Debug, Clone, Default)]
+pub struct TraceConfig {
+    /// Number of hops to follow from the starting account. `0` only expands the starting account.
+    pub depth: usize,
+    /// Limits on the work done by the trace.
+    pub budget: TraceBudget,
+    /// Direction in which transfers are followed.
+    pub direction: TraceDirection,
+    /// Only follow funds forward in time: outgoing transfers of an account are considered only if they
+    /// were processed in or after the slot of the transfer the funds arrived with. Backward hops always
+    /// respect time, only incoming transfers processed before the funds left an account are considered.
+    pub causal: bool,
+    /// The maximum time between the arrival of funds at an account and the transfer that moved them on.
+    /// Applies to backward hops and, in causal mode, forward hops.
+    pub max_delay: Option<Duration>,
+    /// Number of batches of transactions fetched concurrently, `0` and `1` fetch them one batch at a
+    /// time. Transactions are fetched in rounds of `parallelism` batches and processed in the order of
+    /// their signatures, so the result does not depend on it. The RPC call and duration budgets are only
+    /// checked between rounds.
+    pub parallelism: usize,
+    /// Number of transactions fetched with a single request, e.g. a JSON-RPC batch, `0` and `1` fetch
+    /// them with a request each.
+    pub batch_size: usize,
+    /// Also follow SPL Token `Transfer` and `TransferChecked` instructions, from token account to token
+    /// account. A token transfer leaves an account if it is the source token account or its owner, and
+    /// arrives at the destination token account or its owner.
+    pub tokens: bool
+}
+
+/// Transfer followed by a trace, of lamports or, if `TraceConfig::tokens` is set, of SPL Tokens.
+#[derive(Debug, Clone)]
+enum Followed {
+    Lamports(InstructionTransferWrapper),
+    Tokens(InstructionTokenTransferWrapper)
+}
+
+impl Followed {
+    /// Return the transfer `decoded` is, if the trace follows it.
+    fn new(decoded: DecodedInstruction, tokens: bool) -> Option<Self> {
+        match decoded {
+            DecodedInstruction::Transfer(transfer) => Some(Self::Lamports(transfer)),
+            DecodedInstruction::TokenTransfer(transfer) if tokens && matches!(transfer.action.as_str(), "Transfer" | "TransferChecked") => {
+                Some(Self::Tokens(transfer))
+            },
+            _ => None
+        }
+    }
+
+    fn source(&self) -> &str {
+        match self {
+            Self::Lamports(transfer) => &transfer.source,
+            Self::Tokens(transfer) => &transfer.source
+        }
+    }
+
+    fn destination(&self) -> &str {
+        match self {
+            Self::Lamports(transfer) => &transfer.destination,
+            Self::Tokens(transfer) => &transfer.destination
+        }
+    }
+
+    /// Whether the transfer moves funds out of `account`.
+    fn leaves(&self, account: &str) -> bool {
+        match self {
+            Self::Lamports(transfer) => transfer.source == account,
+            Self::Tokens(transfer) => transfer.source == account || transfer.source_owner.as_deref() == Some(account)
+        }
+    }
+
+    /// Whether the transfer moves funds into `account`.
+    fn arrives(&self, account: &str) -> bool {
+        match self {
+            Self::Lamports(transfer) => transfer.destination == account,
+            Self::Tokens(transfer) => transfer.destination == account || transfer.destination_owner.as_deref() == Some(account)
+        }
+    }
+
+    /// Return `(slot, block_time)`.
+    fn time(&self) -> (u64, u64) {
+        match self {
+            Self::Lamports(transfer) => (transfer.slot, transfer.block_time),
+            Self::Tokens(transfer) => (transfer.slot, transfer.block_time)
+        }
+    }
+
+    fn id(&self) -> SData {
+        match self {
+            Self::Lamports(transfer) => transfer.id(),
+            Self::Tokens(transfer) => transfer.id()
+        }
+    }
+}
+
+/// Transfer through which a hop reached an account: for forward hops the transfer the funds arrived
+/// with, for backward hops the transfer the funds left with. Contains `slot`, `block_time` and `id` fields.
+#[derive(Debug, Clone)]
+struct Anchor {
+    slot: u64,
+    block_time: u64,
+    id: SData
+}
+
+impl Anchor {
+    fn new(transfer: &Followed) -> Self {
+        let (slot, block_time) = transfer.time();
+        Anchor { slot, block_time, id: transfer.id() }
+    }
+
+    /// Whether `transfer` is ordered `ordering` relative to the anchor, i.e. `Greater` if it was processed
+    /// after the anchor. Transfers in the same slot are only ordered within the same transaction, by their
+    /// position, and otherwise satisfy either ordering.
+    fn is(&self, transfer: &Followed, ordering: Ordering) -> bool {
+        let id = transfer.id();
+        if id.signature == self.id.signature {
+            let position = (id.instruction_index, id.inner_index);
+            return position.cmp(&(self.id.instruction_index, self.id.inner_index)) == ordering;
+        }
+        let order = transfer.time().0.cmp(&self.slot);
+        order == ordering || order == Ordering::Equal
+    }
+}
+
+/// Account to expand during a trace.
+/// Contains `account`, `depth`, `direction` and `anchor` fields.
+struct Hop {
+    account: String,
+    depth: usize,
+    /// Either `Forward` or `Backward`.
+    direction: TraceDirection,
+    /// Transfer the account was reached through, `None` for the starting account and forward hops
+    /// outside of causal mode.
+    anchor: Option<Anchor>
+}
+
+/// Account that was left unexpanded, or only partially expanded, because a budget was exhausted.
+/// Contains `account`, `depth`, `direction`, `partial` and `limit` fields.
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct TraceFrontier {
+    /// Account as base-58 encoded string.
+    #[pyo3(get)]
+    pub account: String,
+    /// Number of hops from the starting account.
+    #[pyo3(get)]
+    pub depth: usize,
+    /// Direction the account was reached in, either `Forward` or `Backward`.
+    pub direction: TraceDirection,
+    /// Whether some of the account's transactions were processed before the budget was exhausted.
+    #[pyo3(get)]
+    pub partial: bool,
+    /// The budget limit that was exhausted.
+    pub limit: TraceLimit
+}
+
+#[pymethods]
+impl TraceFrontier {
+    /// Direction the account was reached in, either `forward` or `backward`.
+    #[getter(direction)]
+    fn py_direction(&self) -> String {
+        self.direction.to_string()
+    }
+
+    /// The budget limit that was exhausted, e.g. `max accounts`.
+    #[getter(limit)]
+    fn py_limit(&self) -> String {
+        self.limit.to_string()
+    }
+}
+
+/// Work done by a trace.
+/// Contains `accounts`, `transactions`, `rpc_calls` and `elapsed` fields.
+#[derive(Debug, Clone, Default)]
+#[pyclass]
+pub struct TraceStats {
+    /// Number of accounts expanded, counting each direction separately.
+    #[pyo3(get)]
+    pub accounts: usize,
+    /// Number of distinct transactions fetched.
+    #[pyo3(get)]
+    pub transactions: usize,
+    /// Number of RPC requests sent.
+    #[pyo3(get)]
+    pub rpc_calls: usize,
+    /// Wall-clock time spent.
+    pub elapsed: Duration
+}
+
+#[pymethods]
+impl TraceStats {
+    /// Wall-clock time spent, in seconds.
+    #[getter(elapsed)]
+    fn py_elapsed(&self) -> f64 {
+        self.elapsed.as_secs_f64()
+    }
+}
+
+/// Result of a trace.
+/// Contains `transfers`, `token_transfers`, `failures`, `frontier` and `stats` fields.
+#[derive(Debug, Clone, Default)]
+#[pyclass]
+pub struct TraceResult {
+    /// Decoded transfers, sorted by slot, signature, instruction index and inner instruction index.
+    #[pyo3(get)]
+    pub transfers: Vec<InstructionTransferWrapper>,
+    /// Decoded SPL Token transfers if `TraceConfig::tokens` is set, in the same order as `transfers`.
+    #[pyo3(get)]
+    pub token_transfers: Vec<InstructionTokenTransferWrapper>,
+    /// Items that failed and were skipped.
+    #[pyo3(get)]
+    pub failures: Vec<TraceFailure>,
+    /// Accounts left unexpanded because a budget was exhausted.
+    #[pyo3(get)]
+    pub frontier: Vec<TraceFrontier>,
+    /// Work done by the trace.
+    #[pyo3(get)]
+    pub stats: TraceStats
+}
+
+impl TraceResult {
+    /// Return the failure to fetch the signatures of `account`, the account the trace started from, if any.
+    /// The trace found nothing in that case.
+    pub fn root_failure(&self, account: &str) -> Option<&TraceFailure> {
+        self.failures.iter().find(|x| x.account == account && x.signature.is_none())
+    }
+}
+
+/// Tracks the budget of a running trace.
+struct BudgetTracker<'a> {
+    budget: &'a TraceBudget,
+    source: &'a dyn ChainSource,
+    started: Instant,
+    request_count: usize
+}
+
+impl<'a> BudgetTracker<'a> {
+    fn new(budget: &'a TraceBudget, source: &'a dyn ChainSource) -> Self {
+        BudgetTracker { budget, source, started: Instant::now(), request_count: source.request_count() }
+    }
+
+    /// Number of RPC requests sent since the trace started.
+    fn rpc_calls(&self) -> usize {
+        self.source.request_count().saturating_sub(self.request_count)
+    }
+
+    /// Return the first limit exhausted by `stats`, if any. The account limit is only checked
+    /// before expanding a new account.
+    fn exhausted(&self, stats: &TraceStats, expanding: bool) -> Option<TraceLimit> {
+        let reached = |limit: Option<usize>, value: usize| limit.is_some_and(|limit| value >= limit);
+        if expanding && reached(self.budget.max_accounts, stats.accounts) {
+            Some(TraceLimit::MaxAccounts)
+        } else if reached(self.budget.max_transactions, stats.transactions) {
+            Some(TraceLimit::MaxTransactions)
+        } else if reached(self.budget.max_rpc_calls, self.rpc_calls()) {
+            Some(TraceLimit::MaxRpcCalls)
+        } else if self.budget.max_duration.is_some_and(|limit| self.started.elapsed() >= limit) {
+            Some(TraceLimit::MaxDuration)
+        } else {
+            None
+        }
+    }
+}
+
+/// Trace SOL transfers breadth-first starting from `account`, following each transfer to its destination,
+/// its source, or both, depending on `trace.direction`. Every account is expanded at most once per direction,
+/// except when time is respected and the account is found to be reached through an earlier (forward) or
+/// later (backward) transfer than before. Signatures and transactions that fail to be fetched or processed
+/// are recorded in the result's `failures` and skipped. A transaction shared by several accounts is only
+/// fetched once and counted once against the budget. Once a budget is exhausted the trace stops and the
+/// accounts left to expand are reported in the result's `frontier`. Only lamport transfers are followed,
+/// unless `trace.tokens` is set, in which case SPL Token transfers decoded by `registry` are followed too
+/// and returned in the result's `token_transfers`. The transfers are returned in chain order, i.e.
+/// sorted by slot, signature, instruction index and inner instruction index, outer instructions first.
 /// 
 /// # Arguments
 /// * `account` - Account as base-58 encoded string.
-/// * `client` - RPC client.
+/// * `source` - Chain data source, e.g. an RPC client.
 /// * `config` - Config for signature query.
-/// * `breadth` - Depth of signature query.
-/// * `finished_signatures` - Array of `SData` hashes to keep track of seen instructions.
+/// * `registry` - Decoders used to decode the instructions of each transaction.
+/// * `trace` - Depth, budget, direction and causality of the trace.
 /// ```
-pub fn get_transfers_recursive(account: &String, client: &RpcClient, config: &SignatureConfig, breadth: i32, data: &mut Vec<InstructionTransferWrapper>) -> Vec<InstructionTransferWrapper> {
-    if breadth < 0 { return data.to_vec() };
-    let signatures = Signatures::fetch(&account, &client, &config).unwrap();
-    for signature in &signatures {
-        let transaction = TransactionDetail::new(&signature.signature, &client);
-        let intsructions = transaction.get_instructions_processed();
-        for inner_instruction in intsructions {
-            if let Some(decoded) = decode_instruction(inner_instruction) {
-                if data.contains(&decoded) {
-                    continue;
+pub fn trace_transfers(account: &str, source: &dyn ChainSource, config: &SignatureConfig, registry: &DecoderRegistry, trace: &TraceConfig) -> TraceResult {
+    let tracker = BudgetTracker::new(&trace.budget, source);
+    let mut result = TraceResult::default();
+    // Accounts queued so far per direction, with the slot of the transfer they were reached through.
+    let mut visited: HashMap<(String, TraceDirection), Option<u64>> = HashMap::new();
+    let mut seen: HashSet<SData> = HashSet::new();
+    // Transfers of each transaction fetched so far, `None` if it failed, so that a transaction shared by
+    // several accounts is fetched, decoded and counted only once.
+    let mut processed: HashMap<String, Option<Vec<Followed>>> = HashMap::new();
+    let mut queue: VecDeque<Hop> = VecDeque::new();
+    let boundaries = SignatureBoundaries::new();
+
+    let directions = match trace.direction {
+        TraceDirection::Both => vec![TraceDirection::Forward, TraceDirection::Backward],
+        direction => vec![direction]
+    };
+    for direction in directions {
+        visited.insert((account.to_string(), direction), None);
+        queue.push_back(Hop { account: account.to_string(), depth: 0, direction, anchor: None });
+    }
+
+    'trace: while let Some(hop) = queue.pop_front() {
+        let Hop { account, depth, direction, anchor } = hop;
+        if let Some(limit) = tracker.exhausted(&result.stats, true) {
+            result.frontier.push(TraceFrontier { account, depth, direction, partial: false, limit });
+            break;
+        }
+        result.stats.accounts += 1;
+
+        let mut signatures = match Signatures::iter(&account, source, config) {
+            Ok(signatures) => signatures.with_boundaries(&boundaries),
+            Err(err) => {
+                result.failures.push(TraceFailure { account: account.clone(), signature: None, error: err.into() });
+                continue;
+            }
+        };
+        match (&anchor, direction) {
+            (Some(anchor), TraceDirection::Backward) => {
+                signatures = signatures.to_slot(anchor.slot).until_time(anchor.block_time.saturating_add(1));
+                if let Some(max_delay) = trace.max_delay {
+                    signatures = signatures.since_time(anchor.block_time.saturating_sub(max_delay.as_secs()).saturating_sub(1));
                 }
-                else {
-                    println!("Decoded branch:\n{:?}", decoded);
-                    let destination = &decoded.destination.clone();
-                    data.push(decoded);
-                    get_transfers_recursive(destination, &client, &config, breadth-1, data);
+            },
+            (Some(anchor), _) => {
+                signatures = signatures.from_slot(anchor.slot);
+                if let Some(max_delay) = trace.max_delay {
+                    signatures = signatures.until_time(anchor.block_time.saturating_add(max_delay.as_secs()).saturating_add(1));
                 }
+            },
+            (None, _) => {}
+        }
+
+        let mut partial = false;
+        let mut finished = false;
+        while !finished {
+            // Take the signatures of the next round, checking the budget before each new one as if they were
+            // fetched one at a time. Transactions already processed are reused without charging the budget.
+            let mut round = vec![];
+            let mut batch = vec![];
+            let mut exhausted = None;
+            while batch.len() < trace.parallelism.max(1) * trace.batch_size.max(1) {
+                let signature = match signatures.next() {
+                    Some(Ok(signature)) => signature,
+                    Some(Err(err)) => {
+                        result.failures.push(TraceFailure { account: account.clone(), signature: None, error: err.into() });
+                        finished = true;
+                        break;
+                    },
+                    None => {
+                        finished = true;
+                        break;
+                    }
+                };
+                if processed.contains_key(&signature.signature) {
+                    round.push(signature.signature);
+                } else {
+                    if let Some(limit) = tracker.exhausted(&result.stats, false) {
+                        exhausted = Some(limit);
+                        break;
+                    }
+                    result.stats.transactions += 1;
+                    round.push(signature.signature.clone());
+                    batch.push(signature);
+                }
+                partial = true;
+            }
+
+            for (signature, fetched) in batch.iter().zip(fetch_transactions(&batch, source, trace.batch_size)) {
+                let transfers = match fetched {
+                    Ok((transaction, instructions)) => Some(instructions.iter()
+                        .filter_map(|instruction| registry.decode(instruction, &transaction).and_then(|x| Followed::new(x, trace.tokens)))
+                        .collect()),
+                    Err(err) => {
+                        result.failures.push(TraceFailure { account: account.clone(), signature: Some(signature.signature.clone()), error: err.into() });
+                        None
+                    }
+                };
+                processed.insert(signature.signature.clone(), transfers);
+            }
+
+            for signature in &round {
+                let transfers = match processed.get(signature) {
+                    Some(Some(transfers)) => transfers,
+                    _ => continue
+                };
+                for decoded in transfers.iter().cloned() {
+                    // Backward hops only consider transfers into the account made before the funds left it,
+                    // forward hops in causal mode only transfers out of the account made after the funds arrived.
+                    let (next, next_anchor) = match direction {
+                        TraceDirection::Backward => {
+                            if !decoded.arrives(&account) || anchor.as_ref().is_some_and(|x| !x.is(&decoded, Ordering::Less)) {
+                                continue;
+                            }
+                            (decoded.source().to_string(), Some(Anchor::new(&decoded)))
+                        },
+                        _ => {
+                            if trace.causal && (!decoded.leaves(&account) || anchor.as_ref().is_some_and(|x| !x.is(&decoded, Ordering::Greater))) {
+                                continue;
+                            }
+                            (decoded.destination().to_string(), trace.causal.then(|| Anchor::new(&decoded)))
+                        }
+                    };
+
+                    if depth < trace.depth {
+                        let slot = next_anchor.as_ref().map(|x| x.slot);
+                        let expand = match (visited.get(&(next.clone(), direction)), slot) {
+                            (None, _) => true,
+                            (Some(Some(visited_slot)), Some(slot)) => match direction {
+                                TraceDirection::Backward => slot > *visited_slot,
+                                _ => slot < *visited_slot
+                            },
+                            _ => false
+                        };
+                        if expand {
+                            visited.insert((next.clone(), direction), slot);
+                            queue.push_back(Hop { account: next, depth: depth + 1, direction, anchor: next_anchor });
+                        }
+                    }
+                    if seen.insert(decoded.id()) {
+                        match decoded {
+                            Followed::Lamports(transfer) => result.transfers.push(transfer),
+                            Followed::Tokens(transfer) => result.token_transfers.push(transfer)
+                        }
+                    }
+                }
+            }
+
+            if let Some(limit) = exhausted {
+                result.frontier.push(TraceFrontier { account: account.clone(), depth, direction, partial, limit });
+                break 'trace;
             }
         }
     }
-    data.to_vec()
+
+    // Accounts still queued when a budget was exhausted are reported unexpanded.
+    if let Some(limit) = result.frontier.last().map(|x| x.limit) {
+        result.frontier.extend(queue.into_iter().map(|hop| TraceFrontier {
+            account: hop.account,
+            depth: hop.depth,
+            direction: hop.direction,
+            partial: false,
+            limit
+        }));
+    }
+    result.transfers.sort_by(|a, b| {
+        (a.slot, &a.signature, a.instruction_index, a.inner_index).cmp(&(b.slot, &b.signature, b.instruction_index, b.inner_index))
+    });
+    result.token_transfers.sort_by(|a, b| {
+        (a.slot, &a.signature, a.instruction_index, a.inner_index).cmp(&(b.slot, &b.signature, b.instruction_index, b.inner_index))
+    });
+    result.stats.rpc_calls = tracker.rpc_calls();
+    result.stats.elapsed = tracker.started.elapsed();
+    result
+}
+
+/// Fetch the transactions of `signatures` and their processed instructions in batches of `batch_size`,
+/// each on its own thread, returning the results in the order of `signatures`.
+fn fetch_transactions(signatures: &[SignatureDetail], source: &dyn ChainSource, batch_size: usize) -> Vec<Result<(TransactionDetail, Vec<InstructionProcessed>), TransactionDetailError>> {
+    let fetch = |batch: &[SignatureDetail]| -> Vec<_> {
+        TransactionDetail::from_signatures(batch, source).into_iter()
+            .map(|transaction| transaction.and_then(|transaction| transaction.get_instructions_processed().map(|x| (transaction, x))))
+            .collect()
+    };
+    if signatures.len() <= batch_size.max(1) {
+        return fetch(signatures);
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = signatures.chunks(batch_size.max(1)).map(|batch| scope.spawn(move || fetch(batch))).collect();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap_or_else(|err| panic::resume_unwind(err))).collect()
+    })
 }
 
 /// Display debug information to output.
@@ -42,27 +592,318 @@ pub fn get_transfers_recursive(account: &String, client: &RpcClient, config: &Si
 /// # Arguments
 /// 
 /// * `account` - Account as base-58 encoded string.
-/// * `client` - RCP client.
+/// * `source` - Chain data source, e.g. an RPC client.
 /// * `config` - Config for Signature query.
+/// * `registry` - Decoders used to decode the instructions of the transaction.
 /// * `signature_index` Index into the signatures array.
 /// ```
-pub fn display_debug(account: &String, client: &RpcClient, config: &SignatureConfig, signature_index: usize) {
+pub fn display_debug(account: &str, source: &dyn ChainSource, config: &SignatureConfig, registry: &DecoderRegistry, signature_index: usize) -> Result<(), Box<dyn Error>> {
 
     /* Get signatures for the specified configuration */
-    let signatures = Signatures::fetch(&account, &client, &config).unwrap();
+    let signatures = Signatures::fetch(account, source, config)?;
     for signature in &signatures {
         println!("{}", signature);
     }
 
     /* Get a transaction for a specified signature */
-    let transaction = TransactionDetail::new(&signatures[signature_index].signature, &client);
+    let signature = match signatures.get(signature_index) {
+        Some(signature) => signature,
+        None => return Err(format!("Signature index {} out of range", signature_index).into())
+    };
+    let transaction = TransactionDetail::new(&signature.signature, source)?;
     transaction.print();
 
     /* Decode supported instructions for transaction. */
-    let instructions = transaction.get_instructions_processed();
+    let instructions = transaction.get_instructions_processed()?;
     for instruction in instructions {
-        if let Some(decoded) = decode_instruction(instruction) {
+        if let Some(decoded) = registry.decode(&instruction, &transaction) {
             println!("Decoded\n{:?}", decoded);
         }
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base58::ToBase58;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+    use crate::source::fixture::FixtureSource;
+    use crate::constants::{SYSTEM_PROGRAM, TOKEN_PROGRAM};
+
+    /// Time at slot `0`, each slot lasts a second.
+    const GENESIS: u64 = 1_600_000_000;
+
+    /// Transactions of the fixture, each a system transfer as `(name, slot, source, destination)`:
+    /// `Q` funds `R`, which funds `X`, which funds `Y` before and after and `Z` after, which funds `W`.
+    const TRANSACTIONS: [(&str, u64, &str, &str); 6] = [
+        ("t5", 80, "Q", "R"),
+        ("t0", 90, "X", "Y"),
+        ("t1", 100, "R", "X"),
+        ("t2", 110, "X", "Z"),
+        ("t4", 120, "Z", "W"),
+        ("t3", 200, "X", "Y")
+    ];
+
+    fn account(name: &str) -> String {
+        Pubkey::new_from_array([name.as_bytes()[0]; 32]).to_string()
+    }
+
+    fn signature(name: &str) -> String {
+        Signature::new(&[name.as_bytes()[1]; 64]).to_string()
+    }
+
+    fn name(signature: &str) -> &'static str {
+        TRANSACTIONS.iter().find(|x| self::signature(x.0) == signature).unwrap().0
+    }
+
+    /// Fixture source holding `TRANSACTIONS`.
+    fn fixtures() -> FixtureSource {
+        let mut signatures: HashMap<String, Vec<serde_json::Value>> = HashMap::new();
+        let mut transactions = serde_json::Map::new();
+        for (name, slot, source, destination) in TRANSACTIONS {
+            let entry = serde_json::json!({"signature": signature(name), "slot": slot, "blockTime": GENESIS + slot});
+            signatures.entry(account(source)).or_default().push(entry.clone());
+            signatures.entry(account(destination)).or_default().push(entry);
+            let mut data = 2u32.to_le_bytes().to_vec();
+            data.extend_from_slice(&1_000u64.to_le_bytes());
+            transactions.insert(signature(name), serde_json::json!({
+                "slot": slot,
+                "blockTime": GENESIS + slot,
+                "transaction": {
+                    "signatures": [signature(name)],
+                    "message": {
+                        "header": {"numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 1},
+                        "accountKeys": [account(source), account(destination), SYSTEM_PROGRAM],
+                        "recentBlockhash": SYSTEM_PROGRAM,
+                        "instructions": [{"programIdIndex": 2, "accounts": [0, 1], "data": data.to_base58()}]
+                    }
+                },
+                "meta": {
+                    "err": null,
+                    "fee": 5000,
+                    "preBalances": [10_000, 0, 1],
+                    "postBalances": [4_000, 1_000, 1],
+                    "innerInstructions": [],
+                    "preTokenBalances": [],
+                    "postTokenBalances": []
+                }
+            }));
+        }
+        FixtureSource::from_json(&serde_json::json!({"signatures": signatures, "transactions": transactions}).to_string()).unwrap()
+    }
+
+    /// SPL Token transfers of the token fixture, as `(name, slot, owner, source, destination, destination owner)`:
+    /// `R` sends tokens from its token account `a` to `b`, owned by `S`, which sends them on to `c`, owned by `T`.
+    const TOKEN_TRANSACTIONS: [(&str, u64, &str, &str, &str, &str); 2] = [
+        ("k7", 100, "R", "a", "b", "S"),
+        ("k8", 110, "S", "b", "c", "T")
+    ];
+
+    /// Fixture source holding `TOKEN_TRANSACTIONS`.
+    fn token_fixtures() -> FixtureSource {
+        let mut signatures: HashMap<String, Vec<serde_json::Value>> = HashMap::new();
+        let mut transactions = serde_json::Map::new();
+        for (name, slot, owner, source, destination, destination_owner) in TOKEN_TRANSACTIONS {
+            let entry = serde_json::json!({"signature": signature(name), "slot": slot, "blockTime": GENESIS + slot});
+            for key in [owner, source, destination] {
+                signatures.entry(account(key)).or_default().push(entry.clone());
+            }
+            let mut data = vec![3];
+            data.extend_from_slice(&500u64.to_le_bytes());
+            let balance = |index: usize, owner: &str| serde_json::json!({
+                "accountIndex": index,
+                "mint": account("M"),
+                "owner": account(owner),
+                "uiTokenAmount": {"decimals": 0.0, "amount": "500", "uiAmountString": "500"}
+            });
+            transactions.insert(signature(name), serde_json::json!({
+                "slot": slot,
+                "blockTime": GENESIS + slot,
+                "transaction": {
+                    "signatures": [signature(name)],
+                    "message": {
+                        "header": {"numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 1},
+                        "accountKeys": [account(owner), account(source), account(destination), TOKEN_PROGRAM],
+                        "recentBlockhash": SYSTEM_PROGRAM,
+                        "instructions": [{"programIdIndex": 3, "accounts": [1, 2, 0], "data": data.to_base58()}]
+                    }
+                },
+                "meta": {
+                    "err": null,
+                    "fee": 5000,
+                    "preBalances": [10_000, 2_039_280, 2_039_280, 1],
+                    "postBalances": [5_000, 2_039_280, 2_039_280, 1],
+                    "innerInstructions": [],
+                    "preTokenBalances": [balance(1, owner), balance(2, destination_owner)],
+                    "postTokenBalances": [balance(1, owner), balance(2, destination_owner)]
+                }
+            }));
+        }
+        FixtureSource::from_json(&serde_json::json!({"signatures": signatures, "transactions": transactions}).to_string()).unwrap()
+    }
+
+    /// Signature query reading pages of two signatures, starting from `before`.
+    fn config(before: Option<Signature>) -> SignatureConfig {
+        SignatureConfig { time_before: None, time_after: None, before, until: None, limit: Some(2), max: None, commitment: None }
+    }
+
+    fn trace(account: &str, trace: TraceConfig) -> TraceResult {
+        trace_transfers(&self::account(account), &fixtures(), &config(None), &DecoderRegistry::default(), &trace)
+    }
+
+    /// Names of the transactions of the transfers found, sorted.
+    fn names(result: &TraceResult) -> Vec<&'static str> {
+        let mut names: Vec<_> = result.transfers.iter().map(|x| name(&x.signature)).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn trace_depth() {
+        let result = trace("R", TraceConfig { depth: 0, ..Default::default() });
+        assert_eq!(names(&result), ["t1", "t5"]);
+        assert_eq!(result.stats.accounts, 1);
+        assert!(result.failures.is_empty() && result.frontier.is_empty());
+
+        let result = trace("R", TraceConfig { depth: 1, ..Default::default() });
+        assert_eq!(names(&result), ["t0", "t1", "t2", "t3", "t5"]);
+        assert_eq!(result.stats.accounts, 2);
+
+        let result = trace("R", TraceConfig { depth: 2, ..Default::default() });
+        assert_eq!(names(&result), ["t0", "t1", "t2", "t3", "t4", "t5"]);
+    }
+
+    #[test]
+    fn deduplicate_transfers() {
+        // `t1` is found from both `R` and `X`, `t0` and `t3` from both `X` and `Y`.
+        let result = trace("R", TraceConfig { depth: 3, parallelism: 2, batch_size: 2, ..Default::default() });
+        assert_eq!(names(&result), ["t0", "t1", "t2", "t3", "t4", "t5"]);
+        let ids: HashSet<SData> = result.transfers.iter().map(|x| x.id()).collect();
+        assert_eq!(ids.len(), result.transfers.len());
+        assert_eq!(result.stats.transactions, 6);
+    }
+
+    #[test]
+    fn sort_transfers() {
+        let result = trace("Z", TraceConfig { depth: 3, direction: TraceDirection::Both, ..Default::default() });
+        let keys: Vec<_> = result.transfers.iter().map(|x| (x.slot, x.signature.clone(), x.instruction_index, x.inner_index)).collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert!(keys.len() > 1);
+        assert_eq!(keys, sorted);
+
+        let parallel = trace("Z", TraceConfig { depth: 3, direction: TraceDirection::Both, parallelism: 3, batch_size: 2, ..Default::default() });
+        assert_eq!(parallel.transfers, result.transfers);
+    }
+
+    #[test]
+    fn trace_backward() {
+        let result = trace("X", TraceConfig { depth: 2, direction: TraceDirection::Backward, ..Default::default() });
+        assert_eq!(names(&result), ["t1", "t5"]);
+        assert_eq!(result.stats.accounts, 3);
+
+        let result = trace("X", TraceConfig {
+            depth: 2,
+            direction: TraceDirection::Backward,
+            max_delay: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+        assert_eq!(names(&result), ["t1"]);
+    }
+
+    #[test]
+    fn trace_both_directions() {
+        let result = trace("Z", TraceConfig { depth: 1, direction: TraceDirection::Both, ..Default::default() });
+        // Forward to `W`, backward to `X` and the transfer that funded it before `t2`.
+        assert_eq!(names(&result), ["t1", "t2", "t4"]);
+        assert_eq!(result.stats.accounts, 4);
+    }
+
+    #[test]
+    fn trace_causal() {
+        let result = trace("R", TraceConfig { depth: 2, causal: true, ..Default::default() });
+        // `t0` left `X` before `t1` funded it, `Y` is reached through `t3` after which it sent nothing.
+        assert_eq!(names(&result), ["t1", "t2", "t3", "t4"]);
+
+        let result = trace("R", TraceConfig { depth: 2, causal: true, max_delay: Some(Duration::from_secs(50)), ..Default::default() });
+        assert_eq!(names(&result), ["t1", "t2", "t4"]);
+    }
+
+    #[test]
+    fn trace_budget() {
+        let result = trace("R", TraceConfig {
+            depth: 2,
+            budget: TraceBudget { max_accounts: Some(1), ..Default::default() },
+            ..Default::default()
+        });
+        assert_eq!(names(&result), ["t1", "t5"]);
+        let frontier: Vec<_> = result.frontier.iter().map(|x| (x.account.clone(), x.depth, x.partial, x.limit)).collect();
+        assert_eq!(frontier, [(account("X"), 1, false, TraceLimit::MaxAccounts)]);
+
+        let result = trace("R", TraceConfig {
+            depth: 2,
+            budget: TraceBudget { max_transactions: Some(1), ..Default::default() },
+            ..Default::default()
+        });
+        assert_eq!(names(&result), ["t1"]);
+        let frontier: Vec<_> = result.frontier.iter().map(|x| (x.account.clone(), x.depth, x.partial, x.limit)).collect();
+        assert_eq!(frontier, [
+            (account("R"), 0, true, TraceLimit::MaxTransactions),
+            (account("X"), 1, false, TraceLimit::MaxTransactions)
+        ]);
+
+        // `t1` is fetched from `R` and reused for `X` without counting against the budget again.
+        let result = trace("R", TraceConfig {
+            depth: 1,
+            budget: TraceBudget { max_transactions: Some(5), ..Default::default() },
+            ..Default::default()
+        });
+        assert_eq!(names(&result), ["t0", "t1", "t2", "t3", "t5"]);
+        assert_eq!(result.stats.transactions, 5);
+        assert!(result.frontier.is_empty());
+    }
+
+    #[test]
+    fn follow_token_transfers() {
+        let trace = |account: &str, trace: TraceConfig| {
+            let result = trace_transfers(&self::account(account), &token_fixtures(), &config(None), &DecoderRegistry::default(), &trace);
+            let signatures: Vec<_> = result.token_transfers.iter().map(|x| x.signature.clone()).collect();
+            (result, signatures)
+        };
+        let (result, signatures) = trace("R", TraceConfig { depth: 1, ..Default::default() });
+        assert!(result.transfers.is_empty() && signatures.is_empty());
+        assert_eq!(result.stats.accounts, 1);
+
+        // `R` owns the source token account of `k7`, which is followed to `b`.
+        let (result, signatures) = trace("R", TraceConfig { depth: 1, tokens: true, ..Default::default() });
+        assert_eq!(signatures, [signature("k7"), signature("k8")]);
+        assert_eq!(result.token_transfers[0].amount, Some(500));
+        assert_eq!(result.stats.accounts, 2);
+
+        let (_, signatures) = trace("c", TraceConfig { depth: 1, direction: TraceDirection::Backward, tokens: true, ..Default::default() });
+        assert_eq!(signatures, [signature("k7"), signature("k8")]);
+
+        let (_, signatures) = trace("S", TraceConfig { depth: 0, causal: true, tokens: true, ..Default::default() });
+        assert_eq!(signatures, [signature("k8")]);
+    }
+
+    #[test]
+    fn report_failures() {
+        let root = account("R");
+        let result = trace_transfers(&root, &fixtures(), &config(Some(Signature::new(&[0; 64]))), &DecoderRegistry::default(), &TraceConfig::default());
+        assert!(result.transfers.is_empty());
+        assert!(result.root_failure(&root).is_some());
+
+        // A transaction that cannot be fetched is skipped.
+        let missing = Signature::new(&[0; 64]).to_string();
+        let fixtures = FixtureSource::from_json(&serde_json::json!({
+            "signatures": {&root: [{"signature": &missing, "slot": 150, "blockTime": GENESIS + 150}]}
+        }).to_string()).unwrap();
+        let result = trace_transfers(&root, &fixtures, &config(None), &DecoderRegistry::default(), &TraceConfig::default());
+        assert!(result.transfers.is_empty() && result.root_failure(&root).is_none());
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].signature, Some(missing));
+    }
+}
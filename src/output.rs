@@ -0,0 +1,189 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::constants::{SOL_DECIMALS, SYSTEM_PROGRAM};
+use crate::graph::taint::AccountTaint;
+use crate::parsing::amount::format_amount;
+use crate::signature::signature::SignatureDetail;
+use crate::transaction::decoder::DecodedInstruction;
+use crate::transaction::instructions::{InstructionTokenTransferWrapper, InstructionTransferWrapper};
+
+/// Output format of the command-line interface.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Table
+}
+
+/// Record that can be written as a row of a CSV file or table.
+pub trait Tabular {
+    /// Column names.
+    fn headers() -> Vec<&'static str>;
+    /// Column values, in the same order as `headers`.
+    fn row(&self) -> Vec<String>;
+}
+
+/// Print records to output in the specified format.
+///
+/// # Arguments
+///
+/// * `records` - Records to print.
+/// * `format` - Output format.
+pub fn print_records<T: Serialize + Tabular>(records: &[T], format: OutputFormat) -> Result<(), serde_json::Error> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(records)?),
+        OutputFormat::Csv => {
+            println!("{}", csv_line(&T::headers().iter().map(|x| x.to_string()).collect::<Vec<_>>()));
+            for record in records {
+                println!("{}", csv_line(&record.row()));
+            }
+        },
+        OutputFormat::Table => {
+            let headers: Vec<String> = T::headers().iter().map(|x| x.to_string()).collect();
+            let rows: Vec<Vec<String>> = records.iter().map(|x| x.row()).collect();
+            let mut widths: Vec<usize> = headers.iter().map(|x| x.len()).collect();
+            for row in &rows {
+                for (idx, value) in row.iter().enumerate() {
+                    widths[idx] = widths[idx].max(value.chars().count());
+                }
+            }
+            println!("{}", table_line(&headers, &widths));
+            println!("{}", widths.iter().map(|x| "-".repeat(*x)).collect::<Vec<_>>().join("-+-"));
+            for row in &rows {
+                println!("{}", table_line(row, &widths));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Format values as a CSV line, quoting values where needed.
+fn csv_line(values: &[String]) -> String {
+    values.iter()
+        .map(|x| match x.contains([',', '"', '\n']) {
+            true => format!("\"{}\"", x.replace('"', "\"\"")),
+            false => x.clone()
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Format values as a table line, padding each value to the column width.
+fn table_line(values: &[String], widths: &[usize]) -> String {
+    values.iter()
+        .zip(widths)
+        .map(|(value, width)| format!("{:width$}", value, width = width))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Format the position of an instruction within its transaction, e.g. `2` or `2.1` for inner instructions.
+fn instruction_position(instruction_index: usize, inner_index: Option<usize>) -> String {
+    match inner_index {
+        Some(inner_index) => format!("{}.{}", instruction_index, inner_index),
+        None => instruction_index.to_string()
+    }
+}
+
+impl Tabular for InstructionTransferWrapper {
+    fn headers() -> Vec<&'static str> {
+        vec!["signature", "instruction", "time", "action", "source", "destination", "lamports", "amount", "program"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.signature.clone(),
+            instruction_position(self.instruction_index, self.inner_index),
+            self.human_time.clone(),
+            self.action.clone(),
+            self.source.clone(),
+            self.destination.clone(),
+            self.lamports.to_string(),
+            self.sol_amount(),
+            self.program.clone()
+        ]
+    }
+}
+
+impl Tabular for InstructionTokenTransferWrapper {
+    fn headers() -> Vec<&'static str> {
+        vec!["signature", "instruction", "time", "action", "source", "destination", "mint", "amount", "program"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.signature.clone(),
+            instruction_position(self.instruction_index, self.inner_index),
+            self.human_time.clone(),
+            self.action.clone(),
+            self.source.clone(),
+            self.destination.clone(),
+            self.mint.clone().unwrap_or_default(),
+            self.ui_amount().or_else(|| self.amount.map(|amount| amount.to_string())).unwrap_or_default(),
+            self.program.clone()
+        ]
+    }
+}
+
+impl Tabular for SignatureDetail {
+    fn headers() -> Vec<&'static str> {
+        vec!["signature", "slot", "block_time", "time"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.signature.clone(),
+            self.slot.to_string(),
+            self.block_time.map(|x| x.to_string()).unwrap_or_default(),
+            self.block_time_human.clone().unwrap_or_default()
+        ]
+    }
+}
+
+impl Tabular for DecodedInstruction {
+    fn headers() -> Vec<&'static str> {
+        vec!["program", "action", "source", "destination", "amount", "details"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        match self {
+            DecodedInstruction::Transfer(x) => vec![
+                x.program.clone(), x.action.clone(), x.source.clone(), x.destination.clone(), x.sol_amount(), String::new()
+            ],
+            DecodedInstruction::TokenTransfer(x) => vec![
+                x.program.clone(),
+                x.action.clone(),
+                x.source.clone(),
+                x.destination.clone(),
+                x.ui_amount()
+                    .or_else(|| x.amount.map(|amount| amount.to_string()))
+                    .or_else(|| x.lamports.map(|lamports| format!("{} SOL", format_amount(lamports, SOL_DECIMALS))))
+                    .unwrap_or_default(),
+                x.mint.clone().map(|x| format!("mint={}", x)).unwrap_or_default()
+            ],
+            DecodedInstruction::System(x) => vec![
+                SYSTEM_PROGRAM.to_string(), x.to_string(), String::new(), String::new(), String::new(), format!("{:?}", x)
+            ],
+            DecodedInstruction::Program(x) => vec![
+                x.program.clone(), x.action.clone(), String::new(), String::new(), String::new(), x.args.to_string()
+            ]
+        }
+    }
+}
+
+impl Tabular for AccountTaint {
+    fn headers() -> Vec<&'static str> {
+        vec!["account", "received", "sent", "held", "held_sol"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.account.clone(),
+            self.received.to_string(),
+            self.sent.to_string(),
+            self.held.to_string(),
+            format_amount(self.held, SOL_DECIMALS)
+        ]
+    }
+}
\ No newline at end of file
@@ -1 +1,2 @@
+pub mod amount;
 pub mod time;
\ No newline at end of file
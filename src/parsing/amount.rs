@@ -0,0 +1,47 @@
+use pyo3::prelude::*;
+
+/**
+ * Format an amount given in base units as an exact decimal string.
+ * E.g. 1500000000 lamports with 9 decimals is formatted as "1.5".
+ */
+pub fn format_amount(raw: u64, decimals: u8) -> String {
+    let raw = raw.to_string();
+    let decimals = usize::from(decimals);
+    if decimals == 0 {
+        return raw;
+    }
+
+    let padded = format!("{:0>width$}", raw, width = decimals + 1);
+    let (integer, fraction) = padded.split_at(padded.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        integer.to_string()
+    } else {
+        format!("{}.{}", integer, fraction)
+    }
+}
+
+/**
+ * Convert an exact decimal string to a python `decimal.Decimal`.
+ */
+pub fn to_py_decimal(py: Python, value: &str) -> PyResult<PyObject> {
+    let decimal = py.import("decimal")?.getattr("Decimal")?;
+    Ok(decimal.call1((value,))?.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_exact_amounts() {
+        assert_eq!(format_amount(1_500_000_000, 9), "1.5");
+        assert_eq!(format_amount(1_000_000_000, 9), "1");
+        assert_eq!(format_amount(1, 9), "0.000000001");
+        assert_eq!(format_amount(0, 9), "0");
+        assert_eq!(format_amount(123_456, 2), "1234.56");
+        assert_eq!(format_amount(42, 0), "42");
+        assert_eq!(format_amount(u64::MAX, 9), "18446744073.709551615");
+        assert_eq!(format_amount(5, 20), "0.00000000000000000005");
+    }
+}
@@ -1,22 +1,30 @@
 use chrono::{TimeZone, Utc};
+use crate::errors::TimeError;
 
 /**
  * Convert time string to UNIX timestamp.
  * String format: "2012-06-28 12:29:00"
  */
-pub fn convert_time_to_unix(time: String) -> u64 {
-    let time = Utc.datetime_from_str(&time, "%Y-%m-%d %H:%M:%S").unwrap();
-    let time = u64::try_from(time.timestamp()).unwrap();
-    time
+pub fn convert_time_to_unix(time: String) -> Result<u64, TimeError> {
+    let parsed = match Utc.datetime_from_str(&time, "%Y-%m-%d %H:%M:%S") {
+        Ok(parsed) => parsed,
+        Err(err) => return Err(TimeError::ParseError(format!("{}: {}", time, err)))
+    };
+    match u64::try_from(parsed.timestamp()) {
+        Ok(timestamp) => Ok(timestamp),
+        Err(err) => Err(TimeError::RangeError(format!("{}: {}", time, err)))
+    }
 }
 
 /**
  * Convert UNIX timestamp to time string.
+ * Timestamps outside the supported range are returned as is.
  */
 pub fn convert_unix_to_time(time: u64) -> String {
-    let time = i64::try_from(time).unwrap();
-    let time = Utc.timestamp(time, 0);
-    time.format("%Y-%m-%d %H:%M:%S").to_string()
+    match i64::try_from(time).ok().and_then(|x| Utc.timestamp_opt(x, 0).single()) {
+        Some(parsed) => parsed.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => time.to_string()
+    }
 }
 
 pub struct TimeConfig {
@@ -1,14 +1,28 @@
+use std::collections::{HashMap, VecDeque};
 use std::str::FromStr;
+use std::sync::Mutex;
 use core::fmt;
+use serde::Serialize;
 use solana_client::rpc_client::{RpcClient, GetConfirmedSignaturesForAddress2Config};
+use solana_client::rpc_config::RpcBlockConfig;
+use solana_client::rpc_request::MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT;
 use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
-use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
+use solana_transaction_status::TransactionDetails;
+use crate::errors::SolanaSignatureError;
 use crate::parsing::time::{convert_time_to_unix, convert_unix_to_time};
+use crate::source::source::ChainSource;
+
+/// Maximum number of search steps of `Signatures::locate_before`, each sending two requests.
+pub const MAX_LOCATE_STEPS: usize = 12;
+/// Number of blocks searched for a transaction once `Signatures::locate_before` found the boundary slot.
+const LOCATE_BLOCKS: u64 = 5;
 
 /// Wrapper for transaction signature information, `RpcConfirmedTransactionStatusWithSignature`.
 /// Contains `signature`, `slot`, `block_time` and `block_time_human` fields.
+#[derive(Serialize)]
 pub struct SignatureDetail {
     /// Transaction signature as a base-58 encoded string.
     pub signature: String,
@@ -28,7 +42,7 @@ impl fmt::Display for SignatureDetail {
 }
 
 /// Wrapper for `GetConfirmedSignaturesForAddress2Config`.
-/// Contains `time_before`, `time_after`, `before`, `until`, `limit` and `commitment` fields.
+/// Contains `time_before`, `time_after`, `before`, `until`, `limit`, `max` and `commitment` fields.
 pub struct SignatureConfig {
     /// Same as `before`, except instead of a `Signature`, uses an explicity formatted time string, i.e.
     /// `Y-M-D H:M:S`.
@@ -41,8 +55,11 @@ pub struct SignatureConfig {
     pub before: Option<Signature>,
     /// Search until this transaction signature, if found before limit reached.
     pub until: Option<Signature>,
-    /// Maximum transaction signatures to return (between 1 - 1000).
-    pub limit: Option<usize>, 
+    /// Maximum transaction signatures to request per page (between 1 - 1000).
+    pub limit: Option<usize>,
+    /// Maximum transaction signatures to return across all pages. If not provided, paging
+    /// continues until `time_before`, `until` or the oldest transaction of the account is reached.
+    pub max: Option<usize>,
     /// Commitment describes how finalized a block is at a point in time, can be
     /// `finalized`, `confirmed` or `processed`.
     pub commitment: Option<CommitmentConfig>,
@@ -56,75 +73,118 @@ impl Signatures {
     /// # Arguments
     /// 
     /// * `account` - The account to fetch the transaction signatures for.
-    /// * `client` - RPC client.
-    /// * `number` - Amount of transaction signatures to fetch.
-    pub fn fetch(account: &String, client: &RpcClient, config: &SignatureConfig) -> Option<Vec<SignatureDetail>> {
+    /// * `source` - Chain data source, e.g. an RPC client.
+    /// * `config` - Config for signature query.
+    pub fn fetch(account: &str, source: &dyn ChainSource, config: &SignatureConfig) -> Result<Vec<SignatureDetail>, SolanaSignatureError> {
+        Self::iter(account, source, config)?.collect()
+    }
 
-        let pubkey = Pubkey::from_str(&account).expect("Failed to parse account address");
-        let conf = GetConfirmedSignaturesForAddress2Config {
-            before: config.before,
-            until: config.until,
-            limit: config.limit,
-            commitment: config.commitment
+    /// Return a lazy iterator over the transaction signatures for a given account, newest first.
+    /// Pages are requested from the source as the iterator is consumed.
+    /// # Arguments
+    /// 
+    /// * `account` - The account to fetch the transaction signatures for.
+    /// * `source` - Chain data source, e.g. an RPC client.
+    /// * `config` - Config for signature query.
+    pub fn iter<'a>(account: &str, source: &'a dyn ChainSource, config: &'a SignatureConfig) -> Result<SignatureIterator<'a>, SolanaSignatureError> {
+        let pubkey = match Pubkey::from_str(account) {
+            Ok(pubkey) => pubkey,
+            Err(err) => return Err(SolanaSignatureError::SignatureParsePubkeyError(format!("{}: {}", account, err)))
         };
-
-        let signatures = client.get_signatures_for_address_with_config(&pubkey, conf).unwrap();
-        let signatures = Self::get_processed_signatures(signatures);
-
-        let time_before = config.time_before.clone();
-        let time_after = config.time_after.clone();
-        let filter_by_time = time_before.or(time_after).is_some();
-
-        if filter_by_time {
-            Self::filter(signatures, config)
-        }
-        else {
-            Some(signatures)
-        }
+        let parse_time = |time: &Option<String>| match time.clone().map(convert_time_to_unix) {
+            Some(Ok(time)) => Ok(Some(time)),
+            Some(Err(err)) => Err(SolanaSignatureError::TimeError(err.to_string())),
+            None => Ok(None)
+        };
+        Ok(SignatureIterator {
+            source,
+            pubkey,
+            config,
+            time_start: parse_time(&config.time_before)?,
+            time_end: parse_time(&config.time_after)?,
+            slot_start: None,
+            slot_end: None,
+            before: config.before,
+            located: config.before.is_some(),
+            boundaries: None,
+            page: VecDeque::new(),
+            returned: 0,
+            finished: false
+        })
     }
 
-    /// Filter the specified signatures according to the specified configuration.
-    fn filter(signatures: Vec<SignatureDetail>, config: &SignatureConfig) -> Option<Vec<SignatureDetail>> {
+    /// Locate the signature to start searching backwards from, so that the search skips every
+    /// transaction processed at or after `time`. Searches the produced slots by block time and returns
+    /// the first signature of the first block produced at or after `time`. The search stops after
+    /// `MAX_LOCATE_STEPS` steps, returning a later block, so at most `6 + 2 * MAX_LOCATE_STEPS + 1 +
+    /// LOCATE_BLOCKS` requests are sent. Returns `None` if `time` is after the latest block or the
+    /// boundary could not be located.
+    /// # Arguments
+    /// 
+    /// * `client` - RPC client.
+    /// * `time` - UNIX timestamp.
+    pub fn locate_before(client: &RpcClient, time: u64) -> Option<Signature> {
+        let time = i64::try_from(time).ok()?;
 
-        let signatures = signatures.into_iter().filter(|x| {
-            let block_time = u64::try_from(x.block_time.unwrap()).unwrap();
-            if let Some(time_start) = &config.time_before {
-                let time_start = convert_time_to_unix(time_start.to_string());
-                if let Some(time_end) = &config.time_after {
-                    let time_end = convert_time_to_unix(time_end.to_string());
-                    if (block_time < time_end) && (block_time > time_start) {
-                        return true;
-                    }
-                    else {
-                        return false;
-                    }
-                }
-                else {
-                    if block_time > time_start {
-                        return true;
-                    }
-                    else {
-                        return false;
-                    }
+        // Invariant: blocks produced at or after `hi` were produced at or after `time`,
+        // the block produced at or after `lo` was produced before `time`.
+        let mut hi = client.get_slot().ok()?;
+        let mut hi_time = Self::produced_block_time(client, hi)?.1;
+        if hi_time < time {
+            return None;
+        }
+        let mut lo = client.get_first_available_block().ok()?;
+        let mut lo_time = Self::produced_block_time(client, lo)?.1;
+        if lo_time < time {
+            for step in 0..MAX_LOCATE_STEPS {
+                if lo + 1 >= hi {
+                    break;
                 }
-            }
-            else {
-                if let Some(time_end) = &config.time_after {
-                    let time_end = convert_time_to_unix(time_end.to_string());
-                    if block_time < time_end {
-                        return true;
-                    }
-                    else {
-                        return false;
-                    }
+                // Slots are produced at a near constant rate, so the slot is interpolated from the block
+                // times of the bounds. Every other step bisects, in case the rate changed.
+                let offset = match step % 2 {
+                    0 => ((hi - lo) as u128 * (time - lo_time) as u128 / (hi_time - lo_time) as u128) as u64,
+                    _ => (hi - lo) / 2
+                };
+                let mid = (lo + offset).clamp(lo + 1, hi - 1);
+                let (slot, block_time) = Self::produced_block_time(client, mid)?;
+                if slot >= hi || block_time >= time {
+                    hi = mid;
+                    hi_time = hi_time.min(block_time);
                 }
                 else {
-                    return false;
+                    lo = slot.max(mid);
+                    lo_time = block_time;
                 }
             }
-        });
-        let signatures: Vec<SignatureDetail> = signatures.collect();
-        Some(signatures)
+        }
+        else {
+            hi = lo;
+        }
+
+        // Skip blocks without transactions.
+        let slots = client.get_blocks_with_limit(hi, LOCATE_BLOCKS as usize).ok()?;
+        for slot in slots {
+            let config = RpcBlockConfig {
+                encoding: None,
+                transaction_details: Some(TransactionDetails::Signatures),
+                rewards: Some(false),
+                commitment: None,
+                max_supported_transaction_version: None
+            };
+            let block = client.get_block_with_config(slot, config).ok()?;
+            if let Some(signature) = block.signatures.as_ref().and_then(|x| x.first()) {
+                return Signature::from_str(signature).ok();
+            }
+        }
+        None
+    }
+
+    /// Return the first produced slot at or after `slot` together with its block time.
+    fn produced_block_time(client: &RpcClient, slot: u64) -> Option<(u64, i64)> {
+        let produced = *client.get_blocks_with_limit(slot, 1).ok()?.first()?;
+        let block_time = client.get_block_time(produced).ok()?;
+        Some((produced, block_time))
     }
 
     /// Returns an array of processed transaction signatures, as `SignatureDetail` objects.
@@ -135,10 +195,308 @@ impl Signatures {
                 signature: signature.signature,
                 slot: signature.slot,
                 block_time: signature.block_time,
-                block_time_human: Some(convert_unix_to_time(u64::try_from(signature.block_time.unwrap()).unwrap()))
+                block_time_human: signature.block_time.and_then(|x| u64::try_from(x).ok()).map(convert_unix_to_time)
             };
             processed.push(data);
         }
         processed
     }
-}
\ No newline at end of file
+}
+
+/// Signatures to start paging backwards from, located by `ChainSource::locate_before` per time and
+/// commitment, so the iterators of a trace sharing a time window locate its end once.
+#[derive(Default)]
+pub struct SignatureBoundaries {
+    located: Mutex<HashMap<(u64, CommitmentLevel), Option<Signature>>>
+}
+
+impl SignatureBoundaries {
+    /// Returns an empty `SignatureBoundaries`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the signature to start paging backwards from so that transactions processed at or after
+    /// `time` are skipped, locating it with `source` the first time.
+    pub fn locate(&self, source: &dyn ChainSource, time: u64) -> Option<Signature> {
+        let key = (time, source.commitment().commitment);
+        if let Some(located) = self.located.lock().unwrap().get(&key) {
+            return *located;
+        }
+        let located = source.locate_before(time);
+        self.located.lock().unwrap().insert(key, located);
+        located
+    }
+}
+
+/// Lazy iterator over the transaction signatures of an account, see `Signatures::iter`.
+/// Pages backwards through the account history, using the last signature of each page as
+/// `before` for the next request, until `time_before`, `until`, `max` or the oldest transaction
+/// of the account is reached. If `time_after` is set, `before` is not and the first page is
+/// entirely newer than the end of the time window, paging restarts at the end of the time window,
+/// see `Signatures::locate_before`. Iteration stops after the first error.
+pub struct SignatureIterator<'a> {
+    source: &'a dyn ChainSource,
+    pubkey: Pubkey,
+    config: &'a SignatureConfig,
+    /// Lower bound of the time window as UNIX timestamp, taken from `time_before`.
+    time_start: Option<u64>,
+    /// Upper bound of the time window as UNIX timestamp, taken from `time_after`.
+    time_end: Option<u64>,
+    /// Oldest slot to return signatures for, see `SignatureIterator::from_slot`.
+    slot_start: Option<u64>,
+    /// Newest slot to return signatures for, see `SignatureIterator::to_slot`.
+    slot_end: Option<u64>,
+    /// Signature to start the next page from.
+    before: Option<Signature>,
+    /// Whether `before` has been set, either by the caller or by locating the end of the time window.
+    located: bool,
+    /// Boundaries shared with other iterators, see `SignatureIterator::with_boundaries`.
+    boundaries: Option<&'a SignatureBoundaries>,
+    /// Signatures of the current page that have not been returned yet.
+    page: VecDeque<SignatureDetail>,
+    /// Number of signatures returned so far.
+    returned: usize,
+    /// Whether the last page has been requested.
+    finished: bool
+}
+
+impl<'a> SignatureIterator<'a> {
+    /// Only return signatures processed in or after `slot`.
+    pub fn from_slot(mut self, slot: u64) -> Self {
+        self.slot_start = Some(self.slot_start.map_or(slot, |x| x.max(slot)));
+        self
+    }
+
+    /// Only return signatures processed in or before `slot`.
+    pub fn to_slot(mut self, slot: u64) -> Self {
+        self.slot_end = Some(self.slot_end.map_or(slot, |x| x.min(slot)));
+        self
+    }
+
+    /// Narrow the lower bound of the time window, stopping at signatures processed at or before `time_start`.
+    pub fn since_time(mut self, time_start: u64) -> Self {
+        self.time_start = Some(self.time_start.map_or(time_start, |x| x.max(time_start)));
+        self
+    }
+
+    /// Narrow the upper bound of the time window, skipping signatures processed at or after `time_end`.
+    /// Paging starts at the narrowed bound if this is called before the iterator is consumed.
+    pub fn until_time(mut self, time_end: u64) -> Self {
+        self.time_end = Some(self.time_end.map_or(time_end, |x| x.min(time_end)));
+        self
+    }
+
+    /// Locate the end of the time window with `boundaries`, shared with other iterators, instead of on
+    /// its own.
+    pub fn with_boundaries(mut self, boundaries: &'a SignatureBoundaries) -> Self {
+        self.boundaries = Some(boundaries);
+        self
+    }
+
+    /// Request the next page of signatures. If the first page is entirely newer than the end of the time
+    /// window, it is dropped and paging restarts at the end of the time window, if it can be located.
+    fn fetch_page(&mut self) -> Result<(), SolanaSignatureError> {
+        let limit = self.config.limit.unwrap_or(MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT);
+        let conf = GetConfirmedSignaturesForAddress2Config {
+            before: self.before,
+            until: self.config.until,
+            limit: Some(limit),
+            commitment: self.config.commitment
+        };
+
+        let signatures = self.source.get_signatures(&self.pubkey, conf)?;
+        if !self.located {
+            self.located = true;
+            // Locating costs more requests than a page, so it is only worth it when pages would be skipped.
+            let skipped = signatures.len() >= limit && signatures.last()
+                .and_then(|x| x.block_time)
+                .zip(self.time_end)
+                .is_some_and(|(block_time, time_end)| block_time >= time_end as i64);
+            let located = match (skipped, self.boundaries) {
+                (false, _) => None,
+                (true, Some(boundaries)) => boundaries.locate(self.source, self.time_end.unwrap_or_default()),
+                (true, None) => self.source.locate_before(self.time_end.unwrap_or_default())
+            };
+            if located.is_some() {
+                self.before = located;
+                return Ok(());
+            }
+        }
+        if signatures.len() < limit {
+            self.finished = true;
+        }
+        match signatures.last() {
+            Some(last) => match Signature::from_str(&last.signature) {
+                Ok(signature) => self.before = Some(signature),
+                Err(err) => return Err(SolanaSignatureError::ParseSignatureError(format!("{}: {}", last.signature, err)))
+            },
+            None => self.finished = true
+        }
+        self.page.extend(Signatures::get_processed_signatures(signatures));
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for SignatureIterator<'a> {
+    type Item = Result<SignatureDetail, SolanaSignatureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let filter_by_time = self.time_start.or(self.time_end).is_some();
+        loop {
+            if let Some(max) = self.config.max {
+                if self.returned >= max {
+                    return None;
+                }
+            }
+
+            let signature = match self.page.pop_front() {
+                Some(signature) => signature,
+                None if self.finished => return None,
+                None => {
+                    if let Err(err) = self.fetch_page() {
+                        self.finished = true;
+                        return Some(Err(err));
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(slot_start) = self.slot_start {
+                // Signatures are ordered newest first, everything that follows is older.
+                if signature.slot < slot_start {
+                    self.page.clear();
+                    self.finished = true;
+                    return None;
+                }
+            }
+            if let Some(slot_end) = self.slot_end {
+                if signature.slot > slot_end {
+                    continue;
+                }
+            }
+
+            let block_time = match signature.block_time.map(u64::try_from) {
+                Some(Ok(block_time)) => block_time,
+                _ if filter_by_time => continue,
+                _ => {
+                    self.returned += 1;
+                    return Some(Ok(signature));
+                }
+            };
+            if let Some(time_start) = self.time_start {
+                // Signatures are ordered newest first, everything that follows is older.
+                if block_time <= time_start {
+                    self.page.clear();
+                    self.finished = true;
+                    return None;
+                }
+            }
+            if let Some(time_end) = self.time_end {
+                if block_time >= time_end {
+                    continue;
+                }
+            }
+            self.returned += 1;
+            return Some(Ok(signature));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use solana_sdk::account::Account;
+    use crate::errors::SourceError;
+    use crate::source::fixture::FixtureSource;
+
+    const ACCOUNT: &str = "AS5CnoZ7ezMhUXphn32H5Y2YAXnChdZAjjwywYYXuhgP";
+    const TIME: i64 = 1_600_000_000;
+
+    /// Fixture source whose account has 10 signatures, one every 10 seconds, counting the boundaries located.
+    struct LocatingSource {
+        fixtures: FixtureSource,
+        signatures: Vec<(Signature, i64)>,
+        located: AtomicUsize
+    }
+
+    impl LocatingSource {
+        fn new() -> Self {
+            let signatures: Vec<(Signature, i64)> = (0..10u8).rev()
+                .map(|i| (Signature::new(&[i + 1; 64]), TIME + i64::from(i) * 10))
+                .collect();
+            let entries: Vec<_> = signatures.iter().enumerate()
+                .map(|(i, (signature, time))| serde_json::json!({"signature": signature.to_string(), "slot": 100 - i, "blockTime": time}))
+                .collect();
+            let fixtures = FixtureSource::from_json(&serde_json::json!({"signatures": {ACCOUNT: entries}}).to_string()).unwrap();
+            LocatingSource { fixtures, signatures, located: AtomicUsize::new(0) }
+        }
+
+        /// Block times of the signatures returned by `signatures`.
+        fn times(&self, signatures: Vec<Result<SignatureDetail, SolanaSignatureError>>) -> Vec<i64> {
+            signatures.into_iter().map(|x| x.unwrap().block_time.unwrap()).collect()
+        }
+    }
+
+    impl ChainSource for LocatingSource {
+        fn get_signatures(&self, account: &Pubkey, config: GetConfirmedSignaturesForAddress2Config) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, SourceError> {
+            self.fixtures.get_signatures(account, config)
+        }
+
+        fn get_transaction(&self, signature: &Signature) -> Result<String, SourceError> {
+            self.fixtures.get_transaction(signature)
+        }
+
+        fn get_balance(&self, account: &Pubkey) -> Result<u64, SourceError> {
+            self.fixtures.get_balance(account)
+        }
+
+        fn get_account(&self, account: &Pubkey) -> Result<Option<Account>, SourceError> {
+            self.fixtures.get_account(account)
+        }
+
+        fn commitment(&self) -> CommitmentConfig {
+            self.fixtures.commitment()
+        }
+
+        fn locate_before(&self, time: u64) -> Option<Signature> {
+            self.located.fetch_add(1, Ordering::Relaxed);
+            self.signatures.iter().rfind(|x| x.1 >= time as i64).map(|x| x.0)
+        }
+    }
+
+    fn config(limit: usize) -> SignatureConfig {
+        SignatureConfig { time_before: None, time_after: None, before: None, until: None, limit: Some(limit), max: None, commitment: None }
+    }
+
+    #[test]
+    fn skip_locating_when_first_page_reaches_window() {
+        let source = LocatingSource::new();
+        let config = config(3);
+        let signatures = Signatures::iter(ACCOUNT, &source, &config).unwrap().until_time(TIME as u64 + 75);
+        assert_eq!(source.times(signatures.collect()), vec![TIME + 70, TIME + 60, TIME + 50, TIME + 40, TIME + 30, TIME + 20, TIME + 10, TIME]);
+        assert_eq!(source.located.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn locate_when_first_page_is_newer_than_window() {
+        let source = LocatingSource::new();
+        let config = config(3);
+        let signatures = Signatures::iter(ACCOUNT, &source, &config).unwrap().until_time(TIME as u64 + 25).since_time(TIME as u64);
+        assert_eq!(source.times(signatures.collect()), vec![TIME + 20, TIME + 10]);
+        assert_eq!(source.located.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn share_located_boundaries() {
+        let source = LocatingSource::new();
+        let config = config(2);
+        let boundaries = SignatureBoundaries::new();
+        for _ in 0..3 {
+            let signatures = Signatures::iter(ACCOUNT, &source, &config).unwrap().with_boundaries(&boundaries).until_time(TIME as u64 + 15);
+            assert_eq!(source.times(signatures.collect()), vec![TIME + 10, TIME]);
+        }
+        assert_eq!(source.located.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod cache;
+pub mod fixture;
+pub mod live;
+#[allow(clippy::module_inception)]
+pub mod source;
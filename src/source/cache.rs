@@ -0,0 +1,162 @@
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use crate::cache::TransactionCache;
+use crate::errors::SourceError;
+use super::source::ChainSource;
+
+/// Chain data read from another source, with transactions and pages of signatures read from and stored in a
+/// `TransactionCache`. Both are only stored if `source` reads finalized data, as they can no longer change.
+/// Pages starting from the newest signature still grow, they are stored for offline mode but always read
+/// from `source` otherwise. In offline mode transactions and pages missing from the cache fail with
+/// `SourceError::CacheMiss`, all other data is read from `source`.
+pub struct CachedSource<'a> {
+    cache: &'a TransactionCache,
+    source: &'a dyn ChainSource
+}
+
+impl<'a> CachedSource<'a> {
+    /// Returns a new `CachedSource`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - Transaction cache.
+    /// * `source` - Source the cache is filled from.
+    pub fn new(cache: &'a TransactionCache, source: &'a dyn ChainSource) -> Self {
+        CachedSource { cache, source }
+    }
+
+    /// Return the transaction cache.
+    pub fn cache(&self) -> &TransactionCache {
+        self.cache
+    }
+}
+
+impl<'a> ChainSource for CachedSource<'a> {
+    fn get_signatures(&self, account: &Pubkey, config: GetConfirmedSignaturesForAddress2Config) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, SourceError> {
+        let key = signatures_key(account, &config);
+        let finalized = config.commitment.unwrap_or_else(|| self.source.commitment()).is_finalized();
+        if self.cache.offline() || (finalized && config.before.is_some()) {
+            if let Some(page) = self.cache.get(&key).and_then(|x| serde_json::from_str(&x).ok()) {
+                return Ok(page);
+            }
+            if self.cache.offline() {
+                return Err(SourceError::CacheMiss(key));
+            }
+        }
+        let page = self.source.get_signatures(account, config)?;
+        if finalized {
+            if let Ok(serialized) = serde_json::to_string(&page) {
+                self.cache.put(&key, &serialized);
+            }
+        }
+        Ok(page)
+    }
+
+    fn get_transaction(&self, signature: &Signature) -> Result<String, SourceError> {
+        match self.get_transactions(&[*signature]).pop() {
+            Some(result) => result,
+            None => Err(SourceError::NotFound(format!("Transaction {}", signature)))
+        }
+    }
+
+    /// Transactions missing from the cache are read from `source` together.
+    fn get_transactions(&self, signatures: &[Signature]) -> Vec<Result<String, SourceError>> {
+        let cached: Vec<Option<String>> = signatures.iter().map(|x| self.cache.get(&x.to_string())).collect();
+        let missing: Vec<Signature> = signatures.iter().zip(&cached)
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(signature, _)| *signature)
+            .collect();
+        let fetched = if self.cache.offline() {
+            missing.iter().map(|x| Err(SourceError::CacheMiss(x.to_string()))).collect()
+        } else if missing.is_empty() {
+            vec![]
+        } else {
+            self.source.get_transactions(&missing)
+        };
+        let finalized = self.source.commitment().is_finalized();
+        let mut fetched = fetched.into_iter().zip(&missing).map(|(result, signature)| {
+            if let (Ok(serialized), true) = (&result, finalized) {
+                self.cache.put(&signature.to_string(), serialized);
+            }
+            result
+        });
+        cached.into_iter().zip(signatures).map(|(cached, signature)| match cached {
+            Some(serialized) => Ok(serialized),
+            None => fetched.next().unwrap_or_else(|| Err(SourceError::NotFound(format!("Transaction {}", signature))))
+        }).collect()
+    }
+
+    fn get_balance(&self, account: &Pubkey) -> Result<u64, SourceError> {
+        self.source.get_balance(account)
+    }
+
+    fn get_account(&self, account: &Pubkey) -> Result<Option<Account>, SourceError> {
+        self.source.get_account(account)
+    }
+
+    fn commitment(&self) -> CommitmentConfig {
+        self.source.commitment()
+    }
+
+    fn locate_before(&self, time: u64) -> Option<Signature> {
+        self.source.locate_before(time)
+    }
+
+    fn request_count(&self) -> usize {
+        self.source.request_count()
+    }
+}
+
+/// Cache key of the page of signatures of `account` requested with `config`.
+fn signatures_key(account: &Pubkey, config: &GetConfirmedSignaturesForAddress2Config) -> String {
+    let signature = |x: Option<Signature>| x.map_or("none".to_string(), |x| x.to_string());
+    format!("{}.signatures.{}.{}.{}", account, signature(config.before), signature(config.until), config.limit.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use std::str::FromStr;
+    use crate::cache::CacheConfig;
+    use crate::source::fixture::FixtureSource;
+
+    const ACCOUNT: &str = "AS5CnoZ7ezMhUXphn32H5Y2YAXnChdZAjjwywYYXuhgP";
+
+    fn config(before: Option<Signature>) -> GetConfirmedSignaturesForAddress2Config {
+        GetConfirmedSignaturesForAddress2Config { before, until: None, limit: Some(2), commitment: None }
+    }
+
+    fn cache(dir: &Path, offline: bool) -> TransactionCache {
+        TransactionCache::open(CacheConfig { dir: dir.to_path_buf(), max_bytes: None, offline }).unwrap()
+    }
+
+    #[test]
+    fn serve_signature_pages_offline() {
+        let dir = std::env::temp_dir().join(format!("solana_rpc_signatures_{}", std::process::id()));
+        let signatures: Vec<Signature> = (1..=3u8).map(|x| Signature::new(&[x; 64])).collect();
+        let entries: Vec<_> = signatures.iter().enumerate()
+            .map(|(i, x)| serde_json::json!({"signature": x.to_string(), "slot": 30 - i, "blockTime": 1_600_000_030 - i as i64}))
+            .collect();
+        let fixtures = FixtureSource::from_json(&serde_json::json!({"signatures": {ACCOUNT: entries}}).to_string()).unwrap();
+        let account = Pubkey::from_str(ACCOUNT).unwrap();
+
+        let online = cache(&dir, false);
+        let first = CachedSource::new(&online, &fixtures).get_signatures(&account, config(None)).unwrap();
+        let second = CachedSource::new(&online, &fixtures).get_signatures(&account, config(Some(signatures[1]))).unwrap();
+        assert_eq!((first.len(), second.len()), (2, 1));
+        assert_eq!(online.stats().writes, 2);
+
+        let offline = cache(&dir, true);
+        let source = CachedSource::new(&offline, &fixtures);
+        assert_eq!(source.get_signatures(&account, config(None)).unwrap(), first);
+        assert_eq!(source.get_signatures(&account, config(Some(signatures[1]))).unwrap(), second);
+        assert!(matches!(source.get_signatures(&account, config(Some(signatures[2]))), Err(SourceError::CacheMiss(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
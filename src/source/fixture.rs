@@ -0,0 +1,177 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use serde::Deserialize;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_request::MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use crate::errors::SourceError;
+use super::source::ChainSource;
+
+/// Chain data as stored in a fixture file, all fields are optional. Signatures and transactions are
+/// formatted as the results of `getSignaturesForAddress` and `getTransaction` with `json` encoding.
+///
+/// ```json
+/// {
+///     "signatures": {"<account>": [{"signature": "<signature>", "slot": 100, "blockTime": 1600000100}]},
+///     "transactions": {"<signature>": {"slot": 100, "blockTime": 1600000100, "transaction": {}, "meta": {}}},
+///     "balances": {"<account>": 5000},
+///     "accounts": {"<account>": {"lamports": 5000, "owner": "<program>", "data": "<base-58 data>"}}
+/// }
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct FixtureFile {
+    signatures: HashMap<String, Vec<RpcConfirmedTransactionStatusWithSignature>>,
+    transactions: HashMap<String, serde_json::Value>,
+    balances: HashMap<String, u64>,
+    accounts: HashMap<String, FixtureAccount>
+}
+
+/// Account as stored in a fixture file.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FixtureAccount {
+    lamports: u64,
+    /// Owner program as base-58 encoded string.
+    owner: String,
+    /// Account data as base-58 encoded string.
+    #[serde(default)]
+    data: String,
+    #[serde(default)]
+    executable: bool,
+    #[serde(default)]
+    rent_epoch: u64
+}
+
+/// Chain data held in memory, loaded from JSON fixture files, e.g. to run the tracer offline.
+/// Accounts without a recorded balance or account report a balance of zero, like accounts that do not
+/// exist. Signatures and transactions that were not recorded fail with `SourceError::NotFound`.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureSource {
+    /// Signatures per account, newest first.
+    signatures: HashMap<String, Vec<RpcConfirmedTransactionStatusWithSignature>>,
+    transactions: HashMap<String, serde_json::Value>,
+    balances: HashMap<String, u64>,
+    accounts: HashMap<String, Account>
+}
+
+impl FixtureSource {
+    /// Load fixture files, later files extend and override the data of earlier ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - Paths to JSON fixture files.
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Self, SourceError> {
+        let mut source = Self::default();
+        for path in paths {
+            let path = path.as_ref();
+            let content = match fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(err) => return Err(SourceError::FixtureError(format!("{}: {}", path.display(), err)))
+            };
+            if let Err(err) = source.extend_json(&content) {
+                return Err(SourceError::FixtureError(format!("{}: {}", path.display(), err)));
+            }
+        }
+        Ok(source)
+    }
+
+    /// Return a `FixtureSource` with the data of a JSON fixture.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - Fixture formatted as JSON.
+    pub fn from_json(json: &str) -> Result<Self, SourceError> {
+        let mut source = Self::default();
+        match source.extend_json(json) {
+            Ok(()) => Ok(source),
+            Err(err) => Err(SourceError::FixtureError(err))
+        }
+    }
+
+    /// Add the data of a JSON fixture.
+    fn extend_json(&mut self, json: &str) -> Result<(), String> {
+        let file: FixtureFile = match serde_json::from_str(json) {
+            Ok(file) => file,
+            Err(err) => return Err(err.to_string())
+        };
+        for (account, signatures) in file.signatures {
+            let recorded = self.signatures.entry(account).or_default();
+            recorded.retain(|x| !signatures.iter().any(|y| y.signature == x.signature));
+            recorded.extend(signatures);
+            recorded.sort_by_key(|x| Reverse(x.slot));
+        }
+        self.transactions.extend(file.transactions);
+        self.balances.extend(file.balances);
+        for (account, fixture) in file.accounts {
+            let owner = match Pubkey::from_str(&fixture.owner) {
+                Ok(owner) => owner,
+                Err(err) => return Err(format!("{}: owner {}: {}", account, fixture.owner, err))
+            };
+            let data = match bs58::decode(&fixture.data).into_vec() {
+                Ok(data) => data,
+                Err(err) => return Err(format!("{}: data: {}", account, err))
+            };
+            self.accounts.insert(account, Account {
+                lamports: fixture.lamports,
+                data,
+                owner,
+                executable: fixture.executable,
+                rent_epoch: fixture.rent_epoch
+            });
+        }
+        Ok(())
+    }
+}
+
+impl ChainSource for FixtureSource {
+    fn get_signatures(&self, account: &Pubkey, config: GetConfirmedSignaturesForAddress2Config) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, SourceError> {
+        let recorded = match self.signatures.get(&account.to_string()) {
+            Some(recorded) => &recorded[..],
+            None => &[]
+        };
+        let start = match config.before.map(|x| x.to_string()) {
+            Some(before) => match recorded.iter().position(|x| x.signature == before) {
+                Some(position) => position + 1,
+                None => return Err(SourceError::NotFound(format!("Signature {} of {}", before, account)))
+            },
+            None => 0
+        };
+        let until = config.until.map(|x| x.to_string());
+        let limit = config.limit.unwrap_or(MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT);
+        Ok(recorded[start..].iter()
+            .take_while(|x| until.as_ref() != Some(&x.signature))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    fn get_transaction(&self, signature: &Signature) -> Result<String, SourceError> {
+        match self.transactions.get(&signature.to_string()) {
+            Some(transaction) => Ok(transaction.to_string()),
+            None => Err(SourceError::NotFound(format!("Transaction {}", signature)))
+        }
+    }
+
+    fn get_balance(&self, account: &Pubkey) -> Result<u64, SourceError> {
+        let account = account.to_string();
+        Ok(self.balances.get(&account).copied()
+            .or_else(|| self.accounts.get(&account).map(|x| x.lamports))
+            .unwrap_or(0))
+    }
+
+    fn get_account(&self, account: &Pubkey) -> Result<Option<Account>, SourceError> {
+        Ok(self.accounts.get(&account.to_string()).cloned())
+    }
+
+    fn commitment(&self) -> CommitmentConfig {
+        CommitmentConfig::finalized()
+    }
+}
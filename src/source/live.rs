@@ -0,0 +1,120 @@
+use serde_json::{json, Value};
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
+use crate::client::batch::{batch_request, batch_results, is_unsupported};
+use crate::errors::SourceError;
+use crate::signature::signature::Signatures;
+use super::source::ChainSource;
+
+/// Chain data read from a live RPC node. Several transactions are read with a single JSON-RPC batch
+/// request, see `BATCH_METHOD`, or one request each if the client's sender does not support batches.
+impl ChainSource for RpcClient {
+    fn get_signatures(&self, account: &Pubkey, config: GetConfirmedSignaturesForAddress2Config) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, SourceError> {
+        match self.get_signatures_for_address_with_config(account, config) {
+            Ok(signatures) => Ok(signatures),
+            Err(err) => Err(SourceError::ClientError(err.to_string()))
+        }
+    }
+
+    fn get_transaction(&self, signature: &Signature) -> Result<String, SourceError> {
+        let tx = match RpcClient::get_transaction(self, signature, UiTransactionEncoding::Json) {
+            Ok(tx) => tx,
+            Err(err) => return Err(SourceError::ClientError(err.to_string()))
+        };
+        match serde_json::to_string(&tx) {
+            Ok(serialized) => Ok(serialized),
+            Err(err) => Err(SourceError::ClientError(err.to_string()))
+        }
+    }
+
+    fn get_transactions(&self, signatures: &[Signature]) -> Vec<Result<String, SourceError>> {
+        let one_by_one = || signatures.iter().map(|signature| ChainSource::get_transaction(self, signature)).collect();
+        if signatures.len() <= 1 {
+            return one_by_one();
+        }
+        let requests: Vec<(RpcRequest, Value)> = signatures.iter()
+            .map(|signature| (RpcRequest::GetTransaction, json!([signature.to_string(), UiTransactionEncoding::Json])))
+            .collect();
+        let (request, params) = batch_request(&requests);
+        let results = match self.send::<Value>(request, params) {
+            Ok(result) => batch_results(result, signatures.len()),
+            Err(err) if is_unsupported(&err) => return one_by_one(),
+            Err(err) => return signatures.iter().map(|_| Err(SourceError::ClientError(err.to_string()))).collect()
+        };
+        // Transactions are deserialized and serialized again, like `RpcClient::get_transaction` does.
+        results.into_iter().map(|result| {
+            let tx = match result.map(serde_json::from_value::<EncodedConfirmedTransactionWithStatusMeta>) {
+                Ok(Ok(tx)) => tx,
+                Ok(Err(err)) => return Err(SourceError::ClientError(err.to_string())),
+                Err(err) => return Err(SourceError::ClientError(err.to_string()))
+            };
+            match serde_json::to_string(&tx) {
+                Ok(serialized) => Ok(serialized),
+                Err(err) => Err(SourceError::ClientError(err.to_string()))
+            }
+        }).collect()
+    }
+
+    fn get_balance(&self, account: &Pubkey) -> Result<u64, SourceError> {
+        match RpcClient::get_balance(self, account) {
+            Ok(balance) => Ok(balance),
+            Err(err) => Err(SourceError::ClientError(err.to_string()))
+        }
+    }
+
+    fn get_account(&self, account: &Pubkey) -> Result<Option<Account>, SourceError> {
+        match self.get_account_with_commitment(account, RpcClient::commitment(self)) {
+            Ok(response) => Ok(response.value),
+            Err(err) => Err(SourceError::ClientError(err.to_string()))
+        }
+    }
+
+    fn commitment(&self) -> CommitmentConfig {
+        RpcClient::commitment(self)
+    }
+
+    fn locate_before(&self, time: u64) -> Option<Signature> {
+        Signatures::locate_before(self, time)
+    }
+
+    fn request_count(&self) -> usize {
+        self.get_transport_stats().request_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+    use crate::client::cassette::CassetteMode;
+    use crate::client::{get_client, ClientConfig};
+
+    /// Cassette recorded with `--record` from a local stub node answering batch requests with a single invalid
+    /// request error instead of an array, and serving the transactions one at a time.
+    const CASSETTE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cassettes/unsupported_batch.jsonl");
+    const SIGNATURES: [&str; 2] = [
+        "2YBWKDzKA7DjwMe82vZCxsgZ7TSa7NpRZLD8X11hcvTew2bRCZQQ6Gi3GaG3swp96gakbNPhW7gcW7GjkBc15WQ",
+        "5PBPbWnJj6np1NX4LWeJoKytoayeL8Ys4qc5B2M8T5kh7i9XYB4vi3UxQbPLBMqxepUcMvDbr2hce52ZHGx38x3d"
+    ];
+
+    #[test]
+    fn fall_back_to_single_requests() {
+        let client = get_client(ClientConfig { cassette: Some(CassetteMode::Replay(PathBuf::from(CASSETTE))), ..Default::default() }).unwrap();
+        let signatures: Vec<Signature> = SIGNATURES.iter().map(|x| Signature::from_str(x).unwrap()).collect();
+        let transactions = client.get_transactions(&signatures);
+        assert_eq!(transactions.len(), 2);
+        for (signature, transaction) in SIGNATURES.iter().zip(transactions) {
+            let transaction: Value = serde_json::from_str(&transaction.unwrap()).unwrap();
+            assert_eq!(transaction["transaction"]["signatures"][0], *signature);
+        }
+        // The version query, the rejected batch and a request per transaction.
+        assert_eq!(client.request_count(), 4);
+    }
+}
@@ -0,0 +1,52 @@
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use crate::errors::SourceError;
+
+/// Source of the chain data read by the tracer, e.g. a live RPC node, a transaction cache in front of
+/// another source, or fixtures loaded from JSON files.
+pub trait ChainSource: Send + Sync {
+    /// Return one page of transaction signatures for `account`, newest first, following the paging
+    /// semantics of `getSignaturesForAddress`: signatures processed after `before` and up to `until`
+    /// are skipped or excluded, and at most `limit` are returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - Account to return the signatures for.
+    /// * `config` - Paging configuration.
+    fn get_signatures(&self, account: &Pubkey, config: GetConfirmedSignaturesForAddress2Config) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, SourceError>;
+
+    /// Return the transaction for `signature` as JSON, formatted as the result of `getTransaction` with
+    /// `json` encoding.
+    fn get_transaction(&self, signature: &Signature) -> Result<String, SourceError>;
+
+    /// Return the transactions for `signatures`, in order, see `ChainSource::get_transaction`. Sources that
+    /// can read several transactions at once override this, by default they are read one at a time.
+    fn get_transactions(&self, signatures: &[Signature]) -> Vec<Result<String, SourceError>> {
+        signatures.iter().map(|signature| self.get_transaction(signature)).collect()
+    }
+
+    /// Return the balance of `account`, in lamports.
+    fn get_balance(&self, account: &Pubkey) -> Result<u64, SourceError>;
+
+    /// Return `account`, or `None` if it does not exist.
+    fn get_account(&self, account: &Pubkey) -> Result<Option<Account>, SourceError>;
+
+    /// Commitment level the data is read at.
+    fn commitment(&self) -> CommitmentConfig;
+
+    /// Return a signature to start paging backwards from so that transactions processed at or after
+    /// `time` are skipped, see `Signatures::locate_before`. Sources that can't locate it return `None`,
+    /// and those transactions are filtered out while paging instead.
+    fn locate_before(&self, _time: u64) -> Option<Signature> {
+        None
+    }
+
+    /// Number of RPC requests sent so far.
+    fn request_count(&self) -> usize {
+        0
+    }
+}
@@ -1,24 +1,23 @@
 use solana_client::{rpc_client::RpcClient, client_error::ClientError};
 use solana_sdk::pubkey::Pubkey;
 use core::result;
-use std::io;
-use solana_sdk::native_token::LAMPORTS_PER_SOL;
 
 /**
- * Function to return SOL balance from a pubkey.
+ * Function to return balance in lamports from a pubkey.
  */
-pub fn get_balance(client: &RpcClient, pubkey: &Pubkey) -> result::Result<f64, ClientError> {
+#[allow(clippy::result_large_err)]
+pub fn get_balance(client: &RpcClient, pubkey: &Pubkey) -> result::Result<u64, ClientError> {
 
-    let balance = client.get_balance(pubkey)?;
-    Ok(balance as f64/LAMPORTS_PER_SOL as f64)
+    client.get_balance(pubkey)
 }
 
 /**
  * Function to print out SOL balance from a pubkey.
  */
-pub fn show_balance(client: &RpcClient, pubkey: &Pubkey) -> io::Result<()> {
+#[allow(clippy::result_large_err)]
+pub fn show_balance(client: &RpcClient, pubkey: &Pubkey) -> result::Result<(), ClientError> {
 
-    let balance = client.get_balance(pubkey).unwrap();
+    let balance = client.get_balance(pubkey)?;
     println!("Balance for address: {} - is: {}", pubkey, balance);
     Ok(())
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use std::error::Error;
 use std::str::FromStr;
 use solana_client::rpc_client::RpcClient;
 use solana_transaction_status::UiTransactionEncoding;
@@ -7,10 +8,11 @@ use serde_json;
 /**
  * Display raw transaction from a signature.
  */
-pub fn show_transaction_raw(client: &RpcClient, signature: &str) {
+pub fn show_transaction_raw(client: &RpcClient, signature: &str) -> Result<(), Box<dyn Error>> {
 
-    let signature = Signature::from_str(signature).unwrap();
-    let tx = client.get_transaction(&signature, UiTransactionEncoding::Json).unwrap();
+    let signature = Signature::from_str(signature)?;
+    let tx = client.get_transaction(&signature, UiTransactionEncoding::Json)?;
 
-    println!("Transaction details for signature: {}\n {}", signature, serde_json::to_string_pretty(&tx).unwrap());
+    println!("Transaction details for signature: {}\n {}", signature, serde_json::to_string_pretty(&tx)?);
+    Ok(())
 }
\ No newline at end of file
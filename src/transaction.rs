@@ -1,4 +1,7 @@
+pub mod anchor;
 pub mod decode;
+pub mod decoder;
 pub mod enums;
 pub mod instructions;
-pub mod transaction;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod transaction;
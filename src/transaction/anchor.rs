@@ -0,0 +1,535 @@
+use std::fs;
+use std::path::Path;
+use base58::FromBase58;
+use borsh::BorshDeserialize;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use solana_program::hash::hashv;
+use solana_program::pubkey::Pubkey;
+
+use crate::errors::IdlError;
+use super::decoder::{DecodedInstruction, InstructionDecoder, InstructionGeneric};
+use super::transaction::{InstructionProcessed, TransactionDetail};
+
+/// Anchor IDL, as produced by `anchor build`.
+/// Contains `name`, `version`, `address`, `instructions`, `types` and `metadata` fields.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AnchorIdl {
+    /// Program name.
+    #[serde(default)]
+    pub name: String,
+    /// Program version.
+    #[serde(default)]
+    pub version: String,
+    /// Program address, used by newer IDL versions.
+    pub address: Option<String>,
+    /// Instructions exposed by the program.
+    pub instructions: Vec<IdlInstruction>,
+    /// User defined types referenced by instruction arguments.
+    #[serde(default)]
+    pub types: Vec<IdlTypeDef>,
+    /// Deployment metadata, used by older IDL versions to store the program address.
+    pub metadata: Option<IdlMetadata>
+}
+
+/// Contained within an `AnchorIdl` object. Contains `address` field.
+#[derive(Deserialize, Debug, Clone)]
+pub struct IdlMetadata {
+    /// Program address.
+    pub address: Option<String>
+}
+
+/// Contained within an `AnchorIdl` object. Contains `name`, `discriminator`, `accounts` and `args` fields.
+#[derive(Deserialize, Debug, Clone)]
+pub struct IdlInstruction {
+    /// Instruction name.
+    pub name: String,
+    /// Explicit 8 byte discriminator, used by newer IDL versions.
+    pub discriminator: Option<Vec<u8>>,
+    /// Ordered accounts expected by the instruction.
+    pub accounts: Vec<IdlAccountItem>,
+    /// Ordered arguments expected by the instruction.
+    pub args: Vec<IdlField>
+}
+
+/// Account expected by an `IdlInstruction`, either a single account or a nested group of accounts.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IdlAccountItem {
+    Composite { name: String, accounts: Vec<IdlAccountItem> },
+    Single { name: String }
+}
+
+/// Named and typed field of an instruction, struct or enum variant.
+#[derive(Deserialize, Debug, Clone)]
+pub struct IdlField {
+    /// Field name.
+    pub name: String,
+    /// Field type.
+    #[serde(rename = "type")]
+    pub ty: IdlType
+}
+
+/// Type of an `IdlField`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IdlType {
+    /// Primitive type, e.g. `u64`, `string` or `publicKey`.
+    Primitive(String),
+    Vec { vec: Box<IdlType> },
+    Option { option: Box<IdlType> },
+    COption { coption: Box<IdlType> },
+    Array { array: (Box<IdlType>, usize) },
+    Defined { defined: IdlDefined }
+}
+
+/// Reference to a user defined type in `AnchorIdl::types`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IdlDefined {
+    Name(String),
+    Named { name: String }
+}
+
+/// User defined type. Contains `name` and `ty` fields.
+#[derive(Deserialize, Debug, Clone)]
+pub struct IdlTypeDef {
+    /// Type name.
+    pub name: String,
+    /// Type definition.
+    #[serde(rename = "type")]
+    pub ty: IdlTypeDefTy
+}
+
+/// Definition of an `IdlTypeDef`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlTypeDefTy {
+    Struct {
+        #[serde(default)]
+        fields: IdlFields
+    },
+    Enum { variants: Vec<IdlEnumVariant> },
+    /// Definition that cannot be decoded, e.g. a type alias. Arguments of this type are not decoded.
+    #[serde(other)]
+    Unsupported
+}
+
+/// Contained within an `IdlTypeDefTy::Enum`. Contains `name` and `fields` fields.
+#[derive(Deserialize, Debug, Clone)]
+pub struct IdlEnumVariant {
+    /// Variant name.
+    pub name: String,
+    /// Variant fields, if any.
+    #[serde(default)]
+    pub fields: IdlFields
+}
+
+/// Fields of a struct or enum variant, either named or positional.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IdlFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<IdlType>)
+}
+
+impl Default for IdlFields {
+    fn default() -> Self {
+        IdlFields::Named(vec![])
+    }
+}
+
+impl AnchorIdl {
+    /// Load an Anchor IDL from a JSON file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the IDL JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, IdlError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => return Err(IdlError::ReadError(err.to_string()))
+        };
+        Self::from_json(&content)
+    }
+
+    /// Parse an Anchor IDL from a JSON string.
+    pub fn from_json(content: &str) -> Result<Self, IdlError> {
+        match serde_json::from_str(content) {
+            Ok(idl) => Ok(idl),
+            Err(err) => Err(IdlError::ParseError(err.to_string()))
+        }
+    }
+
+    /// Return the program address stored in the IDL, if any.
+    pub fn program_id(&self) -> Option<&String> {
+        self.address.as_ref().or_else(|| self.metadata.as_ref().and_then(|x| x.address.as_ref()))
+    }
+}
+
+/// Instruction decoder driven by an Anchor IDL. Instructions are matched on their 8 byte
+/// discriminator and their arguments Borsh decoded according to the IDL types.
+pub struct AnchorDecoder {
+    program_id: String,
+    idl: AnchorIdl,
+    discriminators: Vec<[u8; 8]>
+}
+
+impl AnchorDecoder {
+    /// Return a new `AnchorDecoder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `program_id` - Program account, as a base-58 encoded string, the IDL describes.
+    /// * `idl` - Anchor IDL.
+    pub fn new(program_id: String, idl: AnchorIdl) -> Self {
+        let discriminators = idl.instructions.iter()
+            .map(|ix| match ix.discriminator.as_deref().and_then(|x| <[u8; 8]>::try_from(x).ok()) {
+                Some(discriminator) => discriminator,
+                None => instruction_discriminator(&ix.name)
+            })
+            .collect();
+        AnchorDecoder { program_id, idl, discriminators }
+    }
+
+    /// Return a new `AnchorDecoder` from an IDL JSON file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the IDL JSON file.
+    /// * `program_id` - Program account, falls back to the address stored in the IDL if `None`.
+    pub fn from_file<P: AsRef<Path>>(path: P, program_id: Option<String>) -> Result<Self, IdlError> {
+        let idl = AnchorIdl::from_file(path)?;
+        let program_id = match program_id.or_else(|| idl.program_id().cloned()) {
+            Some(program_id) => program_id,
+            None => return Err(IdlError::MissingProgramId(idl.name))
+        };
+        Ok(Self::new(program_id, idl))
+    }
+
+    /// Return the IDL used by the decoder.
+    pub fn idl(&self) -> &AnchorIdl {
+        &self.idl
+    }
+
+    /// Decode the arguments of `instruction` from Borsh encoded `data`.
+    fn decode_args(&self, instruction: &IdlInstruction, data: &mut &[u8]) -> Option<Value> {
+        let mut args = Map::new();
+        for arg in &instruction.args {
+            let value = self.decode_type(&arg.ty, data)?;
+            args.insert(arg.name.clone(), value);
+        }
+        Some(Value::Object(args))
+    }
+
+    /// Decode a single Borsh encoded value of type `ty`.
+    fn decode_type(&self, ty: &IdlType, data: &mut &[u8]) -> Option<Value> {
+        let value = match ty {
+            IdlType::Primitive(name) => match &name[..] {
+                "bool" => json!(read::<bool>(data)?),
+                "u8" => json!(read::<u8>(data)?),
+                "i8" => json!(read::<i8>(data)?),
+                "u16" => json!(read::<u16>(data)?),
+                "i16" => json!(read::<i16>(data)?),
+                "u32" => json!(read::<u32>(data)?),
+                "i32" => json!(read::<i32>(data)?),
+                "u64" => json!(read::<u64>(data)?),
+                "i64" => json!(read::<i64>(data)?),
+                "u128" => json!(read::<u128>(data)?.to_string()),
+                "i128" => json!(read::<i128>(data)?.to_string()),
+                "f32" => json!(read::<f32>(data)?),
+                "f64" => json!(read::<f64>(data)?),
+                "string" => json!(read::<String>(data)?),
+                "bytes" => json!(read::<Vec<u8>>(data)?),
+                "publicKey" | "pubkey" => json!(Pubkey::new_from_array(read::<[u8; 32]>(data)?).to_string()),
+                _ => return None
+            },
+            IdlType::Vec { vec } => {
+                let len = read::<u32>(data)?;
+                let mut values = vec![];
+                for _ in 0..len {
+                    values.push(self.decode_type(vec, data)?);
+                }
+                Value::Array(values)
+            },
+            IdlType::Option { option } => match read::<u8>(data)? {
+                0 => Value::Null,
+                _ => self.decode_type(option, data)?
+            },
+            IdlType::COption { coption } => match read::<u32>(data)? {
+                0 => Value::Null,
+                _ => self.decode_type(coption, data)?
+            },
+            IdlType::Array { array: (ty, len) } => {
+                let mut values = vec![];
+                for _ in 0..*len {
+                    values.push(self.decode_type(ty, data)?);
+                }
+                Value::Array(values)
+            },
+            IdlType::Defined { defined } => {
+                let name = match defined {
+                    IdlDefined::Name(name) => name,
+                    IdlDefined::Named { name } => name
+                };
+                let type_def = self.idl.types.iter().find(|x| &x.name == name)?;
+                match &type_def.ty {
+                    IdlTypeDefTy::Struct { fields } => self.decode_fields(fields, data)?,
+                    IdlTypeDefTy::Enum { variants } => {
+                        let index = usize::from(read::<u8>(data)?);
+                        let variant = variants.get(index)?;
+                        let fields = self.decode_fields(&variant.fields, data)?;
+                        let is_empty = fields.as_object().map(|x| x.is_empty()).unwrap_or(false)
+                            || fields.as_array().map(|x| x.is_empty()).unwrap_or(false);
+                        match is_empty {
+                            true => json!(variant.name),
+                            false => json!({ variant.name.clone(): fields })
+                        }
+                    },
+                    IdlTypeDefTy::Unsupported => return None
+                }
+            }
+        };
+        Some(value)
+    }
+
+    /// Decode the Borsh encoded fields of a struct or enum variant.
+    fn decode_fields(&self, fields: &IdlFields, data: &mut &[u8]) -> Option<Value> {
+        match fields {
+            IdlFields::Named(fields) => {
+                let mut values = Map::new();
+                for field in fields {
+                    values.insert(field.name.clone(), self.decode_type(&field.ty, data)?);
+                }
+                Some(Value::Object(values))
+            },
+            IdlFields::Tuple(types) => {
+                let mut values = vec![];
+                for ty in types {
+                    values.push(self.decode_type(ty, data)?);
+                }
+                Some(Value::Array(values))
+            }
+        }
+    }
+}
+
+impl InstructionDecoder for AnchorDecoder {
+    fn program_id(&self) -> &str {
+        &self.program_id
+    }
+
+    fn decode(&self, ix: &InstructionProcessed, _transaction: &TransactionDetail) -> Option<DecodedInstruction> {
+        let data = ix.data.from_base58().ok()?;
+        if data.len() < 8 {
+            return None;
+        }
+        let index = self.discriminators.iter().position(|x| x[..] == data[..8])?;
+        let instruction = &self.idl.instructions[index];
+        let args = self.decode_args(instruction, &mut &data[8..])?;
+
+        let mut names = vec![];
+        flatten_account_names(&instruction.accounts, "", &mut names);
+        let accounts = ix.accounts.iter().enumerate()
+            .map(|(idx, account)| {
+                let name = names.get(idx).cloned().unwrap_or_else(|| format!("remaining_{}", idx - names.len()));
+                (name, account.clone())
+            })
+            .collect();
+
+        let data = InstructionGeneric {
+            program: self.program_id.clone(),
+            action: instruction.name.clone(),
+            accounts,
+            args,
+            block_time: ix.block_time,
+            signature: ix.signature.clone(),
+            instruction_index: ix.instruction_index,
+            inner_index: ix.inner_index
+        };
+        Some(DecodedInstruction::Program(data))
+    }
+}
+
+/// Read a single Borsh encoded value from `data`.
+fn read<T: BorshDeserialize>(data: &mut &[u8]) -> Option<T> {
+    T::deserialize(data).ok()
+}
+
+/// Return the 8 byte discriminator Anchor derives for an instruction, the first 8 bytes of
+/// `sha256("global:<snake_case_name>")`.
+pub fn instruction_discriminator(name: &str) -> [u8; 8] {
+    let preimage = format!("global:{}", to_snake_case(name));
+    let hash = hashv(&[preimage.as_bytes()]);
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash.to_bytes()[..8]);
+    discriminator
+}
+
+/// Convert a camelCase IDL name to snake_case, splitting words like the `heck` crate Anchor uses:
+/// before an uppercase letter following a lowercase letter or digit, and before the last letter of an
+/// acronym followed by a lowercase letter, e.g. `initializeNFT` to `initialize_nft` and `setURIPrefix`
+/// to `set_uri_prefix`.
+fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words: Vec<String> = vec![];
+    let mut word = String::new();
+    // Case of the last letter of the current word, `None` at the start of a word. Digits keep the
+    // case of the letters before them.
+    let mut lowercase: Option<bool> = None;
+    for (index, c) in chars.iter().enumerate() {
+        if *c == '_' {
+            words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+            lowercase = None;
+            continue;
+        }
+        let next_lowercase = chars.get(index + 1).is_some_and(|x| x.is_lowercase());
+        let boundary = match lowercase {
+            Some(true) => c.is_uppercase(),
+            Some(false) => c.is_uppercase() && next_lowercase,
+            None => false
+        };
+        if boundary {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(c.to_lowercase());
+        if c.is_lowercase() || c.is_uppercase() {
+            lowercase = Some(c.is_lowercase());
+        }
+    }
+    words.extend((!word.is_empty()).then_some(word));
+    words.join("_")
+}
+
+/// Flatten nested IDL account groups into an ordered array of account names,
+/// nested accounts are named `group.account`.
+fn flatten_account_names(items: &[IdlAccountItem], prefix: &str, names: &mut Vec<String>) {
+    for item in items {
+        match item {
+            IdlAccountItem::Single { name } => names.push(format!("{}{}", prefix, name)),
+            IdlAccountItem::Composite { name, accounts } => {
+                flatten_account_names(accounts, &format!("{}{}.", prefix, name), names);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+    use crate::transaction::decoder::DecoderRegistry;
+
+    const PROGRAM: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    fn decoder() -> AnchorDecoder {
+        let idl = AnchorIdl::from_json(r#"{
+            "name": "market",
+            "version": "0.1.0",
+            "instructions": [{
+                "name": "placeOrder",
+                "accounts": [{"name": "market", "accounts": [{"name": "book"}]}, {"name": "owner"}],
+                "args": [{"name": "order", "type": {"defined": "Order"}}, {"name": "memo", "type": {"option": "string"}}]
+            }],
+            "types": [
+                {"name": "Price", "type": {"kind": "type", "alias": "u64"}},
+                {"name": "Order", "type": {"kind": "struct", "fields": [
+                    {"name": "side", "type": {"defined": "Side"}},
+                    {"name": "amount", "type": "u64"},
+                    {"name": "owner", "type": "publicKey"},
+                    {"name": "levels", "type": {"vec": {"array": ["u8", 2]}}}
+                ]}},
+                {"name": "Side", "type": {"kind": "enum", "variants": [{"name": "Bid"}, {"name": "Ask", "fields": ["u128"]}]}}
+            ]
+        }"#).unwrap();
+        AnchorDecoder::new(PROGRAM.to_string(), idl)
+    }
+
+    #[test]
+    fn snake_case_follows_heck() {
+        assert_eq!(to_snake_case("initialize"), "initialize");
+        assert_eq!(to_snake_case("placeOrder"), "place_order");
+        assert_eq!(to_snake_case("initializeNFT"), "initialize_nft");
+        assert_eq!(to_snake_case("setURI"), "set_uri");
+        assert_eq!(to_snake_case("setURIPrefix"), "set_uri_prefix");
+        assert_eq!(to_snake_case("mintV2Token"), "mint_v2_token");
+        assert_eq!(to_snake_case("NFT2Mint"), "nft2_mint");
+        assert_eq!(to_snake_case("already_snake"), "already_snake");
+    }
+
+    #[test]
+    fn discriminator_of_snake_case_name() {
+        assert_eq!(instruction_discriminator("initialize"), [175, 175, 109, 31, 13, 152, 155, 237]);
+        assert_eq!(instruction_discriminator("initializeNFT"), [200, 16, 218, 53, 27, 164, 153, 152]);
+        assert_eq!(instruction_discriminator("setURI"), instruction_discriminator("set_uri"));
+        assert_eq!(decoder().discriminators, vec![instruction_discriminator("place_order")]);
+    }
+
+    #[test]
+    fn decode_borsh_types() {
+        let decoder = decoder();
+        let owner = Pubkey::new_from_array([7; 32]);
+        let mut data = vec![1u8];
+        data.extend(5u128.try_to_vec().unwrap());
+        data.extend(1_000u64.try_to_vec().unwrap());
+        data.extend(owner.to_bytes());
+        data.extend(vec![[1u8, 2], [3, 4]].try_to_vec().unwrap());
+        data.extend(Some("memo".to_string()).try_to_vec().unwrap());
+
+        let args = decoder.decode_args(&decoder.idl.instructions[0], &mut &data[..]).unwrap();
+        assert_eq!(args, json!({
+            "order": {"side": {"Ask": ["5"]}, "amount": 1_000, "owner": owner.to_string(), "levels": [[1, 2], [3, 4]]},
+            "memo": "memo"
+        }));
+
+        let mut bid = vec![0u8];
+        bid.extend(0u128.try_to_vec().unwrap());
+        assert_eq!(decoder.decode_type(&IdlType::Defined { defined: IdlDefined::Name("Side".to_string()) }, &mut &bid[..]), Some(json!("Bid")));
+    }
+
+    #[test]
+    fn reject_truncated_and_unsupported_types() {
+        let decoder = decoder();
+        assert_eq!(decoder.decode_type(&IdlType::Primitive("u64".to_string()), &mut &[1u8, 0, 0][..]), None);
+        assert_eq!(decoder.decode_type(&IdlType::Primitive("u256".to_string()), &mut &[0u8; 32][..]), None);
+        // Type aliases are parsed without rejecting the IDL, but not decoded.
+        assert!(matches!(decoder.idl.types[0].ty, IdlTypeDefTy::Unsupported));
+        assert_eq!(decoder.decode_type(&IdlType::Defined { defined: IdlDefined::Name("Price".to_string()) }, &mut &[0u8; 8][..]), None);
+    }
+
+    #[test]
+    fn decode_through_registry() {
+        let path = std::env::temp_dir().join(format!("solana_rpc_idl_{}.json", std::process::id()));
+        fs::write(&path, json!({
+            "address": PROGRAM,
+            "metadata": {"name": "counter", "version": "0.1.0"},
+            "instructions": [{"name": "increment", "accounts": [{"name": "counter"}], "args": [{"name": "by", "type": "u32"}]}]
+        }).to_string()).unwrap();
+        let registry = DecoderRegistry::with_idls(&[&path]).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut data = instruction_discriminator("increment").to_vec();
+        data.extend(3u32.try_to_vec().unwrap());
+        let counter = Pubkey::new_from_array([7; 32]).to_string();
+        let ix = InstructionProcessed {
+            executer: PROGRAM.to_string(),
+            accounts: vec![counter.clone()],
+            data: bs58::encode(data).into_string(),
+            block_time: 1650000000,
+            slot: 10,
+            signature: "signature".to_string(),
+            instruction_index: 0,
+            inner_index: None
+        };
+        let transaction = serde_json::from_value(json!({"slot": 10, "blockTime": 1650000000, "transaction": null, "meta": null})).unwrap();
+        match registry.decode(&ix, &transaction) {
+            Some(DecodedInstruction::Program(decoded)) => {
+                assert_eq!(decoded.action, "increment");
+                assert_eq!(decoded.accounts, [("counter".to_string(), counter)]);
+                assert_eq!(decoded.args, json!({"by": 3}));
+            },
+            decoded => panic!("{:?}", decoded)
+        }
+        assert!(matches!(DecoderRegistry::with_idls(&["missing.json"]), Err(IdlError::ReadError(_))));
+    }
+}
@@ -1,50 +1,551 @@
 use base58::FromBase58;
-use solana_program::native_token::LAMPORTS_PER_SOL;
+use serde::de::DeserializeOwned;
+use solana_program::pubkey::Pubkey;
+use spl_token::instruction::TokenInstruction;
 
-use crate::constants::SYSTEM_PROGRAM; 
+use crate::constants::{SYSTEM_PROGRAM, TOKEN_PROGRAM};
 use crate::parsing::time::convert_unix_to_time;
-use super::transaction::InstructionProcessed;
-use super::instructions::{InstructionTransfer, InstructionTransferWrapper};
+use super::transaction::{InstructionProcessed, TransactionDetail, TransactionTokenProcessed};
+use super::instructions::{
+    InstructionTransfer, InstructionTransferWrapper, InstructionTokenTransferWrapper, InstructionCreateAccount, InstructionAssign,
+    InstructionCreateAccountWithSeed, InstructionAllocate, InstructionAllocateWithSeed,
+    InstructionAssignWithSeed, InstructionTransferWithSeed, SystemInstructionDecoded
+};
 use super::enums::SystemProgramInstruction;
 
 /// Decode inner instruction from transaction and wraps it in an `InstructionTransferWrapper`.
-/// * TODO: In the future it should return an `Option<TraitObject>` for extendability.
+/// Only system program instructions that move lamports between two accounts are returned,
+/// see `decoder::DecoderRegistry` for decoding instructions of other programs.
 pub fn decode_instruction(ix: InstructionProcessed) -> Option<InstructionTransferWrapper> {
 
-    let account_program = &ix.executer[..];
+    match &ix.executer[..] {
+        SYSTEM_PROGRAM => {
+            let decoded = decode_system_instruction(&ix)?;
+            transfer_from_system_instruction(&ix, &decoded)
+        },
+        _ => None
+    }
+}
+
+/// Wrap a decoded system program instruction in an `InstructionTransferWrapper`, if it moves
+/// lamports between two accounts.
+pub fn transfer_from_system_instruction(ix: &InstructionProcessed, decoded: &SystemInstructionDecoded) -> Option<InstructionTransferWrapper> {
 
-    let data = match ix.data.from_base58() {
-        Ok(data) => Some(data),
-        Err(_) => return None
+    let (source, destination, lamports) = decoded.lamport_transfer()?;
+    let data = InstructionTransferWrapper {
+        lamports,
+        program: ix.executer.clone(),
+        source: source.clone(),
+        destination: destination.clone(),
+        action: decoded.to_string(),
+        block_time: ix.block_time,
+        human_time: convert_unix_to_time(ix.block_time),
+        slot: ix.slot,
+        signature: ix.signature.clone(),
+        instruction_index: ix.instruction_index,
+        inner_index: ix.inner_index
     };
+    Some(data)
+}
 
-    let data = data.unwrap();
+/// Decode a system program instruction into a `SystemInstructionDecoded` record.
+/// Returns `None` if the instruction was not executed by the system program or its data is malformed.
+pub fn decode_system_instruction(ix: &InstructionProcessed) -> Option<SystemInstructionDecoded> {
 
-    match account_program {
-        SYSTEM_PROGRAM => {
-            let action: SystemProgramInstruction = bincode::deserialize(&data[..4]).unwrap();
-            match action {
-                // SystemProgram transfer instruction.
-                // Account references: 
-                // [0] => funding account.
-                // [1] => recipient account.
-                SystemProgramInstruction::Transfer => {
-                    let data: InstructionTransfer = bincode::deserialize(&data[4..]).unwrap();
-                    let data = InstructionTransferWrapper {
-                        amount: data.lamports as f64 / LAMPORTS_PER_SOL as f64,
-                        program: account_program.to_string(),
-                        source: ix.accounts[0].clone(),
-                        destination: ix.accounts[1].clone(),
-                        action: SystemProgramInstruction::Transfer.to_string(),
-                        block_time: ix.block_time,
-                        human_time: convert_unix_to_time(ix.block_time),
-                        signature: ix.signature,
-                    };
-                    Some(data)
-                },
-                _ => None
+    if ix.executer != SYSTEM_PROGRAM {
+        return None;
+    }
+
+    let data = ix.data.from_base58().ok()?;
+    if data.len() < 4 {
+        return None;
+    }
+
+    let action: SystemProgramInstruction = bincode::deserialize(&data[..4]).ok()?;
+    let args = &data[4..];
+    let account = |index: usize| ix.accounts.get(index).cloned();
+
+    let decoded = match action {
+        // Account references:
+        // [0] => funding account.
+        // [1] => new account.
+        SystemProgramInstruction::CreateAccount => {
+            let args: InstructionCreateAccount = deserialize(args)?;
+            SystemInstructionDecoded::CreateAccount {
+                funding: account(0)?,
+                new_account: account(1)?,
+                lamports: args.lamports,
+                space: args.space,
+                owner: args.owner.to_string()
+            }
+        },
+        // Account references:
+        // [0] => assigned account.
+        SystemProgramInstruction::Assign => {
+            let args: InstructionAssign = deserialize(args)?;
+            SystemInstructionDecoded::Assign {
+                account: account(0)?,
+                owner: args.owner.to_string()
+            }
+        },
+        // Account references:
+        // [0] => funding account.
+        // [1] => recipient account.
+        SystemProgramInstruction::Transfer => {
+            let args: InstructionTransfer = deserialize(args)?;
+            SystemInstructionDecoded::Transfer {
+                funding: account(0)?,
+                recipient: account(1)?,
+                lamports: args.lamports
+            }
+        },
+        // Account references:
+        // [0] => funding account.
+        // [1] => created account.
+        // [2] => base account (optional).
+        SystemProgramInstruction::CreateAccountWithSeed => {
+            let args: InstructionCreateAccountWithSeed = deserialize(args)?;
+            SystemInstructionDecoded::CreateAccountWithSeed {
+                funding: account(0)?,
+                new_account: account(1)?,
+                base: args.base.to_string(),
+                seed: args.seed,
+                lamports: args.lamports,
+                space: args.space,
+                owner: args.owner.to_string()
+            }
+        },
+        // Account references:
+        // [0] => nonce account.
+        // [1] => recent blockhashes sysvar.
+        // [2] => nonce authority.
+        SystemProgramInstruction::AdvancedNonceAccount => {
+            SystemInstructionDecoded::AdvanceNonceAccount {
+                nonce_account: account(0)?,
+                nonce_authority: account(2)?
+            }
+        },
+        // Account references:
+        // [0] => nonce account.
+        // [1] => recipient account.
+        // [2] => recent blockhashes sysvar.
+        // [3] => rent sysvar.
+        // [4] => nonce authority.
+        SystemProgramInstruction::WithdrawNonceAccount => {
+            let lamports: u64 = deserialize(args)?;
+            SystemInstructionDecoded::WithdrawNonceAccount {
+                nonce_account: account(0)?,
+                recipient: account(1)?,
+                nonce_authority: account(4)?,
+                lamports
+            }
+        },
+        // Account references:
+        // [0] => nonce account.
+        // [1] => recent blockhashes sysvar.
+        // [2] => rent sysvar.
+        SystemProgramInstruction::InitializeNonceAccount => {
+            let authority: Pubkey = deserialize(args)?;
+            SystemInstructionDecoded::InitializeNonceAccount {
+                nonce_account: account(0)?,
+                nonce_authority: authority.to_string()
             }
         },
+        // Account references:
+        // [0] => nonce account.
+        // [1] => nonce authority.
+        SystemProgramInstruction::AuthorizeNonceAccount => {
+            let new_authority: Pubkey = deserialize(args)?;
+            SystemInstructionDecoded::AuthorizeNonceAccount {
+                nonce_account: account(0)?,
+                nonce_authority: account(1)?,
+                new_authority: new_authority.to_string()
+            }
+        },
+        // Account references:
+        // [0] => allocated account.
+        SystemProgramInstruction::Allocate => {
+            let args: InstructionAllocate = deserialize(args)?;
+            SystemInstructionDecoded::Allocate {
+                account: account(0)?,
+                space: args.space
+            }
+        },
+        // Account references:
+        // [0] => allocated account.
+        // [1] => base account.
+        SystemProgramInstruction::AllocateWithSeed => {
+            let args: InstructionAllocateWithSeed = deserialize(args)?;
+            SystemInstructionDecoded::AllocateWithSeed {
+                account: account(0)?,
+                base: args.base.to_string(),
+                seed: args.seed,
+                space: args.space,
+                owner: args.owner.to_string()
+            }
+        },
+        // Account references:
+        // [0] => assigned account.
+        // [1] => base account.
+        SystemProgramInstruction::AssignWithSeed => {
+            let args: InstructionAssignWithSeed = deserialize(args)?;
+            SystemInstructionDecoded::AssignWithSeed {
+                account: account(0)?,
+                base: args.base.to_string(),
+                seed: args.seed,
+                owner: args.owner.to_string()
+            }
+        },
+        // Account references:
+        // [0] => funding account.
+        // [1] => base account for the funding account.
+        // [2] => recipient account.
+        SystemProgramInstruction::TransferWithSeed => {
+            let args: InstructionTransferWithSeed = deserialize(args)?;
+            SystemInstructionDecoded::TransferWithSeed {
+                funding: account(0)?,
+                base: account(1)?,
+                recipient: account(2)?,
+                lamports: args.lamports,
+                from_seed: args.from_seed,
+                from_owner: args.from_owner.to_string()
+            }
+        },
+        // Account references:
+        // [0] => nonce account.
+        SystemProgramInstruction::UpgradeNonceAccount => {
+            SystemInstructionDecoded::UpgradeNonceAccount {
+                nonce_account: account(0)?
+            }
+        }
+    };
+    Some(decoded)
+}
+
+/// Decode an SPL Token program instruction and wrap it in an `InstructionTokenTransferWrapper`.
+/// Token account mints, owners and decimals are resolved through the token balances of `transaction`.
+/// Supports `Transfer`, `TransferChecked`, `MintTo`, `MintToChecked`, `Burn`, `BurnChecked` and `CloseAccount`.
+///
+/// # Arguments
+///
+/// * `ix` - Instruction invoked during transaction processing.
+/// * `transaction` - Transaction the instruction belongs to.
+pub fn decode_token_instruction(ix: &InstructionProcessed, transaction: &TransactionDetail) -> Option<InstructionTokenTransferWrapper> {
+
+    if ix.executer != TOKEN_PROGRAM {
+        return None;
+    }
+
+    let data = ix.data.from_base58().ok()?;
+    let instruction = TokenInstruction::unpack(&data).ok()?;
+    let account = |index: usize| ix.accounts.get(index).cloned();
+
+    let balances_before = transaction.get_token_balances_before().unwrap_or_default();
+    let balances_after = transaction.get_token_balances_after().unwrap_or_default();
+    let balance = |token_account: &str| -> Option<&TransactionTokenProcessed> {
+        balances_before.iter()
+            .chain(balances_after.iter())
+            .find(|entry| entry.token_account == token_account)
+    };
+    let owner = |token_account: &str| balance(token_account).map(|entry| entry.owner.clone());
+
+    // (action, source, source owner, destination, destination owner, mint, amount, decimals)
+    let (action, source, source_owner, destination, destination_owner, mint, amount, decimals) = match instruction {
+        // Account references:
+        // [0] => source account.
+        // [1] => destination account.
+        // [2] => source account owner or delegate.
+        TokenInstruction::Transfer { amount } => {
+            let (source, destination) = (account(0)?, account(1)?);
+            let source_owner = owner(&source).or_else(|| account(2));
+            let destination_owner = owner(&destination);
+            ("Transfer", source, source_owner, destination, destination_owner, None, Some(amount), None)
+        },
+        // Account references:
+        // [0] => source account.
+        // [1] => token mint.
+        // [2] => destination account.
+        // [3] => source account owner or delegate.
+        TokenInstruction::TransferChecked { amount, decimals } => {
+            let (source, destination) = (account(0)?, account(2)?);
+            let source_owner = owner(&source).or_else(|| account(3));
+            let destination_owner = owner(&destination);
+            ("TransferChecked", source, source_owner, destination, destination_owner, account(1), Some(amount), Some(decimals))
+        },
+        // Account references:
+        // [0] => token mint.
+        // [1] => destination account.
+        // [2] => mint authority.
+        TokenInstruction::MintTo { amount } => {
+            let destination = account(1)?;
+            let destination_owner = owner(&destination);
+            ("MintTo", account(0)?, account(2), destination, destination_owner, account(0), Some(amount), None)
+        },
+        TokenInstruction::MintToChecked { amount, decimals } => {
+            let destination = account(1)?;
+            let destination_owner = owner(&destination);
+            ("MintToChecked", account(0)?, account(2), destination, destination_owner, account(0), Some(amount), Some(decimals))
+        },
+        // Account references:
+        // [0] => account to burn from.
+        // [1] => token mint.
+        // [2] => account owner or delegate.
+        TokenInstruction::Burn { amount } => {
+            let source = account(0)?;
+            let source_owner = owner(&source).or_else(|| account(2));
+            ("Burn", source, source_owner, account(1)?, None, account(1), Some(amount), None)
+        },
+        TokenInstruction::BurnChecked { amount, decimals } => {
+            let source = account(0)?;
+            let source_owner = owner(&source).or_else(|| account(2));
+            ("BurnChecked", source, source_owner, account(1)?, None, account(1), Some(amount), Some(decimals))
+        },
+        // Account references:
+        // [0] => account to close.
+        // [1] => destination account for the remaining lamports.
+        // [2] => account owner.
+        // Only empty accounts can be closed, so no tokens move.
+        TokenInstruction::CloseAccount => {
+            let source = account(0)?;
+            let source_owner = owner(&source).or_else(|| account(2));
+            ("CloseAccount", source, source_owner, account(1)?, None, None, None, None)
+        },
+        _ => return None
+    };
+
+    let source_balance = balance(&source);
+    let destination_balance = balance(&destination);
+    let mint = mint.or_else(|| source_balance.or(destination_balance).map(|entry| entry.token_mint.clone()));
+    let decimals = decimals.or_else(|| {
+        source_balance.or(destination_balance)
+            .and_then(|entry| entry.decimals)
+            .map(|decimals| decimals as u8)
+    });
+
+    // The rent of a closed account is reclaimed by the destination, the account is left without lamports.
+    let lamports = match action {
+        "CloseAccount" => transaction.get_lamport_balances(&source).map(|(before, after)| before.saturating_sub(after)),
         _ => None
+    };
+
+    let data = InstructionTokenTransferWrapper {
+        program: ix.executer.clone(),
+        action: action.to_string(),
+        mint,
+        amount,
+        lamports,
+        decimals,
+        source,
+        source_owner,
+        destination,
+        destination_owner,
+        block_time: ix.block_time,
+        human_time: convert_unix_to_time(ix.block_time),
+        slot: ix.slot,
+        signature: ix.signature.clone(),
+        instruction_index: ix.instruction_index,
+        inner_index: ix.inner_index
+    };
+    Some(data)
+}
+
+/// Deserialize bincode encoded instruction arguments.
+fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Option<T> {
+    bincode::deserialize(data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use base58::ToBase58;
+    use crate::parsing::amount::format_amount;
+
+    const FUNDING: &str = "AS5CnoZ7ezMhUXphn32H5Y2YAXnChdZAjjwywYYXuhgP";
+    const RECIPIENT: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    const SOURCE_TOKEN: &str = "7UX2i7SucgLMQcfZ75s3VXmZZY4YRUyJN9X1RgfMoDUi";
+    const DESTINATION_TOKEN: &str = "HN7cABqLq46Es1jh92dQQisAq662SmxELLLsHHe4YWrH";
+    const MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    /// System program instruction with `data` and `accounts`.
+    fn instruction(data: &[u8], accounts: &[&str]) -> InstructionProcessed {
+        InstructionProcessed {
+            executer: SYSTEM_PROGRAM.to_string(),
+            accounts: accounts.iter().map(|x| x.to_string()).collect(),
+            data: data.to_base58(),
+            block_time: 1650000000,
+            slot: 10,
+            signature: "signature".to_string(),
+            instruction_index: 0,
+            inner_index: None
+        }
+    }
+
+    /// Bincode encoded instruction tag followed by `args`.
+    fn data(tag: u32, args: &[&[u8]]) -> Vec<u8> {
+        let mut data = tag.to_le_bytes().to_vec();
+        for arg in args {
+            data.extend_from_slice(arg);
+        }
+        data
+    }
+
+    /// Token program instruction with `data` and `accounts`.
+    fn token_instruction(data: &[u8], accounts: &[&str]) -> InstructionProcessed {
+        InstructionProcessed { executer: TOKEN_PROGRAM.to_string(), ..instruction(data, accounts) }
+    }
+
+    /// Transaction where `FUNDING` owns `SOURCE_TOKEN`, holding 5 tokens of `MINT` then `after`,
+    /// and `RECIPIENT` owns `DESTINATION_TOKEN`. `SOURCE_TOKEN` holds `lamports` then none.
+    fn token_transaction(after: u64, lamports: u64) -> TransactionDetail {
+        let balance = |index: usize, owner: &str, amount: u64| serde_json::json!({
+            "accountIndex": index,
+            "mint": MINT,
+            "owner": owner,
+            "uiTokenAmount": {"decimals": 6.0, "amount": amount.to_string(), "uiAmountString": format_amount(amount, 6)}
+        });
+        serde_json::from_value(serde_json::json!({
+            "slot": 10,
+            "blockTime": 1650000000,
+            "transaction": {
+                "signatures": ["signature"],
+                "message": {
+                    "header": {"numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 1},
+                    "accountKeys": [FUNDING, SOURCE_TOKEN, DESTINATION_TOKEN, TOKEN_PROGRAM],
+                    "recentBlockhash": "EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N",
+                    "instructions": []
+                }
+            },
+            "meta": {
+                "err": null,
+                "fee": 5000,
+                "preBalances": [1_000_000, lamports, 2_039_280, 1],
+                "postBalances": [1_000_000 + lamports - 5000, 0, 2_039_280, 1],
+                "preTokenBalances": [balance(1, FUNDING, 5_000_000), balance(2, RECIPIENT, 0)],
+                "postTokenBalances": [balance(1, FUNDING, after), balance(2, RECIPIENT, 5_000_000 - after)]
+            }
+        })).unwrap()
+    }
+
+    #[test]
+    fn decode_transfer() {
+        let ix = instruction(&data(2, &[&1_500_000_000u64.to_le_bytes()]), &[FUNDING, RECIPIENT]);
+        let decoded = decode_system_instruction(&ix).unwrap();
+        assert_eq!(decoded, SystemInstructionDecoded::Transfer {
+            funding: FUNDING.to_string(),
+            recipient: RECIPIENT.to_string(),
+            lamports: 1_500_000_000
+        });
+
+        let transfer = transfer_from_system_instruction(&ix, &decoded).unwrap();
+        assert_eq!((transfer.source.as_str(), transfer.destination.as_str()), (FUNDING, RECIPIENT));
+        assert_eq!(transfer.lamports, 1_500_000_000);
+        assert_eq!(transfer.action, "Transfer");
+    }
+
+    #[test]
+    fn decode_create_account() {
+        let owner = Pubkey::from_str(RECIPIENT).unwrap();
+        let ix = instruction(&data(0, &[&2_039_280u64.to_le_bytes(), &165u64.to_le_bytes(), owner.as_ref()]), &[FUNDING, RECIPIENT]);
+        assert_eq!(decode_system_instruction(&ix).unwrap(), SystemInstructionDecoded::CreateAccount {
+            funding: FUNDING.to_string(),
+            new_account: RECIPIENT.to_string(),
+            lamports: 2_039_280,
+            space: 165,
+            owner: RECIPIENT.to_string()
+        });
+    }
+
+    #[test]
+    fn decode_transfer_with_seed() {
+        let seed = "seed";
+        let (lamports, seed_len, owner) = (7u64.to_le_bytes(), (seed.len() as u64).to_le_bytes(), Pubkey::default());
+        let ix = instruction(&data(11, &[&lamports, &seed_len, seed.as_bytes(), owner.as_ref()]), &[FUNDING, RECIPIENT, RECIPIENT]);
+        assert_eq!(decode_system_instruction(&ix).unwrap(), SystemInstructionDecoded::TransferWithSeed {
+            funding: FUNDING.to_string(),
+            base: RECIPIENT.to_string(),
+            recipient: RECIPIENT.to_string(),
+            lamports: 7,
+            from_seed: seed.to_string(),
+            from_owner: Pubkey::default().to_string()
+        });
+    }
+
+    #[test]
+    fn decode_non_transfer() {
+        let ix = instruction(&data(8, &[&200u64.to_le_bytes()]), &[FUNDING]);
+        let decoded = decode_system_instruction(&ix).unwrap();
+        assert_eq!(decoded, SystemInstructionDecoded::Allocate { account: FUNDING.to_string(), space: 200 });
+        assert!(transfer_from_system_instruction(&ix, &decoded).is_none());
+    }
+
+    #[test]
+    fn reject_malformed_instruction() {
+        // Truncated tag, truncated arguments, unknown tag and missing accounts.
+        assert!(decode_system_instruction(&instruction(&[2, 0], &[FUNDING, RECIPIENT])).is_none());
+        assert!(decode_system_instruction(&instruction(&data(2, &[&[1, 0]]), &[FUNDING, RECIPIENT])).is_none());
+        assert!(decode_system_instruction(&instruction(&data(13, &[]), &[FUNDING])).is_none());
+        assert!(decode_system_instruction(&instruction(&data(2, &[&1u64.to_le_bytes()]), &[FUNDING])).is_none());
+
+        let mut ix = instruction(&data(2, &[&1u64.to_le_bytes()]), &[FUNDING, RECIPIENT]);
+        ix.executer = TOKEN_PROGRAM.to_string();
+        assert!(decode_system_instruction(&ix).is_none());
+    }
+
+    #[test]
+    fn decode_token_transfer() {
+        // Owners and mint are resolved from the token balances.
+        let transaction = token_transaction(3_500_000, 2_039_280);
+        let ix = token_instruction(&[&[3u8][..], &1_500_000u64.to_le_bytes()].concat(), &[SOURCE_TOKEN, DESTINATION_TOKEN, FUNDING]);
+        let decoded = decode_token_instruction(&ix, &transaction).unwrap();
+        assert_eq!(decoded.action, "Transfer");
+        assert_eq!((decoded.source.as_str(), decoded.destination.as_str()), (SOURCE_TOKEN, DESTINATION_TOKEN));
+        assert_eq!((decoded.source_owner.as_deref(), decoded.destination_owner.as_deref()), (Some(FUNDING), Some(RECIPIENT)));
+        assert_eq!((decoded.mint.as_deref(), decoded.amount, decoded.decimals), (Some(MINT), Some(1_500_000), Some(6)));
+        assert_eq!((decoded.ui_amount().as_deref(), decoded.lamports), (Some("1.5"), None));
+    }
+
+    #[test]
+    fn decode_token_transfer_checked() {
+        let transaction = token_transaction(0, 2_039_280);
+        let data = [&[12u8][..], &5_000_000u64.to_le_bytes(), &[6]].concat();
+        let ix = token_instruction(&data, &[SOURCE_TOKEN, MINT, DESTINATION_TOKEN, FUNDING]);
+        let decoded = decode_token_instruction(&ix, &transaction).unwrap();
+        assert_eq!(decoded.action, "TransferChecked");
+        assert_eq!((decoded.source.as_str(), decoded.destination.as_str()), (SOURCE_TOKEN, DESTINATION_TOKEN));
+        assert_eq!((decoded.mint.as_deref(), decoded.amount, decoded.decimals), (Some(MINT), Some(5_000_000), Some(6)));
+    }
+
+    #[test]
+    fn decode_token_mint_and_burn() {
+        let transaction = token_transaction(5_000_000, 2_039_280);
+        let amount = 42u64.to_le_bytes();
+        let mint = decode_token_instruction(&token_instruction(&[&[7u8][..], &amount].concat(), &[MINT, DESTINATION_TOKEN, FUNDING]), &transaction).unwrap();
+        assert_eq!((mint.action.as_str(), mint.source.as_str(), mint.destination.as_str()), ("MintTo", MINT, DESTINATION_TOKEN));
+        assert_eq!((mint.source_owner.as_deref(), mint.amount), (Some(FUNDING), Some(42)));
+
+        let burn = decode_token_instruction(&token_instruction(&[&[8u8][..], &amount].concat(), &[SOURCE_TOKEN, MINT, FUNDING]), &transaction).unwrap();
+        assert_eq!((burn.action.as_str(), burn.source.as_str(), burn.destination.as_str()), ("Burn", SOURCE_TOKEN, MINT));
+        assert_eq!((burn.mint.as_deref(), burn.amount), (Some(MINT), Some(42)));
+    }
+
+    #[test]
+    fn decode_token_close_account() {
+        // Closing reports the lamports reclaimed and no token amount.
+        let transaction = token_transaction(0, 2_039_280);
+        let decoded = decode_token_instruction(&token_instruction(&[9], &[SOURCE_TOKEN, FUNDING, FUNDING]), &transaction).unwrap();
+        assert_eq!(decoded.action, "CloseAccount");
+        assert_eq!((decoded.source.as_str(), decoded.destination.as_str()), (SOURCE_TOKEN, FUNDING));
+        assert_eq!((decoded.amount, decoded.lamports, decoded.ui_amount()), (None, Some(2_039_280), None));
+    }
+
+    #[test]
+    fn reject_unsupported_token_instruction() {
+        let transaction = token_transaction(0, 2_039_280);
+        // Approve, truncated transfer and a transfer missing its accounts.
+        assert!(decode_token_instruction(&token_instruction(&[&[4u8][..], &1u64.to_le_bytes()].concat(), &[SOURCE_TOKEN, FUNDING, FUNDING]), &transaction).is_none());
+        assert!(decode_token_instruction(&token_instruction(&[3, 1], &[SOURCE_TOKEN, DESTINATION_TOKEN, FUNDING]), &transaction).is_none());
+        assert!(decode_token_instruction(&token_instruction(&[&[3u8][..], &1u64.to_le_bytes()].concat(), &[SOURCE_TOKEN]), &transaction).is_none());
+        assert!(decode_token_instruction(&instruction(&[&[3u8][..], &1u64.to_le_bytes()].concat(), &[SOURCE_TOKEN, DESTINATION_TOKEN]), &transaction).is_none());
     }
-}
\ No newline at end of file
+}
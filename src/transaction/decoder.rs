@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::path::Path;
+use serde::Serialize;
+use serde_json::json;
+
+use super::anchor::AnchorDecoder;
+use super::decode::{decode_system_instruction, decode_token_instruction, transfer_from_system_instruction};
+use super::instructions::{InstructionTransferWrapper, InstructionTokenTransferWrapper, SystemInstructionDecoded};
+use super::transaction::{InstructionProcessed, TransactionDetail};
+use crate::constants::{SYSTEM_PROGRAM, TOKEN_PROGRAM};
+use crate::errors::IdlError;
+
+/// Output of an `InstructionDecoder`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "instruction")]
+pub enum DecodedInstruction {
+    /// Instruction that moves lamports from one account to another.
+    Transfer(InstructionTransferWrapper),
+    /// SPL Token instruction that moves, mints or burns tokens.
+    TokenTransfer(InstructionTokenTransferWrapper),
+    /// System program instruction that does not move lamports.
+    System(SystemInstructionDecoded),
+    /// Instruction decoded by a program specific decoder.
+    Program(InstructionGeneric)
+}
+
+impl DecodedInstruction {
+    /// Return the lamport transfer, if the instruction is one.
+    pub fn as_transfer(&self) -> Option<&InstructionTransferWrapper> {
+        match self {
+            Self::Transfer(transfer) => Some(transfer),
+            _ => None
+        }
+    }
+
+    /// Return the lamport transfer, consuming the decoded instruction.
+    pub fn into_transfer(self) -> Option<InstructionTransferWrapper> {
+        match self {
+            Self::Transfer(transfer) => Some(transfer),
+            _ => None
+        }
+    }
+}
+
+/// Program agnostic decoded instruction, for decoders of programs without a dedicated record.
+/// Contains `program`, `action`, `accounts`, `args`, `block_time` and `signature` fields.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InstructionGeneric {
+    /// Program account that executed the instruction.
+    pub program: String,
+    /// The instruction performed.
+    pub action: String,
+    /// Accounts passed to the instruction, as `(name, account)` pairs.
+    pub accounts: Vec<(String, String)>,
+    /// Decoded instruction arguments.
+    pub args: serde_json::Value,
+    /// Estimated production time, as UNIX timestamp.
+    pub block_time: u64,
+    /// Transaction signature.
+    pub signature: String,
+    /// Index of the outer transaction instruction.
+    pub instruction_index: usize,
+    /// Stack position within the inner instructions of `instruction_index`, `None` for outer instructions.
+    pub inner_index: Option<usize>
+}
+
+/// Action of the instructions of programs without a registered decoder.
+pub const UNKNOWN_ACTION: &str = "Unknown";
+
+impl InstructionGeneric {
+    /// Return the undecoded instruction `ix`, with its accounts named by position and its raw data as
+    /// the `data` argument.
+    pub fn unknown(ix: &InstructionProcessed) -> Self {
+        InstructionGeneric {
+            program: ix.executer.clone(),
+            action: UNKNOWN_ACTION.to_string(),
+            accounts: ix.accounts.iter().enumerate().map(|(index, account)| (index.to_string(), account.clone())).collect(),
+            args: json!({ "data": ix.data }),
+            block_time: ix.block_time,
+            signature: ix.signature.clone(),
+            instruction_index: ix.instruction_index,
+            inner_index: ix.inner_index
+        }
+    }
+}
+
+/// Decoder for the instructions of a single program.
+pub trait InstructionDecoder: Send + Sync {
+    /// Program account, as a base-58 encoded string, whose instructions are decoded.
+    fn program_id(&self) -> &str;
+
+    /// Decode an instruction executed by `program_id`. Returns `None` if the instruction is not supported.
+    ///
+    /// # Arguments
+    ///
+    /// * `ix` - Instruction invoked during transaction processing.
+    /// * `transaction` - Transaction the instruction belongs to.
+    fn decode(&self, ix: &InstructionProcessed, transaction: &TransactionDetail) -> Option<DecodedInstruction>;
+}
+
+/// Decoder for system program instructions.
+pub struct SystemProgramDecoder;
+
+impl InstructionDecoder for SystemProgramDecoder {
+    fn program_id(&self) -> &str {
+        SYSTEM_PROGRAM
+    }
+
+    fn decode(&self, ix: &InstructionProcessed, _transaction: &TransactionDetail) -> Option<DecodedInstruction> {
+        let decoded = decode_system_instruction(ix)?;
+        match transfer_from_system_instruction(ix, &decoded) {
+            Some(transfer) => Some(DecodedInstruction::Transfer(transfer)),
+            None => Some(DecodedInstruction::System(decoded))
+        }
+    }
+}
+
+/// Decoder for SPL Token program instructions.
+pub struct TokenProgramDecoder;
+
+impl InstructionDecoder for TokenProgramDecoder {
+    fn program_id(&self) -> &str {
+        TOKEN_PROGRAM
+    }
+
+    fn decode(&self, ix: &InstructionProcessed, transaction: &TransactionDetail) -> Option<DecodedInstruction> {
+        decode_token_instruction(ix, transaction).map(DecodedInstruction::TokenTransfer)
+    }
+}
+
+/// Collection of `InstructionDecoder`s keyed by program account.
+pub struct DecoderRegistry {
+    decoders: HashMap<String, Box<dyn InstructionDecoder>>
+}
+
+impl DecoderRegistry {
+    /// Return an empty registry.
+    pub fn new() -> Self {
+        DecoderRegistry { decoders: HashMap::new() }
+    }
+
+    /// Register a decoder, replacing any decoder previously registered for the same program.
+    pub fn register(&mut self, decoder: Box<dyn InstructionDecoder>) {
+        self.decoders.insert(decoder.program_id().to_string(), decoder);
+    }
+
+    /// Return the default registry with an `AnchorDecoder` registered for each Anchor IDL file, see
+    /// `AnchorDecoder::from_file`. The program of each IDL is read from its address.
+    ///
+    /// # Arguments
+    ///
+    /// * `idls` - Paths to Anchor IDL JSON files.
+    pub fn with_idls<P: AsRef<Path>>(idls: &[P]) -> Result<Self, IdlError> {
+        let mut registry = Self::default();
+        for idl in idls {
+            registry.register(Box::new(AnchorDecoder::from_file(idl, None)?));
+        }
+        Ok(registry)
+    }
+
+    /// Return the decoder registered for `program`, if any.
+    pub fn get(&self, program: &str) -> Option<&dyn InstructionDecoder> {
+        self.decoders.get(program).map(|decoder| decoder.as_ref())
+    }
+
+    /// Decode an instruction with the decoder registered for its executing program. Instructions of
+    /// programs without a registered decoder are returned undecoded, see `InstructionGeneric::unknown`.
+    /// Returns `None` if the registered decoder does not support the instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `ix` - Instruction invoked during transaction processing.
+    /// * `transaction` - Transaction the instruction belongs to.
+    pub fn decode(&self, ix: &InstructionProcessed, transaction: &TransactionDetail) -> Option<DecodedInstruction> {
+        match self.get(&ix.executer) {
+            Some(decoder) => decoder.decode(ix, transaction),
+            None => Some(DecodedInstruction::Program(InstructionGeneric::unknown(ix)))
+        }
+    }
+}
+
+impl Default for DecoderRegistry {
+    /// Return a registry with the system program and SPL Token program decoders registered.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(SystemProgramDecoder));
+        registry.register(Box::new(TokenProgramDecoder));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM_A: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+    const PROGRAM_B: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+    const ACCOUNT: &str = "AS5CnoZ7ezMhUXphn32H5Y2YAXnChdZAjjwywYYXuhgP";
+
+    /// Decoder returning every instruction of `program` as `action`.
+    struct FixedDecoder {
+        program: &'static str,
+        action: &'static str
+    }
+
+    impl InstructionDecoder for FixedDecoder {
+        fn program_id(&self) -> &str {
+            self.program
+        }
+
+        fn decode(&self, ix: &InstructionProcessed, _transaction: &TransactionDetail) -> Option<DecodedInstruction> {
+            Some(DecodedInstruction::Program(InstructionGeneric { action: self.action.to_string(), ..InstructionGeneric::unknown(ix) }))
+        }
+    }
+
+    fn instruction(program: &str) -> InstructionProcessed {
+        InstructionProcessed {
+            executer: program.to_string(),
+            accounts: vec![ACCOUNT.to_string()],
+            data: "3Bxs41hAcYbghSB9".to_string(),
+            block_time: 1650000000,
+            slot: 10,
+            signature: "signature".to_string(),
+            instruction_index: 1,
+            inner_index: Some(0)
+        }
+    }
+
+    fn transaction() -> TransactionDetail {
+        serde_json::from_value(json!({"slot": 10, "blockTime": 1650000000, "transaction": null, "meta": null})).unwrap()
+    }
+
+    /// Action of `program`'s instruction decoded by `registry`.
+    fn action(registry: &DecoderRegistry, program: &str) -> Option<String> {
+        match registry.decode(&instruction(program), &transaction()) {
+            Some(DecodedInstruction::Program(decoded)) => Some(decoded.action),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn decode_with_registered_decoder() {
+        let mut registry = DecoderRegistry::new();
+        registry.register(Box::new(FixedDecoder { program: PROGRAM_A, action: "A" }));
+        registry.register(Box::new(FixedDecoder { program: PROGRAM_B, action: "B" }));
+        assert_eq!(action(&registry, PROGRAM_A).as_deref(), Some("A"));
+        assert_eq!(action(&registry, PROGRAM_B).as_deref(), Some("B"));
+
+        registry.register(Box::new(FixedDecoder { program: PROGRAM_A, action: "A2" }));
+        assert_eq!(action(&registry, PROGRAM_A).as_deref(), Some("A2"));
+        assert_eq!(action(&registry, PROGRAM_B).as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn decode_unknown_program() {
+        let registry = DecoderRegistry::default();
+        let decoded = registry.decode(&instruction(PROGRAM_A), &transaction());
+        assert_eq!(decoded, Some(DecodedInstruction::Program(InstructionGeneric {
+            program: PROGRAM_A.to_string(),
+            action: UNKNOWN_ACTION.to_string(),
+            accounts: vec![("0".to_string(), ACCOUNT.to_string())],
+            args: json!({ "data": "3Bxs41hAcYbghSB9" }),
+            block_time: 1650000000,
+            signature: "signature".to_string(),
+            instruction_index: 1,
+            inner_index: Some(0)
+        })));
+        // The default registry decodes system program transfers.
+        let transfer = registry.decode(&InstructionProcessed { accounts: vec![ACCOUNT.to_string(), PROGRAM_B.to_string()], ..instruction(SYSTEM_PROGRAM) }, &transaction());
+        assert_eq!(transfer.and_then(|x| x.into_transfer()).map(|x| x.lamports), Some(5));
+    }
+}
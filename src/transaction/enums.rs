@@ -17,7 +17,8 @@ pub enum SystemProgramInstruction {
     Allocate,
     AllocateWithSeed,
     AssignWithSeed,
-    TransferWithSeed
+    TransferWithSeed,
+    UpgradeNonceAccount
 }
 
 impl fmt::Display for SystemProgramInstruction {
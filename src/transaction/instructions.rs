@@ -1,5 +1,11 @@
 use pyo3::prelude::*;
 use serde::{Serialize, Deserialize};
+use solana_program::pubkey::Pubkey;
+use std::fmt;
+
+use crate::constants::SOL_DECIMALS;
+use crate::hash::SData;
+use crate::parsing::amount::{format_amount, to_py_decimal};
 
 /// Solana Transfer system instruction construct.
 /// Contains `lamports` field.
@@ -9,17 +15,168 @@ pub struct InstructionTransfer {
     pub lamports: u64
 }
 
+/// Solana CreateAccount system instruction construct.
+/// Contains `lamports`, `space` and `owner` fields.
+#[derive(Serialize, Deserialize)]
+pub struct InstructionCreateAccount {
+    /// Number of lamports to transfer to the new account.
+    pub lamports: u64,
+    /// Number of bytes of memory to allocate.
+    pub space: u64,
+    /// Address of program that will own the new account.
+    pub owner: Pubkey
+}
+
+/// Solana Assign system instruction construct.
+/// Contains `owner` field.
+#[derive(Serialize, Deserialize)]
+pub struct InstructionAssign {
+    /// Owner program account.
+    pub owner: Pubkey
+}
+
+/// Solana CreateAccountWithSeed system instruction construct.
+/// Contains `base`, `seed`, `lamports`, `space` and `owner` fields.
+#[derive(Serialize, Deserialize)]
+pub struct InstructionCreateAccountWithSeed {
+    /// Base public key.
+    pub base: Pubkey,
+    /// String of ASCII chars, no longer than `Pubkey::MAX_SEED_LEN`.
+    pub seed: String,
+    /// Number of lamports to transfer to the new account.
+    pub lamports: u64,
+    /// Number of bytes of memory to allocate.
+    pub space: u64,
+    /// Owner program account address.
+    pub owner: Pubkey
+}
+
+/// Solana Allocate system instruction construct.
+/// Contains `space` field.
+#[derive(Serialize, Deserialize)]
+pub struct InstructionAllocate {
+    /// Number of bytes of memory to allocate.
+    pub space: u64
+}
+
+/// Solana AllocateWithSeed system instruction construct.
+/// Contains `base`, `seed`, `space` and `owner` fields.
+#[derive(Serialize, Deserialize)]
+pub struct InstructionAllocateWithSeed {
+    /// Base public key.
+    pub base: Pubkey,
+    /// String of ASCII chars, no longer than `Pubkey::MAX_SEED_LEN`.
+    pub seed: String,
+    /// Number of bytes of memory to allocate.
+    pub space: u64,
+    /// Owner program account.
+    pub owner: Pubkey
+}
+
+/// Solana AssignWithSeed system instruction construct.
+/// Contains `base`, `seed` and `owner` fields.
+#[derive(Serialize, Deserialize)]
+pub struct InstructionAssignWithSeed {
+    /// Base public key.
+    pub base: Pubkey,
+    /// String of ASCII chars, no longer than `Pubkey::MAX_SEED_LEN`.
+    pub seed: String,
+    /// Owner program account.
+    pub owner: Pubkey
+}
+
+/// Solana TransferWithSeed system instruction construct.
+/// Contains `lamports`, `from_seed` and `from_owner` fields.
+#[derive(Serialize, Deserialize)]
+pub struct InstructionTransferWithSeed {
+    /// Number of lamports to transfer.
+    pub lamports: u64,
+    /// Seed to use to derive the funding account address.
+    pub from_seed: String,
+    /// Owner to use to derive the funding account address.
+    pub from_owner: Pubkey
+}
+
+/// A decoded system program instruction, with its arguments and the accounts
+/// it references named by their role. Public keys are base-58 encoded strings.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum SystemInstructionDecoded {
+    /// Create a new account.
+    CreateAccount { funding: String, new_account: String, lamports: u64, space: u64, owner: String },
+    /// Assign account to a program.
+    Assign { account: String, owner: String },
+    /// Transfer lamports.
+    Transfer { funding: String, recipient: String, lamports: u64 },
+    /// Create a new account at an address derived from a base pubkey and a seed.
+    CreateAccountWithSeed { funding: String, new_account: String, base: String, seed: String, lamports: u64, space: u64, owner: String },
+    /// Consumes a stored nonce, replacing it with a successor.
+    AdvanceNonceAccount { nonce_account: String, nonce_authority: String },
+    /// Withdraw funds from a nonce account.
+    WithdrawNonceAccount { nonce_account: String, recipient: String, nonce_authority: String, lamports: u64 },
+    /// Drive state of uninitialized nonce account to initialized, setting the nonce value.
+    InitializeNonceAccount { nonce_account: String, nonce_authority: String },
+    /// Change the entity authorized to execute nonce instructions on the account.
+    AuthorizeNonceAccount { nonce_account: String, nonce_authority: String, new_authority: String },
+    /// Allocate space in a (possibly new) account without funding.
+    Allocate { account: String, space: u64 },
+    /// Allocate space for and assign an account at an address derived from a base pubkey and a seed.
+    AllocateWithSeed { account: String, base: String, seed: String, space: u64, owner: String },
+    /// Assign account to a program based on a seed.
+    AssignWithSeed { account: String, base: String, seed: String, owner: String },
+    /// Transfer lamports from a derived address.
+    TransferWithSeed { funding: String, base: String, recipient: String, lamports: u64, from_seed: String, from_owner: String },
+    /// One-time idempotent upgrade of legacy nonce versions.
+    UpgradeNonceAccount { nonce_account: String }
+}
+
+impl SystemInstructionDecoded {
+    /// Return the `source`, `destination` and `lamports` of the instruction if it moves lamports
+    /// between two accounts, otherwise `None`.
+    pub fn lamport_transfer(&self) -> Option<(&String, &String, u64)> {
+        match self {
+            Self::CreateAccount { funding, new_account, lamports, .. } => Some((funding, new_account, *lamports)),
+            Self::Transfer { funding, recipient, lamports } => Some((funding, recipient, *lamports)),
+            Self::CreateAccountWithSeed { funding, new_account, lamports, .. } => Some((funding, new_account, *lamports)),
+            Self::WithdrawNonceAccount { nonce_account, recipient, lamports, .. } => Some((nonce_account, recipient, *lamports)),
+            Self::TransferWithSeed { funding, recipient, lamports, .. } => Some((funding, recipient, *lamports)),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for SystemInstructionDecoded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::CreateAccount { .. } => "CreateAccount",
+            Self::Assign { .. } => "Assign",
+            Self::Transfer { .. } => "Transfer",
+            Self::CreateAccountWithSeed { .. } => "CreateAccountWithSeed",
+            Self::AdvanceNonceAccount { .. } => "AdvanceNonceAccount",
+            Self::WithdrawNonceAccount { .. } => "WithdrawNonceAccount",
+            Self::InitializeNonceAccount { .. } => "InitializeNonceAccount",
+            Self::AuthorizeNonceAccount { .. } => "AuthorizeNonceAccount",
+            Self::Allocate { .. } => "Allocate",
+            Self::AllocateWithSeed { .. } => "AllocateWithSeed",
+            Self::AssignWithSeed { .. } => "AssignWithSeed",
+            Self::TransferWithSeed { .. } => "TransferWithSeed",
+            Self::UpgradeNonceAccount { .. } => "UpgradeNonceAccount"
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// Wrapper for a decoded transfer system instruction with python bindings.
-/// Contains `amount`, `source`, `destination`, `action`, `block_time`, `human_time` and `signature`.
-#[derive(Debug, Clone, PartialEq)]
+/// Contains `lamports`, `source`, `destination`, `action`, `block_time`, `human_time`, `slot`,
+/// `signature`, `instruction_index` and `inner_index`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 #[pyclass]
 pub struct InstructionTransferWrapper {
     /// Program account that executed the instruction.
     #[pyo3(get)]
     pub program: String,
-    /// Amount being transferred from `source` to `destination` account.
+    /// Number of lamports being transferred from `source` to `destination` account.
     #[pyo3(get)]
-    pub amount: f64,
+    pub lamports: u64,
     /// Source account.
     #[pyo3(get)]
     pub source: String,
@@ -35,7 +192,125 @@ pub struct InstructionTransferWrapper {
     /// Estimated production time, as human readable formatted string.
     #[pyo3(get)]
     pub human_time: String,
+    /// The slot the transaction was processed in.
+    #[pyo3(get)]
+    pub slot: u64,
     /// Transaction signature.
     #[pyo3(get)]
-    pub signature: String
-}
\ No newline at end of file
+    pub signature: String,
+    /// Index of the outer transaction instruction.
+    #[pyo3(get)]
+    pub instruction_index: usize,
+    /// Stack position within the inner instructions of `instruction_index`, `None` for outer instructions.
+    #[pyo3(get)]
+    pub inner_index: Option<usize>
+}
+
+impl InstructionTransferWrapper {
+    /// Identity of the instruction within its transaction.
+    pub fn id(&self) -> SData {
+        SData {
+            signature: self.signature.clone(),
+            instruction_index: self.instruction_index,
+            inner_index: self.inner_index
+        }
+    }
+
+    /// Amount transferred in SOL, as an exact decimal string.
+    pub fn sol_amount(&self) -> String {
+        format_amount(self.lamports, SOL_DECIMALS)
+    }
+}
+
+#[pymethods]
+impl InstructionTransferWrapper {
+    /// Amount transferred in SOL, as a python `Decimal`.
+    #[getter(amount)]
+    fn py_amount(&self, py: Python) -> PyResult<PyObject> {
+        to_py_decimal(py, &self.sol_amount())
+    }
+}
+
+/// Wrapper for a decoded SPL Token program instruction with python bindings.
+/// Contains `program`, `action`, `mint`, `amount`, `lamports`, `decimals`, `source`, `source_owner`,
+/// `destination`, `destination_owner`, `block_time`, `human_time`, `slot`, `signature`, `instruction_index` and
+/// `inner_index` fields.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[pyclass]
+pub struct InstructionTokenTransferWrapper {
+    /// Program account that executed the instruction.
+    #[pyo3(get)]
+    pub program: String,
+    /// The instruction performed.
+    #[pyo3(get)]
+    pub action: String,
+    /// Token mint, if it could be resolved.
+    #[pyo3(get)]
+    pub mint: Option<String>,
+    /// Amount of tokens in base units, ignoring decimals. `None` for `CloseAccount` instructions, which
+    /// move no tokens.
+    #[pyo3(get)]
+    pub amount: Option<u64>,
+    /// Lamports moved to `destination`, i.e. the rent reclaimed by `CloseAccount` instructions.
+    #[pyo3(get)]
+    pub lamports: Option<u64>,
+    /// Token mint decimals, if they could be resolved.
+    #[pyo3(get)]
+    pub decimals: Option<u8>,
+    /// Source token account, or the mint for `MintTo` instructions.
+    #[pyo3(get)]
+    pub source: String,
+    /// Owner of the source token account, or the mint authority for `MintTo` instructions.
+    #[pyo3(get)]
+    pub source_owner: Option<String>,
+    /// Destination token account, or the mint for `Burn` instructions.
+    #[pyo3(get)]
+    pub destination: String,
+    /// Owner of the destination token account.
+    #[pyo3(get)]
+    pub destination_owner: Option<String>,
+    /// Estimated production time, as UNIX timestamp.
+    #[pyo3(get)]
+    pub block_time: u64,
+    /// Estimated production time, as human readable formatted string.
+    #[pyo3(get)]
+    pub human_time: String,
+    /// The slot the transaction was processed in.
+    #[pyo3(get)]
+    pub slot: u64,
+    /// Transaction signature.
+    #[pyo3(get)]
+    pub signature: String,
+    /// Index of the outer transaction instruction.
+    #[pyo3(get)]
+    pub instruction_index: usize,
+    /// Stack position within the inner instructions of `instruction_index`, `None` for outer instructions.
+    #[pyo3(get)]
+    pub inner_index: Option<usize>
+}
+
+impl InstructionTokenTransferWrapper {
+    /// Identity of the instruction within its transaction.
+    pub fn id(&self) -> SData {
+        SData {
+            signature: self.signature.clone(),
+            instruction_index: self.instruction_index,
+            inner_index: self.inner_index
+        }
+    }
+
+    /// Amount of tokens accounting for decimals, as an exact decimal string.
+    /// Returns `None` if no tokens were moved or the mint decimals could not be resolved.
+    pub fn ui_amount(&self) -> Option<String> {
+        self.amount.zip(self.decimals).map(|(amount, decimals)| format_amount(amount, decimals))
+    }
+}
+
+#[pymethods]
+impl InstructionTokenTransferWrapper {
+    /// Amount of tokens accounting for decimals, as a python `Decimal`.
+    #[getter(ui_amount)]
+    fn py_ui_amount(&self, py: Python) -> PyResult<Option<PyObject>> {
+        self.ui_amount().map(|amount| to_py_decimal(py, &amount)).transpose()
+    }
+}
@@ -1,9 +1,9 @@
 use std::str::FromStr;
 use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
 use solana_sdk::signature::Signature;
-use solana_transaction_status::UiTransactionEncoding;
 use crate::errors::TransactionDetailError;
+use crate::signature::signature::SignatureDetail;
+use crate::source::source::ChainSource;
 
 impl TransactionDetail {
 
@@ -12,12 +12,33 @@ impl TransactionDetail {
     /// # Arguments
     /// 
     /// * `signature` - Transaction signature encoded as a baes-58 string slice.
-    /// * `client` - RPC client.
+    /// * `source` - Chain data source, e.g. an RPC client or a `CachedSource` to read the transaction
+    ///   from a cache first.
     /// ```
-    pub fn new(signature: &str, client: &RpcClient) -> Self {
+    pub fn new(signature: &str, source: &dyn ChainSource) -> Result<Self, TransactionDetailError> {
+        Self::process_transaction(signature, source)
+    }
 
-        let tx = Self::process_transaction(signature, client).unwrap();
-        tx
+    /// Create `TransactionDetail` objects for a page of signatures, in order. The transactions are read
+    /// together, e.g. with a single batch request, and each fails on its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `signatures` - Transaction signatures.
+    /// * `source` - Chain data source, e.g. an RPC client.
+    pub fn from_signatures(signatures: &[SignatureDetail], source: &dyn ChainSource) -> Vec<Result<Self, TransactionDetailError>> {
+        let parsed: Vec<Result<Signature, TransactionDetailError>> = signatures.iter()
+            .map(|x| Signature::from_str(&x.signature).map_err(|err| TransactionDetailError::ParseSignatureError(err.to_string())))
+            .collect();
+        let valid: Vec<Signature> = parsed.iter().filter_map(|x| x.as_ref().ok().copied()).collect();
+        let mut fetched = source.get_transactions(&valid).into_iter();
+        parsed.into_iter().map(|signature| {
+            signature?;
+            match fetched.next() {
+                Some(serialized) => Self::deserialize(&serialized?),
+                None => Err(TransactionDetailError::ClientError("Missing transaction".to_string()))
+            }
+        }).collect()
     }
 
     /// Print raw transaction to output.
@@ -29,7 +50,7 @@ impl TransactionDetail {
     pub fn get_account_keys(&self) -> Result<&Vec<String>, TransactionDetailError> {
         match &self.transaction {
             Some(tx) => Ok(&tx.message.account_keys),
-            None => return Err(TransactionDetailError::TransactionDataError("Failed to get account keys.".to_string()))
+            None => Err(TransactionDetailError::TransactionDataError("Failed to get account keys.".to_string()))
         }
     }
 
@@ -37,32 +58,45 @@ impl TransactionDetail {
     pub fn get_transaction_signature_id(&self) -> Result<&String, TransactionDetailError> {
         match &self.transaction {
             Some(signature) => Ok(&signature.signatures[0]),
-            None => return Err(TransactionDetailError::TransactionDataError("Failed to get transaction signature".to_string()))
+            None => Err(TransactionDetailError::TransactionDataError("Failed to get transaction signature".to_string()))
         }
     }
 
+    /// Return the slot the transaction was processed in.
+    pub fn get_slot(&self) -> u64 {
+        self.slot
+    }
+
     /// Return time when transaction was processed as UNIX timestamp.
     pub fn get_block_time(&self) -> Result<&u64, TransactionDetailError> {
         match &self.block_time {
             Some(time) => Ok(time),
-            None => return Err(TransactionDetailError::TransactionDataError("Failed to get blocktime".to_string()))
+            None => Err(TransactionDetailError::TransactionDataError("Failed to get blocktime".to_string()))
         }
     }
 
+    /// Return the lamport balance of `account` before and after the transaction was processed, if
+    /// the transaction used the account.
+    pub fn get_lamport_balances(&self, account: &str) -> Option<(u64, u64)> {
+        let meta = self.meta.as_ref()?;
+        let index = self.get_account_keys().ok()?.iter().position(|key| key == account)?;
+        Some((*meta.pre_balances.get(index)?, *meta.post_balances.get(index)?))
+    }
+
     /// Return an array of processed token balances before the transaction was processed.
     /// Wrapped in `TransactionTokenProcessed` objects.
     pub fn get_token_balances_before(&self) -> Result<Vec<TransactionTokenProcessed>, TransactionDetailError> {
         let mut processed = vec![];
-        let meta = self.get_meta().unwrap();
+        let meta = self.get_meta()?;
         match &meta.pre_token_balances {
             Some(token_data) => {
                 for token in token_data {
-                    let data = self.get_token_entry_processed(&token);
+                    let data = self.get_token_entry_processed(token)?;
                     processed.push(data);
                 }
                 Ok(processed)
             },
-            None => return Err(TransactionDetailError::TransactionMetaError("Failed to get pre token data".to_string()))
+            None => Err(TransactionDetailError::TransactionMetaError("Failed to get pre token data".to_string()))
         }
     }
 
@@ -70,52 +104,71 @@ impl TransactionDetail {
     /// Wrapped in `TransactionTokenProcessed` objects.
     pub fn get_token_balances_after(&self) -> Result<Vec<TransactionTokenProcessed>, TransactionDetailError> {
         let mut processed = vec![];
-        let meta = self.get_meta().unwrap();
+        let meta = self.get_meta()?;
         match &meta.post_token_balances {
             Some(token_data) => {
                 for token in token_data {
-                    let data = self.get_token_entry_processed(&token);
+                    let data = self.get_token_entry_processed(token)?;
                     processed.push(data);
                 }
                 Ok(processed)
             },
-            None => return Err(TransactionDetailError::TransactionMetaError("Failed to get post token data".to_string()))
+            None => Err(TransactionDetailError::TransactionMetaError("Failed to get post token data".to_string()))
         }
     }
 
+    /// Return the error the transaction failed with, `None` if it succeeded.
+    pub fn get_error(&self) -> Option<&serde_json::Value> {
+        self.meta.as_ref().and_then(|meta| meta.err.as_ref())
+    }
+
     /// Return an array of instructions invoked during transaction processing.
     /// This includes the parent instructions (and their corresponding inner instructions)
-    pub fn get_instructions_processed(&self) -> Vec<InstructionProcessed> {
+    /// Failed transactions have no instructions processed, as their effects were rolled back.
+    pub fn get_instructions_processed(&self) -> Result<Vec<InstructionProcessed>, TransactionDetailError> {
         let mut instructions_processed = vec![];
-        let account_keys = self.get_account_keys().unwrap();
-        let block_time = self.get_block_time().unwrap();
-        let transaction_signature = self.get_transaction_signature_id().unwrap();
-        let instructions_parent = self.get_ixs().unwrap();
-        let instructions_inner = self.get_ixs_inner().unwrap();
+        // The effects of a failed transaction are rolled back, only its fee is charged.
+        if self.get_error().is_some() {
+            return Ok(instructions_processed);
+        }
+        let account_keys = self.get_account_keys()?;
+        let block_time = self.get_block_time()?;
+        let slot = self.get_slot();
+        let transaction_signature = self.get_transaction_signature_id()?;
+        let instructions_parent = self.get_ixs()?;
+        // Transactions without recorded inner instructions are processed as having none.
+        let instructions_inner = match self.get_ixs_inner() {
+            Ok(ixs) => &ixs[..],
+            Err(TransactionDetailError::TransactionInnerInstruction(_)) => &[],
+            Err(err) => return Err(err)
+        };
 
         // Looping over parent instructions!
         for (instruction_idx, instruction_parent) in instructions_parent.iter().enumerate() {
 
-            let data = InstructionProcessed::new(&instruction_parent, &account_keys, &block_time, &transaction_signature);
+            let position = (instruction_idx, None);
+            let data = InstructionProcessed::new(instruction_parent, account_keys, block_time, slot, transaction_signature, position)?;
             instructions_processed.push(data);
 
-            // Looping over inner instructions if exist!
-            //if let [_first, .., _intsruction_idx] = &instructions_inner[..] {
-            if instructions_inner.len() > instruction_idx {
-                for instruction_data in &instructions_inner[instruction_idx].instructions {
-                    let data = InstructionProcessed::new(&instruction_data, &account_keys, block_time, &transaction_signature);
-                    instructions_processed.push(data);
-                }
+            // Inner instructions are only recorded for parent instructions that invoked other programs,
+            // so they are matched on their `index` rather than their position in the array.
+            let inner = instructions_inner.iter()
+                .filter(|inner| inner.index as usize == instruction_idx)
+                .flat_map(|inner| &inner.instructions);
+            for (inner_idx, instruction_data) in inner.enumerate() {
+                let position = (instruction_idx, Some(inner_idx));
+                let data = InstructionProcessed::new(instruction_data, account_keys, block_time, slot, transaction_signature, position)?;
+                instructions_processed.push(data);
             }
         }
-        instructions_processed
+        Ok(instructions_processed)
     }
 
     /// Return transaction status metadata, `TransactionMetaData`, object.
     fn get_meta(&self) -> Result<&TransactionMetaData, TransactionDetailError> {
         match &self.meta {
             Some(meta) => Ok(meta),
-            None => return Err(TransactionDetailError::TransactionMetaError("Failed to get meta data".to_string()))
+            None => Err(TransactionDetailError::TransactionMetaError("Failed to get meta data".to_string()))
         }
     }
 
@@ -123,55 +176,55 @@ impl TransactionDetail {
     fn get_ixs(&self) -> Result<&Vec<TransactionInstructionData>, TransactionDetailError> {
         match &self.transaction {
             Some(ixs) => Ok(&ixs.message.instructions),
-            None => return Err(TransactionDetailError::TransactionInstruction("Failed to get instructions".to_string()))
+            None => Err(TransactionDetailError::TransactionInstruction("Failed to get instructions".to_string()))
         }
     }
 
     /// Returns an array of `TransactionInnerInstruction` objects.
     fn get_ixs_inner(&self) -> Result<&Vec<TransactionInnerInstruction>, TransactionDetailError> {
-        let meta = self.get_meta().unwrap();
+        let meta = self.get_meta()?;
         match &meta.inner_instructions {
             Some(ixs) => Ok(ixs),
-            None => return Err(TransactionDetailError::TransactionInnerInstruction("Failed to get inner instructions".to_string()))
+            None => Err(TransactionDetailError::TransactionInnerInstruction("Failed to get inner instructions".to_string()))
         }
     }
 
     /// Process `TransactionToken` into a public `TransactionTokenProcessed` wrapper.
-    fn get_token_entry_processed(&self, entry: &TransactionToken) -> TransactionTokenProcessed {
-        let accounts = self.get_account_keys().unwrap();
-        TransactionTokenProcessed {
-            token_account: accounts[entry.account_index].clone(),
+    fn get_token_entry_processed(&self, entry: &TransactionToken) -> Result<TransactionTokenProcessed, TransactionDetailError> {
+        let accounts = self.get_account_keys()?;
+        let token_account = match accounts.get(entry.account_index) {
+            Some(token_account) => token_account,
+            None => return Err(TransactionDetailError::TransactionMetaError(format!("Token account index {} out of range", entry.account_index)))
+        };
+        Ok(TransactionTokenProcessed {
+            token_account: token_account.clone(),
             token_mint: entry.mint.clone(),
             owner: entry.owner.clone(),
             amount: entry.ui_token_amount.ui_amount_string.clone(),
+            amount_raw: entry.ui_token_amount.amount.clone(),
             decimals: entry.ui_token_amount.decimals
-        }
+        })
     }
 
     /// Process a raw transaction into a `TransactionDetail` object.
-    fn process_transaction(signature: &str, client: &RpcClient) -> Result<Self, TransactionDetailError> {
+    fn process_transaction(signature: &str, source: &dyn ChainSource) -> Result<Self, TransactionDetailError> {
 
         let signature = match Signature::from_str(signature) {
             Ok(signature) => signature,
             Err(err) => return Err(TransactionDetailError::ParseSignatureError(err.to_string()))
         };
 
-        let tx = match client.get_transaction(&signature, UiTransactionEncoding::Json) {
-            Ok(tx) => tx,
-            Err(err) => return Err(TransactionDetailError::ClientError(err.to_string()))
-        };
+        let serialized = source.get_transaction(&signature)?;
 
-        let serialized = match serde_json::to_string(&tx) {
-            Ok(serialized) => serialized,
-            Err(err) => return Err(TransactionDetailError::SerializeError(err.to_string()))
-        };
-        
-        let deserialized = match serde_json::from_str(&serialized) {
-            Ok(deserialized) => deserialized,
-            Err(err) => return Err(TransactionDetailError::DeserializeError(err.to_string()))  
-        };
+        Self::deserialize(&serialized)
+    }
 
-        Ok(deserialized)
+    /// Deserialize a raw transaction into a `TransactionDetail` object.
+    fn deserialize(serialized: &str) -> Result<Self, TransactionDetailError> {
+        match serde_json::from_str(serialized) {
+            Ok(deserialized) => Ok(deserialized),
+            Err(err) => Err(TransactionDetailError::DeserializeError(err.to_string()))
+        }
     }
 }
 
@@ -238,10 +291,13 @@ struct TransactionHeader {
     num_readonly_unsigned_accounts: u32
 }
 
-/// Contained within an `TransactionDetail` object. Contains `fee`, `pre_balances`, `post_balances`,
+/// Contained within an `TransactionDetail` object. Contains `err`, `fee`, `pre_balances`, `post_balances`,
 /// `inner_instructions`, `log_messages`, `pre_token_balances` and `post_token_balances` fields.
 #[derive(Deserialize, Serialize, Debug)]
 struct TransactionMetaData {
+    #[serde(default)]
+    /// Error the transaction failed with, `None` if it succeeded.
+    err: Option<serde_json::Value>,
     /// Fee this transaction was charged.
     fee: u64,
     #[serde(rename = "preBalances")]
@@ -318,7 +374,7 @@ struct TransactionTokenDetails {
 }
 
 /// Wrapper for `TransactionToken and TransactionTokenDetails` in a transaction.
-/// Contains `token_account`, `token_mint`, `amount`, `decimals` and `owner` fields.
+/// Contains `token_account`, `token_mint`, `amount`, `amount_raw`, `decimals` and `owner` fields.
 #[derive(Debug)]
 pub struct TransactionTokenProcessed {
     /// Token account pubkey encoded as a base-58 string.
@@ -327,6 +383,8 @@ pub struct TransactionTokenProcessed {
     pub token_mint: String,
     /// Token account balance, accounting for decimals.
     pub amount: Option<String>,
+    /// Token account balance in base units, ignoring decimals.
+    pub amount_raw: Option<String>,
     /// Token mint decimals.
     pub decimals: Option<f32>,
     /// Token account owner.
@@ -334,7 +392,7 @@ pub struct TransactionTokenProcessed {
 }
 
 /// Wrapper for `TransactionInstructionData`.
-/// Contains `executer`, `accounts`, `data`, `block_time` and `signature` fields.
+/// Contains `executer`, `accounts`, `data`, `block_time`, `slot`, `signature`, `instruction_index` and `inner_index` fields.
 #[derive(Debug)]
 pub struct InstructionProcessed {
     /// Program account that executed this instruction as a base-58 encoded string.
@@ -345,8 +403,14 @@ pub struct InstructionProcessed {
     pub data: String,
     /// UNIX timestamp when transaction was processed.
     pub block_time: u64,
+    /// The slot the transaction was processed in.
+    pub slot: u64,
     /// Transaction signature id encoded as a base-58 string.
-    pub signature: String
+    pub signature: String,
+    /// Index of the outer transaction instruction.
+    pub instruction_index: usize,
+    /// Stack position within the inner instructions of `instruction_index`, `None` for outer instructions.
+    pub inner_index: Option<usize>
 }
 
 impl InstructionProcessed {
@@ -357,23 +421,85 @@ impl InstructionProcessed {
     /// * `instruction_data` - Instruction data within a transaction.
     /// * `account_keys` - An array of accounts participating within a transaction.
     /// * `block_time` - UNIX time when transaction was processed.
+    /// * `slot` - The slot the transaction was processed in.
     /// * `signature` - Transaction signature id.
+    /// * `position` - Outer instruction index and inner instruction stack position.
     /// ```
-    fn new(instruction_data: &TransactionInstructionData, account_keys: &Vec<String>, block_time: &u64, signature: &String) -> Self {
+    fn new(instruction_data: &TransactionInstructionData, account_keys: &[String], block_time: &u64, slot: u64, signature: &str, position: (usize, Option<usize>)) -> Result<Self, TransactionDetailError> {
 
+        let account = |index: u32| match account_keys.get(index as usize) {
+            Some(account) => Ok(account.clone()),
+            None => Err(TransactionDetailError::TransactionInstruction(format!("Account index {} out of range", index)))
+        };
         let mut accounts_participating = vec![];
         for account_idx in &instruction_data.accounts {
-            let index = usize::try_from(*account_idx).unwrap();
-            accounts_participating.push(account_keys[index].clone());
+            accounts_participating.push(account(*account_idx)?);
         }
-        let exec_idx = usize::try_from(instruction_data.program_id_index).unwrap();
-        let processed = InstructionProcessed {
-            executer: account_keys[exec_idx].clone(),
+
+        Ok(InstructionProcessed {
+            executer: account(instruction_data.program_id_index)?,
             accounts: accounts_participating,
             data: instruction_data.data.clone(),
-            block_time: block_time.clone(),
-            signature: signature.clone()
-        };
-        processed
+            block_time: *block_time,
+            slot,
+            signature: signature.to_string(),
+            instruction_index: position.0,
+            inner_index: position.1
+        })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::SYSTEM_PROGRAM;
+
+    const FUNDING: &str = "AS5CnoZ7ezMhUXphn32H5Y2YAXnChdZAjjwywYYXuhgP";
+    const RECIPIENT: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    /// Serialized transaction with a single transfer of 1 lamport, failed with `err`.
+    fn transaction(err: &str) -> TransactionDetail {
+        let serialized = format!(r#"{{
+            "slot": 10,
+            "blockTime": 1650000000,
+            "transaction": {{
+                "signatures": ["5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW"],
+                "message": {{
+                    "header": {{"numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 1}},
+                    "accountKeys": ["{}", "{}", "{}"],
+                    "recentBlockhash": "EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N",
+                    "instructions": [{{"programIdIndex": 2, "accounts": [0, 1], "data": "3Bxs412MvVNQj175"}}]
+                }}
+            }},
+            "meta": {{
+                "err": {},
+                "fee": 5000,
+                "preBalances": [10000, 0, 1],
+                "postBalances": [4999, 1, 1],
+                "innerInstructions": [],
+                "logMessages": [],
+                "preTokenBalances": [],
+                "postTokenBalances": []
+            }}
+        }}"#, FUNDING, RECIPIENT, SYSTEM_PROGRAM, err);
+        TransactionDetail::deserialize(&serialized).unwrap()
+    }
+
+    #[test]
+    fn instructions_of_successful_transaction() {
+        let transaction = transaction("null");
+        assert!(transaction.get_error().is_none());
+        let instructions = transaction.get_instructions_processed().unwrap();
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(instructions[0].executer, SYSTEM_PROGRAM);
+        assert_eq!(instructions[0].accounts, vec![FUNDING.to_string(), RECIPIENT.to_string()]);
+        assert_eq!((instructions[0].instruction_index, instructions[0].inner_index), (0, None));
+    }
+
+    #[test]
+    fn failed_transaction_has_no_instructions() {
+        let transaction = transaction(r#"{"InstructionError": [0, {"Custom": 1}]}"#);
+        assert!(transaction.get_error().is_some());
+        assert!(transaction.get_instructions_processed().unwrap().is_empty());
+    }
+}